@@ -0,0 +1,225 @@
+//! Answers kept for a while and served again to every client asking the same, so a dashboard
+//! polling the API does not turn into a scrape of the archive per client.
+
+use anyhow::Result;
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// what a listing is kept for unless configured otherwise
+pub const LISTING_TTL: Duration = Duration::from_secs(60);
+/// what a thread, its tree or its attachments are kept for unless configured otherwise, they
+/// change only as replies come in
+pub const THREAD_TTL: Duration = Duration::from_secs(600);
+
+/// prefix of the keys, to share a Redis with other applications
+const KEY_PREFIX: &str = "pgdevhub:";
+
+/// Where the answers are kept. The calls block, they are made on tokio's blocking thread pool.
+pub trait ResponseCache: Send + Sync {
+    /// what was kept under `key`, unless it expired
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// keep `value` under `key` for `ttl`
+    fn put(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()>;
+}
+
+/// The cache at `url`: a Redis server for `redis://` urls, with the `redis` feature, or the
+/// memory of the server for `memory`.
+pub fn open(url: &str) -> Result<Arc<dyn ResponseCache>> {
+    if url == "memory" {
+        return Ok(Arc::new(Memory::default()));
+    }
+    if url.starts_with("redis://") || url.starts_with("rediss://") {
+        #[cfg(feature = "redis")]
+        return Ok(Arc::new(Redis::open(url)?));
+        #[cfg(not(feature = "redis"))]
+        anyhow::bail!("{url}: a Redis cache needs pgdevhub built with the redis feature");
+    }
+    anyhow::bail!("unknown response cache '{url}', give a redis:// url or memory")
+}
+
+/// Answers kept in the memory of the server, lost when it stops.
+#[derive(Default)]
+pub struct Memory {
+    entries: Mutex<HashMap<String, (Instant, Vec<u8>)>>,
+}
+
+impl ResponseCache for Memory {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let entries = self.entries.lock().unwrap();
+        Ok(entries
+            .get(key)
+            .filter(|(expires, _)| *expires > Instant::now())
+            .map(|(_, value)| value.clone()))
+    }
+
+    fn put(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let mut entries = self.entries.lock().unwrap();
+        let now = Instant::now();
+        entries.retain(|_, (expires, _)| *expires > now);
+        entries.insert(key.to_string(), (now + ttl, value.to_vec()));
+        Ok(())
+    }
+}
+
+/// Answers kept in Redis, shared by every server pointed at it and kept across restarts.
+#[cfg(feature = "redis")]
+pub struct Redis {
+    client: redis::Client,
+    // opened on first use, and again after it failed
+    connection: Mutex<Option<redis::Connection>>,
+}
+
+#[cfg(feature = "redis")]
+impl Redis {
+    pub fn open(url: &str) -> Result<Self> {
+        Ok(Redis {
+            client: redis::Client::open(url)?,
+            connection: Mutex::new(None),
+        })
+    }
+
+    fn query<T: redis::FromRedisValue>(&self, command: &redis::Cmd) -> Result<T> {
+        let mut connection = self.connection.lock().unwrap();
+        let open = match connection.as_mut() {
+            Some(open) => open,
+            None => connection.insert(self.client.get_connection()?),
+        };
+        let result = command.query(open);
+        if result.is_err() {
+            *connection = None;
+        }
+        Ok(result?)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl ResponseCache for Redis {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        self.query(redis::cmd("GET").arg(key))
+    }
+
+    fn put(&self, key: &str, value: &[u8], ttl: Duration) -> Result<()> {
+        let ttl_ms = ttl.as_millis().max(1) as u64;
+        self.query(redis::cmd("SET").arg(key).arg(value).arg("PX").arg(ttl_ms))
+    }
+}
+
+/// A cache of the API's answers and what each kind of answer is kept for. A zero TTL keeps none
+/// of that kind.
+#[derive(Clone)]
+pub struct ResponseCaching {
+    pub cache: Arc<dyn ResponseCache>,
+    /// listings of threads, like `/api/active-subjects`, and the feed
+    pub listing_ttl: Duration,
+    /// `/api/thread/...`, `/api/discussion` and `/api/attachments`
+    pub thread_ttl: Duration,
+}
+
+impl ResponseCaching {
+    pub fn new(cache: Arc<dyn ResponseCache>) -> Self {
+        ResponseCaching {
+            cache,
+            listing_ttl: LISTING_TTL,
+            thread_ttl: THREAD_TTL,
+        }
+    }
+
+    fn ttl(&self, path: &str) -> Duration {
+        let thread_paths = ["/api/thread/", "/api/discussion", "/api/attachments"];
+        if thread_paths.iter().any(|prefix| path.starts_with(prefix)) {
+            self.thread_ttl
+        } else {
+            self.listing_ttl
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct CachedResponse {
+    content_type: Option<String>,
+    body: String,
+}
+
+/// answer a GET from the cache when an answer to the same url and `Accept` is kept there, with
+/// `X-Cache: hit`, and keep the successful answers that are not. a cache that fails is only
+/// logged, the request is then answered as if there were none
+pub(super) async fn serve_cached(
+    State(caching): State<ResponseCaching>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ttl = caching.ttl(request.uri().path());
+    if request.method() != axum::http::Method::GET || ttl.is_zero() {
+        return next.run(request).await;
+    }
+    let accept = request.headers().get(header::ACCEPT);
+    let key = format!(
+        "{KEY_PREFIX}{} {}",
+        request.uri(),
+        accept.and_then(|accept| accept.to_str().ok()).unwrap_or("")
+    );
+
+    let cache = caching.cache.clone();
+    let lookup = key.clone();
+    match tokio::task::spawn_blocking(move || cache.get(&lookup)).await {
+        Result::Ok(Result::Ok(Some(kept))) => {
+            if let Result::Ok(kept) = serde_json::from_slice::<CachedResponse>(&kept) {
+                let mut response =
+                    (StatusCode::OK, [("x-cache", "hit")], kept.body).into_response();
+                if let Some(content_type) = kept
+                    .content_type
+                    .and_then(|value| HeaderValue::from_str(&value).ok())
+                {
+                    response
+                        .headers_mut()
+                        .insert(header::CONTENT_TYPE, content_type);
+                }
+                return response;
+            }
+        }
+        Result::Ok(Result::Ok(None)) => {}
+        Result::Ok(Err(err)) => tracing::warn!("response cache lookup failed: {err:#}"),
+        Err(err) => tracing::warn!("response cache lookup failed: {err}"),
+    }
+
+    let response = next.run(request).await;
+    if response.status() != StatusCode::OK {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let body = match axum::body::to_bytes(body, usize::MAX).await {
+        Result::Ok(body) => body,
+        Err(err) => {
+            return super::ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, err.into())
+                .into_response()
+        }
+    };
+    // the answers are JSON, CSV or XML, anything else is not kept
+    if let Result::Ok(text) = std::str::from_utf8(&body) {
+        let kept = CachedResponse {
+            content_type: parts
+                .headers
+                .get(header::CONTENT_TYPE)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            body: text.to_string(),
+        };
+        let cache = caching.cache.clone();
+        let stored =
+            tokio::task::spawn_blocking(move || cache.put(&key, &serde_json::to_vec(&kept)?, ttl))
+                .await;
+        match stored {
+            Result::Ok(Result::Ok(())) => {}
+            Result::Ok(Err(err)) => tracing::warn!("failed to cache a response: {err:#}"),
+            Err(err) => tracing::warn!("failed to cache a response: {err}"),
+        }
+    }
+    Response::from_parts(parts, body.into())
+}
@@ -0,0 +1,1531 @@
+//! JSON API over the scraper.
+//!
+//! Every handler awaits the scraper on the request's own task, so a client that goes away
+//! stops its scrape.
+//!
+//! Every endpoint scrapes the server's mailing list unless asked for another one with
+//! `?list=pgsql-bugs`.
+//!
+//! With a [`cache::ResponseCaching`], answers are kept in memory or in Redis and served again
+//! to whoever asks the same before they expire.
+//!
+//! [`serve`] can keep the default listings ready in the background, see [`warm`].
+
+pub mod cache;
+pub mod warm;
+
+use crate::{
+    add_replies, add_reply_counts, attachments_between, build_thread_tree, classify_thread,
+    download_attachments, feed, first_new_subjects_between, get_active_subjects_between,
+    get_new_subjects_between, get_thread_detail, is_not_found, latest_new_threads,
+    message_position, new_subjects_multi, parse_message_ref, permalink, thread_for_discussion_link,
+    AttachmentContent, ContentStats, EmailThread, EmailThreadDetail, Fetcher, MailingList,
+    MessageId, References, ThreadAttachment, ThreadCategory, ThreadDetailOptions, ThreadTree,
+};
+use anyhow::Result;
+use axum::extract::{FromRequestParts, Path, Query, Request, State};
+use axum::http::request::Parts;
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use base64::Engine;
+use chrono::{NaiveDateTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use tower_http::services::{ServeDir, ServeFile};
+use tower_http::trace::TraceLayer;
+
+/// attachments up to this size are embedded when a thread detail asks for inline attachments
+const INLINE_ATTACHMENT_MAX_BYTES: usize = 64 * 1024;
+/// threads listed by `/api/latest` unless asked otherwise, and the most it lists
+const LATEST_DEFAULT_LIMIT: usize = 25;
+const LATEST_MAX_LIMIT: usize = 500;
+/// threads listed by `/api/new-subjects/preview` unless asked otherwise, and the most it lists
+const PREVIEW_DEFAULT_K: usize = 10;
+const PREVIEW_MAX_K: usize = 100;
+
+/// listings kept by [`serve_stale`], the oldest one is dropped for a new one past this
+const STALE_CACHE_ENTRIES: usize = 256;
+
+/// messages of a thread tree fetched when the request does not say, each one costs a fetch
+const TREE_DEFAULT_MAX: usize = 200;
+/// most messages of a thread tree a request may ask for
+const TREE_MAX_LIMIT: usize = 1000;
+
+#[derive(Debug, Serialize)]
+struct EmailThreadResponse {
+    id: String,
+    // the message on postgresql.org
+    url: String,
+    subject: String,
+    author: String,
+    datetime: NaiveDateTime,
+    category: ThreadCategory,
+    // only counted when asked for
+    reply_count: Option<usize>,
+    list: MailingList,
+    // only listed when asked for
+    replies: Option<ReplyIds>,
+}
+
+/// reply ids of a listed thread. a list in JSON, and joined by spaces in CSV, which has no room for
+/// lists within a column
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum ReplyIds {
+    List(Vec<String>),
+    Joined(String),
+}
+
+impl From<EmailThread> for EmailThreadResponse {
+    fn from(thread: EmailThread) -> Self {
+        EmailThreadResponse {
+            category: classify_thread(&thread, None),
+            url: permalink(&thread.id),
+            id: thread.id,
+            subject: thread.subject,
+            author: thread.author,
+            datetime: thread.datetime,
+            reply_count: thread.reply_count,
+            list: thread.list,
+            replies: thread.replies.map(ReplyIds::List),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct AttachmentResponse {
+    name: String,
+    href: String,
+    // bytes, as listed by the archive
+    size: Option<u64>,
+    // only present for small attachments when inlining was requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    inline: Option<InlineAttachment>,
+}
+
+/// an attachment listed with the message it came with
+#[derive(Debug, Serialize)]
+struct MessageAttachmentResponse {
+    message_id: String,
+    name: String,
+    href: String,
+    size: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct InlineAttachment {
+    content_type: String,
+    size: usize,
+    // base64 of the attachment bytes
+    data: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ThreadNodeResponse {
+    id: String,
+    author: String,
+    datetime: Option<NaiveDateTime>,
+    parent: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ThreadTreeResponse {
+    nodes: Vec<ThreadNodeResponse>,
+    truncated: bool,
+    total: usize,
+}
+
+impl From<ThreadTree> for ThreadTreeResponse {
+    fn from(tree: ThreadTree) -> Self {
+        ThreadTreeResponse {
+            nodes: tree
+                .nodes
+                .into_iter()
+                .map(|node| ThreadNodeResponse {
+                    id: node.id,
+                    author: node.author,
+                    datetime: node.datetime,
+                    parent: node.parent,
+                })
+                .collect(),
+            truncated: tree.truncated,
+            total: tree.total,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct EmailThreadDetailResponse {
+    id: String,
+    // the message on postgresql.org
+    url: String,
+    subject: String,
+    datetime: NaiveDateTime,
+    author_name: String,
+    author_email: String,
+    content: String,
+    content_missing: bool,
+    // what the author wrote, without the messages quoted in `content`
+    content_new_text: String,
+    stats: ContentStats,
+    // diffs pasted into the body rather than attached
+    inline_patches: Vec<String>,
+    references: References,
+    attachments: Vec<AttachmentResponse>,
+    // the sum of the known attachment sizes
+    total_attachment_bytes: u64,
+    replies: Vec<String>,
+    // where the message is in its thread, from 1 for the starter to `thread_size`
+    position: usize,
+    thread_size: usize,
+    category: ThreadCategory,
+    headers: BTreeMap<String, String>,
+}
+
+impl EmailThreadDetailResponse {
+    /// `category` is passed along since `detail` has usually given up its attachments already
+    fn new(
+        detail: EmailThreadDetail,
+        category: ThreadCategory,
+        attachments: Vec<AttachmentResponse>,
+    ) -> Result<Self> {
+        let (position, thread_size) = message_position(&detail.replies, &detail.id)?;
+        Ok(EmailThreadDetailResponse {
+            url: permalink(&detail.id),
+            id: detail.id,
+            subject: detail.subject,
+            datetime: detail.datetime,
+            author_name: detail.author_name,
+            author_email: detail.author_email,
+            content: detail.content,
+            content_missing: detail.content_missing,
+            content_new_text: detail.content_new_text,
+            stats: detail.stats,
+            inline_patches: detail.inline_patches,
+            references: detail.references,
+            total_attachment_bytes: attachments
+                .iter()
+                .filter_map(|attachment| attachment.size)
+                .sum(),
+            attachments,
+            replies: detail.replies,
+            position,
+            thread_size,
+            category,
+            headers: detail.headers,
+        })
+    }
+}
+
+impl TryFrom<EmailThreadDetail> for EmailThreadDetailResponse {
+    type Error = anyhow::Error;
+
+    fn try_from(mut detail: EmailThreadDetail) -> Result<Self> {
+        let category = detail.category();
+        let attachments = std::mem::take(&mut detail.attachments)
+            .into_iter()
+            .map(|attachment| AttachmentResponse {
+                name: attachment.name,
+                href: attachment.href,
+                size: attachment.size,
+                inline: None,
+            })
+            .collect();
+        EmailThreadDetailResponse::new(detail, category, attachments)
+    }
+}
+
+/// a failed request. scraping failures are reported as a bad gateway since postgresql.org is
+/// our upstream.
+struct ApiError {
+    status: StatusCode,
+    error: anyhow::Error,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, error: anyhow::Error) -> Self {
+        ApiError { status, error }
+    }
+
+    fn bad_request(error: anyhow::Error) -> Self {
+        ApiError::new(StatusCode::BAD_REQUEST, error)
+    }
+}
+
+impl<E: Into<anyhow::Error>> From<E> for ApiError {
+    fn from(err: E) -> Self {
+        let error = err.into();
+        // everything else went wrong between us and the archive
+        let status = if is_not_found(&error) {
+            StatusCode::NOT_FOUND
+        } else {
+            StatusCode::BAD_GATEWAY
+        };
+        ApiError { status, error }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = Json(serde_json::json!({ "error": format!("{:#}", self.error) }));
+        (self.status, body).into_response()
+    }
+}
+
+/// representations a listing can be served in, picked from the `Accept` header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Json,
+    Csv,
+}
+
+impl Format {
+    /// the first acceptable format in the order the client listed them, JSON when the client
+    /// does not care
+    fn negotiate(headers: &HeaderMap) -> Result<Format, ApiError> {
+        let Some(accept) = headers.get(header::ACCEPT) else {
+            return Ok(Format::Json);
+        };
+        let accept = accept
+            .to_str()
+            .map_err(|err| ApiError::bad_request(err.into()))?;
+        for media_range in accept.split(',') {
+            let mut params = media_range.split(';');
+            let media_type = params.next().unwrap_or("").trim();
+            let refused = params.any(|param| {
+                param
+                    .trim()
+                    .strip_prefix("q=")
+                    .and_then(|q| q.parse::<f32>().ok())
+                    == Some(0.0)
+            });
+            if refused {
+                continue;
+            }
+            match media_type.to_ascii_lowercase().as_str() {
+                "application/json" | "application/*" | "*/*" => return Ok(Format::Json),
+                "text/csv" | "text/*" => return Ok(Format::Csv),
+                _ => {}
+            }
+        }
+        Err(ApiError::new(
+            StatusCode::NOT_ACCEPTABLE,
+            anyhow::anyhow!("cannot serve any of '{accept}', try application/json or text/csv"),
+        ))
+    }
+
+    /// `threads` as the rows of a listing in this format
+    fn thread_rows(self, threads: Vec<EmailThread>) -> Vec<EmailThreadResponse> {
+        let mut rows: Vec<EmailThreadResponse> = threads.into_iter().map(Into::into).collect();
+        if self == Format::Csv {
+            for row in &mut rows {
+                if let Some(ReplyIds::List(ids)) = &row.replies {
+                    row.replies = Some(ReplyIds::Joined(ids.join(" ")));
+                }
+            }
+        }
+        rows
+    }
+
+    fn render<T: Serialize>(self, rows: &[T]) -> Result<Response, ApiError> {
+        match self {
+            Format::Json => Ok(Json(rows).into_response()),
+            Format::Csv => {
+                let mut writer = csv::Writer::from_writer(Vec::new());
+                for row in rows {
+                    writer.serialize(row)?;
+                }
+                let body = writer.into_inner().map_err(|err| err.into_error())?;
+                Ok(([(header::CONTENT_TYPE, "text/csv; charset=utf-8")], body).into_response())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RangeQuery {
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+}
+
+impl RangeQuery {
+    /// the requested range, by default the `default_span` up to what `fetcher` says is now
+    fn range(&self, fetcher: &Fetcher, default_span: TimeDelta) -> (NaiveDateTime, NaiveDateTime) {
+        let end = self.end.unwrap_or_else(|| fetcher.now());
+        let start = self.start.unwrap_or(end - default_span);
+        (start, end)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ReplyCountsQuery {
+    #[serde(default)]
+    reply_counts: bool,
+    // the reply ids too, which also gives the counts
+    #[serde(default)]
+    replies: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct NoticesQuery {
+    // keep moderation and bounce notices in the results
+    #[serde(default)]
+    include_notices: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListQuery {
+    // the one list scraped instead of the server's, like `pgsql-bugs`
+    list: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ListsQuery {
+    // comma-separated list names, like `pgsql-hackers,pgsql-bugs`
+    lists: Option<String>,
+}
+
+impl ListsQuery {
+    /// the requested lists, none when the parameter is missing
+    fn lists(&self) -> Result<Option<Vec<MailingList>>> {
+        self.lists
+            .as_deref()
+            .map(|lists| lists.split(',').map(str::parse).collect())
+            .transpose()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct LatestQuery {
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PreviewQuery {
+    k: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TreeQuery {
+    max: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+struct DiscussionQuery {
+    // a `Discussion:` link, an archive url or a message-id
+    r#ref: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ThreadQuery {
+    #[serde(default)]
+    inline_attachments: bool,
+    // metadata and reply ids only, no content or attachments
+    #[serde(default)]
+    compact: bool,
+}
+
+/// the router's fetcher with a budget of its own, so the budget limits each API request rather
+/// than the whole lifetime of the server, switched to the list asked for with `?list=`
+struct RunFetcher(Fetcher);
+
+#[axum::async_trait]
+impl FromRequestParts<Fetcher> for RunFetcher {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        fetcher: &Fetcher,
+    ) -> Result<Self, Self::Rejection> {
+        let fetcher = fetcher.clone().with_new_budget();
+        let Query(query) = Query::<ListQuery>::try_from_uri(&parts.uri)
+            .map_err(|err| ApiError::bad_request(err.into()))?;
+        Ok(RunFetcher(match query.list {
+            Some(list) => fetcher.with_list(list.parse().map_err(ApiError::bad_request)?),
+            None => fetcher,
+        }))
+    }
+}
+
+/// the last good answer to each listing request, for when the archive is down
+#[derive(Default)]
+struct StaleCache {
+    responses: Mutex<HashMap<String, StaleResponse>>,
+}
+
+struct StaleResponse {
+    // when it was fetched
+    as_of: NaiveDateTime,
+    content_type: Option<HeaderValue>,
+    body: axum::body::Bytes,
+}
+
+/// keep the listings that succeed, and answer a request the archive failed for with the last
+/// good listing for the same url and `Accept` instead of a 502. that one comes as a
+/// `203 Non-Authoritative Information` with `X-Stale: true` and `X-As-Of` saying when it was
+/// fetched. they are headers rather than fields as a listing is a bare list in JSON and in CSV
+async fn serve_stale(
+    State((fetcher, cache)): State<(Fetcher, Arc<StaleCache>)>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let accept = request.headers().get(header::ACCEPT);
+    let key = format!(
+        "{} {}",
+        request.uri(),
+        accept.and_then(|accept| accept.to_str().ok()).unwrap_or("")
+    );
+    let response = next.run(request).await;
+    match response.status() {
+        StatusCode::OK => {
+            let (parts, body) = response.into_parts();
+            let body = match axum::body::to_bytes(body, usize::MAX).await {
+                Result::Ok(body) => body,
+                Err(err) => {
+                    return ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, err.into())
+                        .into_response()
+                }
+            };
+            let mut responses = cache.responses.lock().unwrap();
+            if responses.len() >= STALE_CACHE_ENTRIES && !responses.contains_key(&key) {
+                let oldest = responses
+                    .iter()
+                    .min_by_key(|(_, response)| response.as_of)
+                    .map(|(key, _)| key.clone());
+                responses.remove(&oldest.unwrap());
+            }
+            responses.insert(
+                key,
+                StaleResponse {
+                    as_of: fetcher.now(),
+                    content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+                    body: body.clone(),
+                },
+            );
+            Response::from_parts(parts, body.into())
+        }
+        StatusCode::BAD_GATEWAY => {
+            let responses = cache.responses.lock().unwrap();
+            let Some(stale) = responses.get(&key) else {
+                return response;
+            };
+            let mut stale_response = (
+                StatusCode::NON_AUTHORITATIVE_INFORMATION,
+                [
+                    ("x-stale", "true".to_string()),
+                    (
+                        "x-as-of",
+                        stale.as_of.format("%Y-%m-%dT%H:%M:%S").to_string(),
+                    ),
+                    (
+                        header::WARNING.as_str(),
+                        "110 - \"Response is Stale\"".to_string(),
+                    ),
+                ],
+                stale.body.clone(),
+            )
+                .into_response();
+            if let Some(content_type) = &stale.content_type {
+                stale_response
+                    .headers_mut()
+                    .insert(header::CONTENT_TYPE, content_type.clone());
+            }
+            stale_response
+        }
+        _ => response,
+    }
+}
+
+/// the API routes, nested under `base_path` when the server sits behind a reverse proxy at a
+/// path like `/pgdev`. with a `static_dir`, every other path is served from that directory, and
+/// paths without a file get its `index.html` so a single-page frontend can route them itself.
+/// with `caching`, the answers of the API routes are kept and served again
+pub fn create_router(
+    fetcher: Fetcher,
+    base_path: Option<&str>,
+    static_dir: Option<&std::path::Path>,
+    caching: Option<cache::ResponseCaching>,
+) -> Router {
+    // listings are served stale while the archive is down, thread pages are not
+    let stale_cache = (fetcher.clone(), Arc::new(StaleCache::default()));
+    let mut router = Router::new()
+        .route("/api/new-subjects", get(new_subjects))
+        .route("/api/new-subjects/preview", get(new_subjects_preview))
+        .route("/api/latest", get(latest))
+        .route("/api/active-subjects", get(active_subjects))
+        .route_layer(middleware::from_fn_with_state(stale_cache, serve_stale))
+        .route("/api/thread/:id", get(thread_detail))
+        .route("/api/thread/:id/tree", get(thread_tree))
+        .route("/api/discussion", get(discussion))
+        .route("/api/attachments", get(attachments))
+        .route("/feed/active.xml", get(active_feed));
+    if let Some(caching) = caching {
+        router = router.route_layer(middleware::from_fn_with_state(caching, cache::serve_cached));
+    }
+    let mut router = router.layer(TraceLayer::new_for_http()).with_state(fetcher);
+    if let Some(dir) = static_dir {
+        let index = ServeFile::new(dir.join("index.html"));
+        router = router.fallback_service(ServeDir::new(dir).fallback(index));
+    }
+    match base_path.map(|path| path.trim_matches('/')) {
+        Some(path) if !path.is_empty() => Router::new().nest(&format!("/{path}"), router),
+        _ => router,
+    }
+}
+
+pub async fn serve(
+    addr: &str,
+    fetcher: Fetcher,
+    base_path: Option<&str>,
+    static_dir: Option<&std::path::Path>,
+    caching: Option<cache::ResponseCaching>,
+    warm_every: Option<std::time::Duration>,
+) -> Result<()> {
+    let mut app = create_router(fetcher.clone(), base_path, static_dir, caching);
+    if let Some(every) = warm_every {
+        app = warm::keep_warm(app, fetcher, base_path, every);
+    }
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    tracing::info!(
+        "listening on {}{}",
+        listener.local_addr()?,
+        base_path.unwrap_or("")
+    );
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn new_subjects(
+    RunFetcher(mut fetcher): RunFetcher,
+    Query(query): Query<RangeQuery>,
+    Query(counts): Query<ReplyCountsQuery>,
+    Query(lists): Query<ListsQuery>,
+    Query(notices): Query<NoticesQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let format = Format::negotiate(&headers)?;
+    if notices.include_notices {
+        fetcher = fetcher.include_notices();
+    }
+    let (start, end) = query.range(&fetcher, TimeDelta::days(7));
+    let lists = lists.lists().map_err(ApiError::bad_request)?;
+    let mut threads = match lists {
+        Some(lists) => new_subjects_multi(&fetcher, &lists, start, end).await?,
+        None => get_new_subjects_between(&fetcher, start, end).await?,
+    };
+    if counts.replies {
+        add_replies(&fetcher, &mut threads).await;
+    } else if counts.reply_counts {
+        add_reply_counts(&fetcher, &mut threads).await;
+    }
+    format.render(&format.thread_rows(threads))
+}
+
+/// the first `k` new subjects of the range, for showing something before the whole range is
+/// listed by `/api/new-subjects`
+async fn new_subjects_preview(
+    RunFetcher(fetcher): RunFetcher,
+    Query(query): Query<RangeQuery>,
+    Query(preview): Query<PreviewQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let format = Format::negotiate(&headers)?;
+    let (start, end) = query.range(&fetcher, TimeDelta::days(7));
+    let k = preview.k.unwrap_or(PREVIEW_DEFAULT_K);
+    if k > PREVIEW_MAX_K {
+        return Err(ApiError::bad_request(anyhow::anyhow!(
+            "k must be at most {PREVIEW_MAX_K}"
+        )));
+    }
+    let threads = first_new_subjects_between(&fetcher, start, end, k).await?;
+    format.render(&format.thread_rows(threads))
+}
+
+async fn latest(
+    RunFetcher(fetcher): RunFetcher,
+    Query(query): Query<LatestQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let format = Format::negotiate(&headers)?;
+    let limit = query.limit.unwrap_or(LATEST_DEFAULT_LIMIT);
+    if limit > LATEST_MAX_LIMIT {
+        return Err(ApiError::bad_request(anyhow::anyhow!(
+            "limit must be at most {LATEST_MAX_LIMIT}"
+        )));
+    }
+    let threads = latest_new_threads(&fetcher, limit).await?;
+    format.render(&format.thread_rows(threads))
+}
+
+async fn thread_tree(
+    RunFetcher(fetcher): RunFetcher,
+    Path(id): Path<String>,
+    Query(query): Query<TreeQuery>,
+) -> Result<Json<ThreadTreeResponse>, ApiError> {
+    let id = MessageId::parse(&id).map_err(ApiError::bad_request)?;
+    let max = query.max.unwrap_or(TREE_DEFAULT_MAX);
+    if max > TREE_MAX_LIMIT {
+        return Err(ApiError::bad_request(anyhow::anyhow!(
+            "max must be at most {TREE_MAX_LIMIT}"
+        )));
+    }
+    let tree = build_thread_tree(&fetcher, &id.to_string(), Some(max)).await?;
+    Ok(Json(tree.into()))
+}
+
+async fn attachments(
+    RunFetcher(fetcher): RunFetcher,
+    Query(query): Query<RangeQuery>,
+) -> Result<Json<Vec<MessageAttachmentResponse>>, ApiError> {
+    let (start, end) = query.range(&fetcher, TimeDelta::days(7));
+    let attachments = attachments_between(&fetcher, start, end).await?;
+    Ok(Json(
+        attachments
+            .into_iter()
+            .map(|(id, attachment)| MessageAttachmentResponse {
+                message_id: id.to_string(),
+                name: attachment.name,
+                href: attachment.href,
+                size: attachment.size,
+            })
+            .collect(),
+    ))
+}
+
+/// the feed of active discussions. it carries an `ETag` and, unless it is empty, a
+/// `Last-Modified` of its newest message, and a reader asking with a matching `If-None-Match` or
+/// an `If-Modified-Since` not before that gets a bodiless 304 instead of the feed again
+async fn active_feed(
+    RunFetcher(fetcher): RunFetcher,
+    Query(query): Query<RangeQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    let (start, end) = query.range(&fetcher, TimeDelta::days(1));
+    let threads = get_active_subjects_between(&fetcher, start, end).await?;
+    let title = format!("Active discussions on {}", fetcher.list.name());
+    let feed = feed::atom_feed(&fetcher, &title, &threads, end);
+
+    let etag = feed::entity_tag(&threads);
+    let last_modified = threads.iter().map(|thread| thread.datetime).max();
+    let header_text = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    // a tag the reader sent decides alone, the date is only looked at without one
+    let not_modified = match header_text(header::IF_NONE_MATCH) {
+        Some(tags) => tags
+            .split(',')
+            .any(|tag| tag.trim() == "*" || tag.trim().trim_start_matches("W/") == etag),
+        None => header_text(header::IF_MODIFIED_SINCE)
+            .and_then(feed::parse_http_date)
+            .zip(last_modified)
+            .is_some_and(|(since, modified)| modified <= since),
+    };
+
+    let mut response = if not_modified {
+        StatusCode::NOT_MODIFIED.into_response()
+    } else {
+        (
+            [(header::CONTENT_TYPE, "application/atom+xml; charset=utf-8")],
+            feed,
+        )
+            .into_response()
+    };
+    let response_headers = response.headers_mut();
+    response_headers.insert(header::ETAG, etag.parse().unwrap());
+    if let Some(last_modified) = last_modified {
+        response_headers.insert(
+            header::LAST_MODIFIED,
+            feed::http_date(last_modified).parse().unwrap(),
+        );
+    }
+    Ok(response)
+}
+
+async fn active_subjects(
+    RunFetcher(mut fetcher): RunFetcher,
+    Query(query): Query<RangeQuery>,
+    Query(notices): Query<NoticesQuery>,
+) -> Result<Json<Vec<EmailThreadDetailResponse>>, ApiError> {
+    if notices.include_notices {
+        fetcher = fetcher.include_notices();
+    }
+    let (start, end) = query.range(&fetcher, TimeDelta::days(1));
+    let threads = get_active_subjects_between(&fetcher, start, end).await?;
+    Ok(Json(
+        threads
+            .into_iter()
+            .map(TryInto::try_into)
+            .collect::<Result<_>>()?,
+    ))
+}
+
+async fn discussion(
+    RunFetcher(fetcher): RunFetcher,
+    Query(query): Query<DiscussionQuery>,
+) -> Result<Json<EmailThreadDetailResponse>, ApiError> {
+    parse_message_ref(&query.r#ref).map_err(ApiError::bad_request)?;
+    let detail = thread_for_discussion_link(&fetcher, &query.r#ref).await?;
+    Ok(Json(detail.try_into()?))
+}
+
+async fn thread_detail(
+    RunFetcher(fetcher): RunFetcher,
+    Path(id): Path<String>,
+    Query(query): Query<ThreadQuery>,
+) -> Result<Json<EmailThreadDetailResponse>, ApiError> {
+    let id = MessageId::parse(&id).map_err(ApiError::bad_request)?;
+    let options = if query.compact {
+        ThreadDetailOptions::COMPACT
+    } else {
+        ThreadDetailOptions::default()
+    };
+    let mut detail = get_thread_detail(&fetcher, &id, options).await?;
+    if !query.inline_attachments {
+        return Ok(Json(detail.try_into()?));
+    }
+    let category = detail.category();
+    let attachments = std::mem::take(&mut detail.attachments);
+    let contents = download_attachments(&fetcher, &attachments, INLINE_ATTACHMENT_MAX_BYTES).await;
+    let attachments = attachments
+        .into_iter()
+        .zip(contents)
+        .map(|(attachment, content)| Ok(inline_attachment(attachment, content?)))
+        .collect::<Result<_>>()?;
+    Ok(Json(EmailThreadDetailResponse::new(
+        detail,
+        category,
+        attachments,
+    )?))
+}
+
+fn inline_attachment(
+    attachment: ThreadAttachment,
+    content: Option<AttachmentContent>,
+) -> AttachmentResponse {
+    let inline = content.map(|content| InlineAttachment {
+        content_type: content.content_type,
+        size: content.bytes.len(),
+        data: base64::engine::general_purpose::STANDARD.encode(&content.bytes),
+    });
+    AttachmentResponse {
+        name: attachment.name,
+        href: attachment.href,
+        size: attachment.size,
+        inline,
+    }
+}
+
+#[tokio::test]
+async fn thread_detail_inlines_small_attachments() {
+    use crate::mock_server::{MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/message-id/attachment/1/v1-0001-fix.patch" => {
+            MockResponse::new(200, "text/x-diff", "diff --git a/x b/x\n")
+        }
+        "/message-id/attachment/2/dump.tar.gz" => MockResponse::new(
+            200,
+            "application/gzip",
+            vec![0; INLINE_ATTACHMENT_MAX_BYTES + 1],
+        ),
+        path => {
+            let id = path.trim_start_matches("/message-id/");
+            let mut page = MessagePage::new(id);
+            page.attachments = vec![
+                (
+                    "v1-0001-fix.patch".to_string(),
+                    "/message-id/attachment/1/v1-0001-fix.patch".to_string(),
+                    "19 bytes".to_string(),
+                ),
+                (
+                    "dump.tar.gz".to_string(),
+                    "/message-id/attachment/2/dump.tar.gz".to_string(),
+                    "1.5 MB".to_string(),
+                ),
+            ];
+            MockResponse::html(page.render())
+        }
+    });
+
+    let router = create_router(Fetcher::new(&server.url()), None, None, None);
+    let response = router
+        .clone()
+        .oneshot(
+            Request::get("/api/thread/starter")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = router
+        .oneshot(
+            Request::get("/api/thread/starter%40example.org?inline_attachments=true")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let detail: serde_json::Value = serde_json::from_slice(&body).unwrap();
+
+    assert_eq!(detail["category"], "patch");
+    let small = &detail["attachments"][0];
+    assert_eq!(small["inline"]["content_type"], "text/x-diff");
+    let data = base64::engine::general_purpose::STANDARD
+        .decode(small["inline"]["data"].as_str().unwrap())
+        .unwrap();
+    assert_eq!(data, b"diff --git a/x b/x\n");
+
+    let large = &detail["attachments"][1];
+    assert!(large.get("inline").is_none());
+    assert_eq!(large["href"], "/message-id/attachment/2/dump.tar.gz");
+    assert_eq!(large["size"], 1_572_864);
+    assert_eq!(detail["total_attachment_bytes"], 19 + 1_572_864);
+}
+
+#[tokio::test]
+async fn new_subjects_negotiates_csv() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second, with comma", "Bob", "12:30"),
+            ],
+        )]))
+    });
+    let app = create_router(Fetcher::new(&server.url()), None, None, None);
+    let request = |accept: &str| {
+        Request::get("/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59")
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(request("text/csv;q=0.9, application/xml"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/csv; charset=utf-8"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+    let mut lines = body.lines();
+    assert_eq!(
+        lines.next(),
+        Some("id,url,subject,author,datetime,category,reply_count,list,replies")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("first,https://www.postgresql.org/message-id/first,First topic,Alice,2025-01-18T09:00:00,discussion,,pgsql-hackers,")
+    );
+    assert_eq!(
+        lines.next(),
+        Some("second,https://www.postgresql.org/message-id/second,\"Second, with comma\",Bob,2025-01-18T12:30:00,discussion,,pgsql-hackers,")
+    );
+
+    let response = app.clone().oneshot(request("*/*")).await.unwrap();
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+
+    let response = app.oneshot(request("application/xml")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_ACCEPTABLE);
+}
+
+#[tokio::test]
+async fn routes_nest_under_base_path() {
+    use crate::mock_server::{listing_page, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|_| MockResponse::html(listing_page(&[])));
+    let app = create_router(Fetcher::new(&server.url()), Some("/pgdev/"), None, None);
+    let request = |uri| Request::get(uri).body(Body::empty()).unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(request(
+            "/pgdev/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59",
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert_eq!(&body[..], b"[]");
+
+    let response = app.oneshot(request("/api/new-subjects")).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn new_subjects_default_to_the_last_week() {
+    use crate::mock_server::{listing_page, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use chrono::NaiveDate;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|_| MockResponse::html(listing_page(&[])));
+    let fetcher = Fetcher::new(&server.url()).with_clock(|| {
+        NaiveDate::from_ymd_opt(2025, 1, 18)
+            .unwrap()
+            .and_hms_opt(9, 15, 0)
+            .unwrap()
+    });
+    let response = create_router(fetcher, None, None, None)
+        .oneshot(
+            Request::get("/api/new-subjects")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(server.paths()[0], "/list/pgsql-hackers/since/202501110915");
+}
+
+#[tokio::test]
+async fn active_feed_is_well_formed_atom() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            let mut page = MessagePage::new(id);
+            page.subject = format!("Fix <{id}> & more");
+            page.content = Some(
+                "<p>Patch attached.<br><script>alert(1)</script>\
+                 <a href=\"https://example.org/?a=1&amp;b=2\">CI</a></p>"
+                    .to_string(),
+            );
+            return MockResponse::html(page.render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("first%40x", "First topic", "Alice", "09:00"),
+                ("second%40x", "Second topic", "Bob", "12:30"),
+            ],
+        )]))
+    });
+    let response = create_router(Fetcher::new(&server.url()), None, None, None)
+        .oneshot(
+            Request::get("/feed/active.xml?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "application/atom+xml; charset=utf-8"
+    );
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body = String::from_utf8(body.to_vec()).unwrap();
+
+    let doc = roxmltree::Document::parse(&body).unwrap();
+    let feed = doc.root_element();
+    assert_eq!(feed.tag_name().name(), "feed");
+    let entries: Vec<_> = feed
+        .children()
+        .filter(|node| node.has_tag_name("entry"))
+        .collect();
+    assert_eq!(entries.len(), 2);
+    let child_text = |node: roxmltree::Node, name| {
+        node.children()
+            .find(|child| child.has_tag_name(name))
+            .and_then(|child| child.text())
+            .unwrap()
+            .to_string()
+    };
+    assert_eq!(child_text(entries[0], "title"), "Fix <first%40x> & more");
+    assert_eq!(
+        child_text(entries[0], "content"),
+        "<p>Patch attached.<br><a href=\"https://example.org/?a=1&amp;b=2\">CI</a></p>"
+    );
+    let author = entries[0]
+        .children()
+        .find(|child| child.has_tag_name("author"))
+        .unwrap();
+    assert_eq!(child_text(author, "email"), "jane.doe@example.org");
+}
+
+#[tokio::test]
+async fn static_dir_serves_frontend_with_index_fallback() {
+    use crate::mock_server::{listing_page, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let dir = std::env::temp_dir().join(format!("pgdevhub-static-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("index.html"), "<div id=\"app\"></div>").unwrap();
+    std::fs::write(dir.join("app.js"), "render()").unwrap();
+    let server = MockServer::start(|_| MockResponse::html(listing_page(&[])));
+    let app = create_router(Fetcher::new(&server.url()), None, Some(&dir), None);
+    let get = |uri: &str| {
+        let app = app.clone();
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, String::from_utf8(body.to_vec()).unwrap())
+        }
+    };
+
+    let (status, body) = get("/app.js").await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "render()"));
+    let (status, body) = get("/threads/abc%40example.org").await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "<div id=\"app\"></div>")
+    );
+    let (status, body) = get("/").await;
+    assert_eq!(
+        (status, body.as_str()),
+        (StatusCode::OK, "<div id=\"app\"></div>")
+    );
+    let (status, body) =
+        get("/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59").await;
+    assert_eq!((status, body.as_str()), (StatusCode::OK, "[]"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn responses_link_to_postgresql_org() {
+    use crate::MailingList;
+    use chrono::NaiveDate;
+
+    let thread = EmailThread {
+        id: "CAH+a=b/c@mail.gmail.com".to_string(),
+        list: MailingList::default(),
+        subject: "Odd id".to_string(),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+    let expected = "https://www.postgresql.org/message-id/CAH%2Ba%3Db%2Fc%40mail.gmail.com";
+    assert_eq!(EmailThreadResponse::from(thread).url, expected);
+
+    // a slug of the same id links the same way
+    let slug = "CAH%2Ba%3Db%2Fc%40mail.gmail.com";
+    assert_eq!(permalink(slug), expected);
+    assert!(url::Url::parse(expected).is_ok());
+}
+
+#[tokio::test]
+async fn active_feed_answers_conditional_requests() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 22, 2025",
+            &[("first%40x", "First topic", "Alice", "13:59")],
+        )]))
+    });
+    let app = create_router(Fetcher::new(&server.url()), None, None, None);
+    let get = |conditional: Option<(header::HeaderName, &str)>| {
+        let mut request =
+            Request::get("/feed/active.xml?start=2025-01-22T00:00:00&end=2025-01-22T23:59:59");
+        if let Some((name, value)) = conditional {
+            request = request.header(name, value);
+        }
+        app.clone().oneshot(request.body(Body::empty()).unwrap())
+    };
+
+    let first = get(None).await.unwrap();
+    assert_eq!(first.status(), StatusCode::OK);
+    let etag = first.headers()[header::ETAG].to_str().unwrap().to_string();
+    let last_modified = first.headers()[header::LAST_MODIFIED].to_str().unwrap();
+    assert_eq!(last_modified, "Wed, 22 Jan 2025 13:59:09 GMT");
+
+    let response = get(Some((header::IF_NONE_MATCH, &etag))).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[header::ETAG], etag.as_str());
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(body.is_empty());
+
+    let response = get(Some((header::IF_MODIFIED_SINCE, last_modified)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+    let earlier = "Wed, 22 Jan 2025 13:00:00 GMT";
+    let response = get(Some((header::IF_MODIFIED_SINCE, earlier)))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let response = get(Some((header::IF_NONE_MATCH, "\"stale\"")))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn preview_fetches_only_the_pages_it_needs() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" => &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Topic b", "Bob", "10:00"),
+            ],
+            "202501181000" => &[
+                ("b", "Topic b", "Bob", "10:00"),
+                ("c", "Topic c", "Carol", "11:00"),
+                ("d", "Topic d", "Dave", "12:00"),
+            ],
+            _ => &[("e", "Topic e", "Eve", "13:00")],
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let response = create_router(Fetcher::new(&server.url()), None, None, None)
+        .oneshot(
+            Request::get(
+                "/api/new-subjects/preview?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59&k=3",
+            )
+            .body(Body::empty())
+            .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let threads: Vec<serde_json::Value> = serde_json::from_slice(&body).unwrap();
+    let ids: Vec<_> = threads.iter().map(|thread| &thread["id"]).collect();
+    assert_eq!(ids, ["a", "b", "c"]);
+    // two since-pages and a starter check per subject, none for d or the third page
+    assert_eq!(
+        server.paths(),
+        [
+            "/list/pgsql-hackers/since/202501180000",
+            "/message-id/a",
+            "/message-id/b",
+            "/list/pgsql-hackers/since/202501181000",
+            "/message-id/c",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn listings_are_served_stale_while_the_archive_is_down() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use crate::{FetchConfig, RetryPolicy};
+    use axum::body::Body;
+    use chrono::NaiveDate;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    let down = Arc::new(AtomicBool::new(false));
+    let down_ = down.clone();
+    let server = MockServer::start(move |req| {
+        if down_.load(Ordering::SeqCst) {
+            return MockResponse::disconnect();
+        }
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[("first%40x", "First topic", "Alice", "09:00")],
+        )]))
+    });
+    let once = RetryPolicy {
+        attempts: 1,
+        backoff: Duration::from_millis(1),
+    };
+    let fetcher = Fetcher::new(&server.url())
+        .with_config(FetchConfig {
+            connect_retry: once,
+            timeout_retry: once,
+            request_retry: once,
+            ..FetchConfig::default()
+        })
+        .with_clock(|| {
+            NaiveDate::from_ymd_opt(2025, 1, 19)
+                .unwrap()
+                .and_hms_opt(8, 0, 0)
+                .unwrap()
+        });
+    let app = create_router(fetcher, None, None, None);
+    let get = |uri: &str| {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let status = response.status();
+            let headers = response.headers().clone();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (status, headers, body)
+        }
+    };
+    let uri = "/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59";
+
+    let (status, _, fresh) = get(uri).await;
+    assert_eq!(status, StatusCode::OK);
+    down.store(true, Ordering::SeqCst);
+
+    let (status, headers, stale) = get(uri).await;
+    assert_eq!(status, StatusCode::NON_AUTHORITATIVE_INFORMATION);
+    assert_eq!(headers["x-stale"], "true");
+    assert_eq!(headers["x-as-of"], "2025-01-19T08:00:00");
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert_eq!(stale, fresh);
+
+    // nothing to fall back on for a range never fetched
+    let (status, headers, _) = get("/api/new-subjects?start=2025-01-17T00:00:00").await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert!(headers.get("x-stale").is_none());
+}
+
+#[tokio::test]
+async fn dropped_request_stops_the_scrape() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    // a slow archive with a new thread on every page, so the walk would go on for the whole day
+    let server = MockServer::start(|req| {
+        std::thread::sleep(Duration::from_millis(50));
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        let since = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let since = NaiveDateTime::parse_from_str(since, "%Y%m%d%H%M").unwrap();
+        let next = since + TimeDelta::minutes(1);
+        let id = format!("t{}", next.format("%H%M"));
+        let time = next.format("%H:%M").to_string();
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[(&id, "Topic", "Alice", &time)],
+        )]))
+    });
+    let app = create_router(Fetcher::new(&server.url()), None, None, None);
+    let request =
+        Request::get("/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59")
+            .body(Body::empty())
+            .unwrap();
+
+    // the client gives up while the scrape is under way
+    let gone = tokio::time::timeout(Duration::from_millis(300), app.oneshot(request)).await;
+    assert!(gone.is_err());
+    let at_drop = server.requests().len();
+    assert!(at_drop > 0);
+
+    // the request in flight may still finish, but no other is made
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    let settled = server.requests().len();
+    assert!(
+        settled <= at_drop + 1,
+        "{at_drop} requests at the drop, {settled} after"
+    );
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert_eq!(server.requests().len(), settled);
+}
+
+#[tokio::test]
+async fn list_parameter_picks_the_mailing_list() {
+    use crate::mock_server::{listing_page, MockResponse, MockServer};
+    use axum::body::Body;
+    use axum::http::Request;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|_| MockResponse::html(listing_page(&[])));
+    let router = create_router(Fetcher::new(&server.url()), None, None, None);
+    let get = |uri: &str| {
+        router
+            .clone()
+            .oneshot(Request::get(uri).body(Body::empty()).unwrap())
+    };
+
+    let response = get("/api/latest?list=pgsql-performance").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(server.paths()[0].starts_with("/list/pgsql-performance/"));
+
+    let response = get("/api/latest?list=pgsql-nope").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(server.paths().len(), 1);
+}
+
+#[tokio::test]
+async fn cached_answers_spare_the_archive() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[("first%40x", "First topic", "Alice", "09:00")],
+        )]))
+    });
+    let mut caching = cache::ResponseCaching::new(cache::open("memory").unwrap());
+    caching.thread_ttl = Duration::ZERO;
+    let app = create_router(Fetcher::new(&server.url()), None, None, Some(caching));
+    let get = |uri: &str, accept: &str| {
+        let request = Request::get(uri)
+            .header(header::ACCEPT, accept)
+            .body(Body::empty())
+            .unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let headers = response.headers().clone();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (headers, body)
+        }
+    };
+    let uri = "/api/new-subjects?start=2025-01-18T00:00:00&end=2025-01-18T23:59:59";
+
+    let (headers, fresh) = get(uri, "application/json").await;
+    assert!(headers.get("x-cache").is_none());
+    let fetched = server.requests().len();
+    let (headers, cached) = get(uri, "application/json").await;
+    assert_eq!(headers["x-cache"], "hit");
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    assert_eq!(cached, fresh);
+    assert_eq!(server.requests().len(), fetched);
+
+    // another format or range is another answer
+    let (headers, csv) = get(uri, "text/csv").await;
+    assert!(headers.get("x-cache").is_none());
+    assert_ne!(csv, fresh);
+    assert!(server.requests().len() > fetched);
+
+    // threads are not kept with a zero TTL
+    let fetched = server.requests().len();
+    get("/api/thread/first%40x", "application/json").await;
+    let (headers, _) = get("/api/thread/first%40x", "application/json").await;
+    assert!(headers.get("x-cache").is_none());
+    assert!(server.requests().len() > fetched);
+
+    assert!(cache::open("memcached://localhost").is_err());
+    #[cfg(not(feature = "redis"))]
+    assert!(matches!(
+        cache::open("redis://localhost"),
+        Err(err) if err.to_string().contains("redis feature")
+    ));
+}
+
+#[tokio::test]
+async fn default_listings_are_served_warm() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use axum::body::Body;
+    use chrono::NaiveDate;
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[("first%40x", "First topic", "Alice", "09:00")],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url()).with_clock(|| {
+        NaiveDate::from_ymd_opt(2025, 1, 18)
+            .unwrap()
+            .and_hms_opt(9, 15, 0)
+            .unwrap()
+    });
+    let app = create_router(fetcher.clone(), Some("/pgdev"), None, None);
+    let app = warm::keep_warm(app, fetcher, Some("/pgdev"), Duration::from_secs(3600));
+    let get = |uri: &str| {
+        let request = Request::get(uri).body(Body::empty()).unwrap();
+        let app = app.clone();
+        async move {
+            let response = app.oneshot(request).await.unwrap();
+            let headers = response.headers().clone();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            (headers, body)
+        }
+    };
+
+    // the first request waits for the listing the server started with
+    let (headers, first) = get("/pgdev/api/new-subjects").await;
+    assert_eq!(headers["x-as-of"], "2025-01-18T09:15:00");
+    assert_eq!(headers[header::CONTENT_TYPE], "application/json");
+    let listed: Vec<serde_json::Value> = serde_json::from_slice(&first).unwrap();
+    assert_eq!(listed[0]["id"], "first%40x");
+    let (headers, active) = get("/pgdev/api/active-subjects").await;
+    assert!(headers.contains_key("x-as-of"));
+    assert_ne!(active, first);
+
+    let fetched = server.requests().len();
+    let (_, again) = get("/pgdev/api/new-subjects").await;
+    assert_eq!(again, first);
+    assert_eq!(server.requests().len(), fetched);
+
+    // any other range is listed when asked for
+    let (headers, _) = get("/pgdev/api/new-subjects?start=2025-01-18T00:00:00").await;
+    assert!(headers.get("x-as-of").is_none());
+    assert!(server.requests().len() > fetched);
+}
@@ -0,0 +1,169 @@
+//! The default listings kept ready in the background, so that a dashboard opening
+//! `/api/active-subjects` or `/api/new-subjects` gets them at once instead of waiting for the
+//! scrape of a whole day or week.
+//!
+//! A task asks the router for them when the server starts and again every while. Requests for
+//! them, with no query and asking for JSON, are answered with the last copy, `X-As-Of` saying
+//! when it was listed. Until the first copy is ready they wait for it rather than scraping the
+//! same pages again.
+
+use crate::Fetcher;
+use axum::body::{Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::{header, HeaderMap, HeaderValue, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Router;
+use chrono::NaiveDateTime;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+use tower::ServiceExt;
+
+/// how often the listings are listed again unless configured otherwise
+pub const REFRESH_EVERY: Duration = Duration::from_secs(600);
+
+/// the listings kept ready, in their default range of a day and a week
+const WARM_PATHS: [&str; 2] = ["/api/active-subjects", "/api/new-subjects"];
+
+enum Warmth {
+    /// the first listing is under way
+    Pending,
+    /// the first listing failed, requests are answered as if nothing were kept
+    Failed,
+    Ready(Arc<WarmResponse>),
+}
+
+struct WarmResponse {
+    // when it was listed
+    as_of: NaiveDateTime,
+    content_type: Option<HeaderValue>,
+    body: Bytes,
+}
+
+/// the last copy of each warm listing, by path with the base path
+struct Warm {
+    fetcher: Fetcher,
+    responses: HashMap<String, watch::Sender<Warmth>>,
+}
+
+/// `app` with the default listings under `base_path` kept ready and listed again `every` while,
+/// by a task spawned on the current runtime
+pub(super) fn keep_warm(
+    app: Router,
+    fetcher: Fetcher,
+    base_path: Option<&str>,
+    every: Duration,
+) -> Router {
+    let base = match base_path.map(|path| path.trim_matches('/')) {
+        Some(path) if !path.is_empty() => format!("/{path}"),
+        _ => String::new(),
+    };
+    let warm = Arc::new(Warm {
+        fetcher,
+        responses: WARM_PATHS
+            .iter()
+            .map(|path| (format!("{base}{path}"), watch::Sender::new(Warmth::Pending)))
+            .collect(),
+    });
+    tokio::spawn(warm.clone().refresh(app.clone(), every));
+    app.layer(axum::middleware::from_fn_with_state(warm, serve_warm))
+}
+
+impl Warm {
+    async fn refresh(self: Arc<Self>, app: Router, every: Duration) {
+        loop {
+            for (path, sender) in &self.responses {
+                match self.list(&app, path).await {
+                    Result::Ok(response) => {
+                        sender.send_replace(Warmth::Ready(Arc::new(response)));
+                    }
+                    Err(err) => {
+                        // a copy already kept is served until a listing succeeds again
+                        tracing::warn!("failed to refresh {path}: {err}");
+                        sender.send_if_modified(|warmth| match warmth {
+                            Warmth::Pending => {
+                                *warmth = Warmth::Failed;
+                                true
+                            }
+                            _ => false,
+                        });
+                    }
+                }
+            }
+            tokio::time::sleep(every).await;
+        }
+    }
+
+    async fn list(&self, app: &Router, path: &str) -> Result<WarmResponse, String> {
+        let as_of = self.fetcher.now();
+        let request = Request::get(path)
+            .header(header::ACCEPT, "application/json")
+            .body(Body::empty())
+            .map_err(|err| err.to_string())?;
+        let response = app
+            .clone()
+            .oneshot(request)
+            .await
+            .unwrap_or_else(|err| match err {});
+        if response.status() != StatusCode::OK {
+            return Err(format!("the listing answered {}", response.status()));
+        }
+        let (parts, body) = response.into_parts();
+        Ok(WarmResponse {
+            as_of,
+            content_type: parts.headers.get(header::CONTENT_TYPE).cloned(),
+            body: axum::body::to_bytes(body, usize::MAX)
+                .await
+                .map_err(|err| err.to_string())?,
+        })
+    }
+}
+
+/// whether the request takes JSON, which is what is kept
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .is_none_or(|accept| matches!(accept.to_str(), Result::Ok("application/json" | "*/*")))
+}
+
+/// answer a default listing with its last copy, or pass the request on
+async fn serve_warm(State(warm): State<Arc<Warm>>, request: Request, next: Next) -> Response {
+    let sender = warm
+        .responses
+        .get(request.uri().path())
+        .filter(|_| request.uri().query().is_none() && accepts_json(request.headers()));
+    let Some(sender) = sender else {
+        return next.run(request).await;
+    };
+    let mut receiver = sender.subscribe();
+    let ready = match receiver
+        .wait_for(|warmth| !matches!(warmth, Warmth::Pending))
+        .await
+    {
+        Result::Ok(warmth) => match &*warmth {
+            Warmth::Ready(response) => Some(response.clone()),
+            _ => None,
+        },
+        Err(_) => None,
+    };
+    let Some(warm_response) = ready else {
+        return next.run(request).await;
+    };
+    let mut response = (
+        StatusCode::OK,
+        [(
+            "x-as-of",
+            warm_response.as_of.format("%Y-%m-%dT%H:%M:%S").to_string(),
+        )],
+        warm_response.body.clone(),
+    )
+        .into_response();
+    if let Some(content_type) = &warm_response.content_type {
+        response
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, content_type.clone());
+    }
+    response
+}
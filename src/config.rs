@@ -0,0 +1,295 @@
+//! Settings from a TOML file and `PGDEVHUB_*` environment variables, so the scraper can be
+//! pointed at a mirror or tuned without rebuilding. The command line wins over the environment,
+//! which wins over the file.
+
+use crate::{FetchConfig, Fetcher, MailingList, PG_SITE};
+use anyhow::{bail, Context, Result};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// prefix of the environment variables, e.g. `PGDEVHUB_SITE` for `site`
+const ENV_PREFIX: &str = "PGDEVHUB_";
+
+/// Each setting is unset unless the file or the environment gives it, the built-in default
+/// applies then.
+///
+/// ```toml
+/// site = "https://archive-mirror.example.org"
+/// list = "pgsql-bugs"
+/// timeout-secs = 60
+/// min-delay-ms = 500
+/// request-rate = 2.0
+/// page-concurrency = 2
+/// attachment-rate = 1.0
+/// cache-dir = "/var/cache/pgdevhub"
+/// database = "postgres://pgdevhub@db.example.org/pgdevhub"
+/// bind = "0.0.0.0:8080"
+/// response-cache = "redis://127.0.0.1/"
+/// listing-ttl-secs = 120
+/// ```
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct Config {
+    /// the archive scraped, [`PG_SITE`] or a mirror of it
+    pub site: Option<String>,
+    /// the list traversed, by its name in archive urls like `pgsql-bugs`
+    #[serde(deserialize_with = "mailing_list")]
+    pub list: Option<MailingList>,
+    /// see [`FetchConfig::request_timeout`]
+    pub timeout_secs: Option<u64>,
+    /// see [`FetchConfig::min_delay_between_requests`]
+    pub min_delay_ms: Option<u64>,
+    /// see [`FetchConfig::request_rate`]
+    pub request_rate: Option<f64>,
+    /// see [`FetchConfig::request_burst`]
+    pub request_burst: Option<u32>,
+    /// see [`FetchConfig::page_concurrency`]
+    pub page_concurrency: Option<usize>,
+    /// see [`FetchConfig::attachment_concurrency`]
+    pub attachment_concurrency: Option<usize>,
+    /// see [`FetchConfig::attachment_rate`]
+    pub attachment_rate: Option<f64>,
+    /// see [`FetchConfig::list_concurrency`]
+    pub list_concurrency: Option<usize>,
+    /// where files kept across runs go, like the thread starters learned
+    pub cache_dir: Option<PathBuf>,
+    /// where scraped threads and messages are kept, see [`storage::open`](crate::storage::open)
+    pub database: Option<String>,
+    /// the address the API server listens on
+    pub bind: Option<String>,
+    /// where the API server keeps its answers, see [`api::cache::open`](crate::api::cache::open)
+    pub response_cache: Option<String>,
+    /// see [`ResponseCaching::listing_ttl`](crate::api::cache::ResponseCaching)
+    pub listing_ttl_secs: Option<u64>,
+    /// see [`ResponseCaching::thread_ttl`](crate::api::cache::ResponseCaching)
+    pub thread_ttl_secs: Option<u64>,
+    /// how often the API server lists its default listings again, see [`api::warm`](crate::api::warm)
+    pub warm_every_secs: Option<u64>,
+}
+
+fn mailing_list<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<MailingList>, D::Error> {
+    String::deserialize(deserializer)?
+        .parse()
+        .map(Some)
+        .map_err(D::Error::custom)
+}
+
+impl Config {
+    /// the settings of the file at `path`, if any, overridden by the environment
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let text = path
+            .map(|path| {
+                std::fs::read_to_string(path)
+                    .with_context(|| format!("failed to read the config {}", path.display()))
+            })
+            .transpose()?;
+        let config = Config::from_sources(text.as_deref(), |name| std::env::var(name).ok());
+        match path {
+            Some(path) => config.with_context(|| format!("bad config {}", path.display())),
+            None => config,
+        }
+    }
+
+    /// the settings of the TOML `text` overridden by the variables `env` finds
+    fn from_sources(text: Option<&str>, env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut config: Config = toml::from_str(text.unwrap_or(""))?;
+        let var = |key: &str| {
+            let name = format!("{ENV_PREFIX}{}", key.to_uppercase().replace('-', "_"));
+            env(&name).map(|value| (name, value))
+        };
+        fn parse<T: std::str::FromStr>((name, value): (String, String)) -> Result<T>
+        where
+            T::Err: Into<anyhow::Error>,
+        {
+            value
+                .trim()
+                .parse()
+                .map_err(Into::into)
+                .with_context(|| format!("bad {name} '{value}'"))
+        }
+        if let Some((_, site)) = var("site") {
+            config.site = Some(site);
+        }
+        if let Some(list) = var("list") {
+            config.list = Some(parse(list)?);
+        }
+        if let Some(timeout) = var("timeout-secs") {
+            config.timeout_secs = Some(parse(timeout)?);
+        }
+        if let Some(delay) = var("min-delay-ms") {
+            config.min_delay_ms = Some(parse(delay)?);
+        }
+        if let Some(rate) = var("request-rate") {
+            config.request_rate = Some(parse(rate)?);
+        }
+        if let Some(burst) = var("request-burst") {
+            config.request_burst = Some(parse(burst)?);
+        }
+        if let Some(concurrency) = var("page-concurrency") {
+            config.page_concurrency = Some(parse(concurrency)?);
+        }
+        if let Some(concurrency) = var("attachment-concurrency") {
+            config.attachment_concurrency = Some(parse(concurrency)?);
+        }
+        if let Some(rate) = var("attachment-rate") {
+            config.attachment_rate = Some(parse(rate)?);
+        }
+        if let Some(concurrency) = var("list-concurrency") {
+            config.list_concurrency = Some(parse(concurrency)?);
+        }
+        if let Some((_, dir)) = var("cache-dir") {
+            config.cache_dir = Some(dir.into());
+        }
+        if let Some((_, database)) = var("database") {
+            config.database = Some(database);
+        }
+        if let Some((_, bind)) = var("bind") {
+            config.bind = Some(bind);
+        }
+        if let Some((_, cache)) = var("response-cache") {
+            config.response_cache = Some(cache);
+        }
+        if let Some(ttl) = var("listing-ttl-secs") {
+            config.listing_ttl_secs = Some(parse(ttl)?);
+        }
+        if let Some(ttl) = var("thread-ttl-secs") {
+            config.thread_ttl_secs = Some(parse(ttl)?);
+        }
+        if let Some(secs) = var("warm-every-secs") {
+            config.warm_every_secs = Some(parse(secs)?);
+        }
+        // no request could ever start at a rate of zero or less
+        for (key, rate) in [
+            ("request-rate", config.request_rate),
+            ("attachment-rate", config.attachment_rate),
+        ] {
+            if let Some(rate) = rate {
+                if !(rate.is_finite() && rate > 0.0) {
+                    bail!("{key} must be a positive number of requests per second, not {rate}");
+                }
+            }
+        }
+        Ok(config)
+    }
+
+    /// the default [`FetchConfig`] tuned by these settings
+    pub fn fetch_config(&self) -> FetchConfig {
+        let mut fetch = FetchConfig::default();
+        if let Some(timeout) = self.timeout_secs {
+            fetch.request_timeout = Some(Duration::from_secs(timeout));
+        }
+        if let Some(delay) = self.min_delay_ms {
+            fetch.min_delay_between_requests = Some(Duration::from_millis(delay));
+        }
+        if let Some(rate) = self.request_rate {
+            fetch.request_rate = Some(rate);
+        }
+        if let Some(burst) = self.request_burst {
+            fetch.request_burst = burst;
+        }
+        if let Some(concurrency) = self.page_concurrency {
+            fetch.page_concurrency = concurrency;
+        }
+        if let Some(concurrency) = self.attachment_concurrency {
+            fetch.attachment_concurrency = concurrency;
+        }
+        if let Some(rate) = self.attachment_rate {
+            fetch.attachment_rate = Some(rate);
+        }
+        if let Some(concurrency) = self.list_concurrency {
+            fetch.list_concurrency = concurrency;
+        }
+        fetch
+    }
+
+    /// a fetcher of the configured site and list, with `fetch` usually from
+    /// [`Config::fetch_config`]
+    pub fn fetcher(&self, fetch: FetchConfig) -> Fetcher {
+        Fetcher::new(self.site.as_deref().unwrap_or(PG_SITE))
+            .with_list(self.list.unwrap_or_default())
+            .with_config(fetch)
+    }
+
+    /// the file of thread starters in the cache directory, which is created when missing
+    pub fn starter_cache(&self) -> Result<Option<PathBuf>> {
+        Ok(self.cache_path("")?.map(|dir| dir.join("starters.tsv")))
+    }
+
+    /// the directory of pages kept for [`http::Cached`](crate::http::Cached) in the cache
+    /// directory, created when missing
+    pub fn http_cache(&self) -> Result<Option<PathBuf>> {
+        self.cache_path("http")
+    }
+
+    /// `name` in the cache directory, created when missing
+    fn cache_path(&self, name: &str) -> Result<Option<PathBuf>> {
+        let Some(dir) = &self.cache_dir else {
+            return Ok(None);
+        };
+        let dir = dir.join(name);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("failed to create the cache directory {}", dir.display()))?;
+        Ok(Some(dir))
+    }
+}
+
+#[test]
+fn environment_overrides_the_file() {
+    let text = "site = \"https://mirror.example.org/\"\n\
+                list = \"pgsql-bugs\"\n\
+                timeout-secs = 60\n\
+                page-concurrency = 2\n\
+                bind = \"0.0.0.0:8080\"\n";
+    let env = |name: &str| match name {
+        "PGDEVHUB_TIMEOUT_SECS" => Some("5".to_string()),
+        "PGDEVHUB_MIN_DELAY_MS" => Some("250".to_string()),
+        "PGDEVHUB_ATTACHMENT_RATE" => Some("0.5".to_string()),
+        _ => None,
+    };
+    let config = Config::from_sources(Some(text), env).unwrap();
+    assert_eq!(
+        config,
+        Config {
+            site: Some("https://mirror.example.org/".to_string()),
+            list: Some(MailingList::Bugs),
+            timeout_secs: Some(5),
+            min_delay_ms: Some(250),
+            page_concurrency: Some(2),
+            attachment_rate: Some(0.5),
+            bind: Some("0.0.0.0:8080".to_string()),
+            ..Config::default()
+        }
+    );
+
+    let fetcher = config.fetcher(config.fetch_config());
+    assert_eq!(
+        fetcher.message_url("a%40x"),
+        "https://mirror.example.org/message-id/a%40x"
+    );
+    assert_eq!(fetcher.list, MailingList::Bugs);
+    assert_eq!(fetcher.config.request_timeout, Some(Duration::from_secs(5)));
+    assert_eq!(fetcher.config.page_concurrency, 2);
+    assert_eq!(fetcher.config.attachment_rate, Some(0.5));
+    assert_eq!(
+        fetcher.config.attachment_concurrency,
+        FetchConfig::default().attachment_concurrency
+    );
+
+    assert!(Config::from_sources(Some("list = \"pgsql-nope\""), |_| None).is_err());
+    assert!(Config::from_sources(Some("timeout = 5"), |_| None).is_err());
+    assert!(Config::from_sources(None, |_| Some("soon".to_string())).is_err());
+    assert!(Config::from_sources(Some("request-rate = 0.0"), |_| None).is_err());
+    let env = |name: &str| (name == "PGDEVHUB_REQUEST_RATE").then(|| "NaN".to_string());
+    assert!(Config::from_sources(None, env).is_err());
+    assert!(Config::from_sources(Some("attachment-rate = -1.0"), |_| None).is_err());
+    let env = |name: &str| (name == "PGDEVHUB_ATTACHMENT_RATE").then(|| "inf".to_string());
+    assert!(Config::from_sources(None, env).is_err());
+    assert_eq!(
+        Config::from_sources(None, |_| None).unwrap(),
+        Config::default()
+    );
+}
@@ -0,0 +1,161 @@
+//! The scheduled digest of new subjects, delivered at least once across restarts.
+
+use crate::{get_new_subjects_between, EmailThread, Fetcher};
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+/// ids of delivered threads remembered, enough to cover the overlap of two consecutive runs
+const RECENT_IDS: usize = 500;
+
+const WATERMARK_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// where the digest left off: threads up to the watermark are all delivered, and the recent ids
+/// say which ones past it were delivered before a run failed. kept in a file when there is one,
+/// the watermark on the first line and an id per line after it
+#[derive(Debug)]
+pub struct DigestState {
+    path: Option<PathBuf>,
+    watermark: NaiveDateTime,
+    delivered: VecDeque<String>,
+}
+
+impl DigestState {
+    /// a state kept in memory only, starting at `watermark`
+    pub fn new(watermark: NaiveDateTime) -> Self {
+        DigestState {
+            path: None,
+            watermark,
+            delivered: VecDeque::new(),
+        }
+    }
+
+    /// the state stored at `path`, or one starting at `watermark` when there is no such file yet
+    pub fn load(path: &Path, watermark: NaiveDateTime) -> Result<Self> {
+        let mut state = DigestState::new(watermark);
+        state.path = Some(path.to_path_buf());
+        if !path.exists() {
+            return Ok(state);
+        }
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read the digest state {}", path.display()))?;
+        let mut lines = text.lines();
+        let first = lines.next().unwrap_or("");
+        state.watermark = NaiveDateTime::parse_from_str(first, WATERMARK_FORMAT)
+            .with_context(|| format!("bad watermark '{first}' in {}", path.display()))?;
+        state.delivered = lines.map(str::to_string).collect();
+        Ok(state)
+    }
+
+    pub fn watermark(&self) -> NaiveDateTime {
+        self.watermark
+    }
+
+    fn is_delivered(&self, id: &str) -> bool {
+        self.delivered.iter().any(|delivered| delivered == id)
+    }
+
+    fn mark_delivered(&mut self, id: &str) -> Result<()> {
+        if self.delivered.len() == RECENT_IDS {
+            self.delivered.pop_front();
+        }
+        self.delivered.push_back(id.to_string());
+        self.save()
+    }
+
+    fn advance(&mut self, watermark: NaiveDateTime) -> Result<()> {
+        self.watermark = watermark;
+        self.save()
+    }
+
+    /// write the state next to its file and rename it over, so a crash leaves the old or the
+    /// new state but never half of one
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        let mut text = self.watermark.format(WATERMARK_FORMAT).to_string();
+        for id in &self.delivered {
+            text.push('\n');
+            text.push_str(id);
+        }
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, text)
+            .and_then(|()| std::fs::rename(&tmp, path))
+            .with_context(|| format!("failed to save the digest state {}", path.display()))
+    }
+}
+
+/// hand each new subject between the watermark of `state` and `until` to `deliver`, skipping
+/// the ones already delivered. each delivery is recorded as soon as it succeeds, and the
+/// watermark only moves to `until` once all of them did, so a run that fails or is killed
+/// midway is picked up by the next one without losing or repeating a thread
+pub async fn run(
+    fetcher: &Fetcher,
+    state: &mut DigestState,
+    until: NaiveDateTime,
+    mut deliver: impl FnMut(&EmailThread) -> Result<()>,
+) -> Result<()> {
+    let threads = get_new_subjects_between(fetcher, state.watermark, until).await?;
+    for thread in &threads {
+        if state.is_delivered(&thread.id) {
+            continue;
+        }
+        deliver(thread)?;
+        state.mark_delivered(&thread.id)?;
+    }
+    state.advance(until)
+}
+
+#[tokio::test]
+async fn restart_after_failed_delivery_skips_nothing() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use chrono::NaiveDate;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Topic b", "Bob", "10:00"),
+                ("c", "Topic c", "Carol", "11:00"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let path = std::env::temp_dir().join(format!("pgdevhub-digest-{}", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let start = day.into();
+    let until = day.and_hms_opt(12, 0, 0).unwrap();
+
+    let mut delivered = Vec::new();
+    let mut state = DigestState::load(&path, start).unwrap();
+    // the process dies while delivering the second thread
+    let crashed = run(&fetcher, &mut state, until, |thread| {
+        anyhow::ensure!(thread.id != "b", "webhook unreachable");
+        delivered.push(thread.id.clone());
+        Ok(())
+    })
+    .await;
+    assert!(crashed.is_err());
+    drop(state);
+
+    let mut state = DigestState::load(&path, until).unwrap();
+    assert_eq!(state.watermark(), start);
+    run(&fetcher, &mut state, until, |thread| {
+        delivered.push(thread.id.clone());
+        Ok(())
+    })
+    .await
+    .unwrap();
+    assert_eq!(delivered, ["a", "b", "c"]);
+
+    let state = DigestState::load(&path, start).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(state.watermark(), until);
+}
@@ -0,0 +1,169 @@
+//! Atom feeds of threads with their full content, for reading discussions offline.
+
+use crate::{EmailThreadDetail, Fetcher, LIST_PATH};
+use chrono::NaiveDateTime;
+use html_escape::{encode_double_quoted_attribute, encode_text};
+use scraper::{ElementRef, Html, Node};
+
+/// tags kept by [`sanitize_html`], enough for the archive's rendering of a mail body
+const ALLOWED_TAGS: [&str; 17] = [
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "div",
+    "em",
+    "i",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "span",
+    "strong",
+    "tt",
+    "u",
+    "ul",
+];
+
+/// `html` with only the tags in [`ALLOWED_TAGS`], no attributes but the href of links to http(s)
+/// or the site itself, and no scripts or styles. other tags are dropped but their text is kept
+pub fn sanitize_html(html: &str) -> String {
+    let fragment = Html::parse_fragment(html);
+    let mut sanitized = String::new();
+    push_sanitized_children(&mut sanitized, fragment.root_element());
+    sanitized
+}
+
+fn push_sanitized_children(out: &mut String, elem: ElementRef) {
+    for child in elem.children() {
+        match child.value() {
+            Node::Text(text) => out.push_str(&encode_text(&**text)),
+            Node::Element(_) => push_sanitized(out, ElementRef::wrap(child).unwrap()),
+            _ => {}
+        }
+    }
+}
+
+fn push_sanitized(out: &mut String, elem: ElementRef) {
+    let name = elem.value().name();
+    if matches!(name, "script" | "style") {
+        return;
+    }
+    if !ALLOWED_TAGS.contains(&name) {
+        push_sanitized_children(out, elem);
+        return;
+    }
+    out.push('<');
+    out.push_str(name);
+    let href = elem.value().attr("href").filter(|href| {
+        let href = href.trim_start().to_ascii_lowercase();
+        href.starts_with("http://") || href.starts_with("https://") || href.starts_with('/')
+    });
+    if let (Some(href), "a") = (href, name) {
+        out.push_str(&format!(
+            " href=\"{}\"",
+            encode_double_quoted_attribute(href)
+        ));
+    }
+    out.push('>');
+    if name == "br" {
+        return;
+    }
+    push_sanitized_children(out, elem);
+    out.push_str(&format!("</{name}>"));
+}
+
+/// an entity tag for a feed of `threads`. archived messages do not change, so the feed is the
+/// same as long as it holds the same messages, whatever its `<updated>` says
+pub fn entity_tag(threads: &[EmailThreadDetail]) -> String {
+    use std::hash::{Hash, Hasher};
+
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    for thread in threads {
+        thread.id.hash(&mut hasher);
+        thread.datetime.hash(&mut hasher);
+    }
+    format!("\"{:016x}\"", hasher.finish())
+}
+
+/// `datetime` as an HTTP date, like `Sat, 18 Jan 2025 09:00:00 GMT`, taking it as UTC like
+/// [`atom_time`]
+pub fn http_date(datetime: NaiveDateTime) -> String {
+    datetime.format(HTTP_DATE_FORMAT).to_string()
+}
+
+pub fn parse_http_date(date: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(date.trim(), HTTP_DATE_FORMAT).ok()
+}
+
+const HTTP_DATE_FORMAT: &str = "%a, %d %b %Y %H:%M:%S GMT";
+
+/// archive times carry no zone, they are given as UTC like the archive shows them
+fn atom_time(datetime: NaiveDateTime) -> String {
+    datetime.format("%Y-%m-%dT%H:%M:%SZ").to_string()
+}
+
+/// an Atom feed titled `title` with an entry per message of `threads`, updated at the newest
+/// of them or at `now` when there are none
+pub fn atom_feed(
+    fetcher: &Fetcher,
+    title: &str,
+    threads: &[EmailThreadDetail],
+    now: NaiveDateTime,
+) -> String {
+    let list_url = format!("{}{LIST_PATH}/{}/", fetcher.site, fetcher.list.name());
+    let updated = threads
+        .iter()
+        .map(|thread| thread.datetime)
+        .max()
+        .unwrap_or(now);
+    let mut feed = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+         <feed xmlns=\"http://www.w3.org/2005/Atom\">\n\
+         <title>{}</title>\n\
+         <id>{}</id>\n\
+         <link href=\"{}\"/>\n\
+         <updated>{}</updated>\n",
+        encode_text(title),
+        encode_text(&list_url),
+        encode_double_quoted_attribute(&list_url),
+        atom_time(updated),
+    );
+    for thread in threads {
+        let url = fetcher.message_url(&thread.id);
+        feed.push_str(&format!(
+            "<entry>\n\
+             <title>{}</title>\n\
+             <id>{}</id>\n\
+             <link href=\"{}\"/>\n\
+             <updated>{}</updated>\n\
+             <author><name>{}</name><email>{}</email></author>\n\
+             <content type=\"html\">{}</content>\n\
+             </entry>\n",
+            encode_text(&thread.subject),
+            encode_text(&url),
+            encode_double_quoted_attribute(&url),
+            atom_time(thread.datetime),
+            encode_text(&thread.author_name),
+            encode_text(&thread.author_email),
+            encode_text(&sanitize_html(&thread.content)),
+        ));
+    }
+    feed.push_str("</feed>\n");
+    feed
+}
+
+#[test]
+fn sanitize_keeps_text_and_safe_markup() {
+    assert_eq!(
+        sanitize_html(
+            "<p onclick=\"x()\">Hi &amp; <b>bye</b><br>\
+             <a href=\"https://example.org/?a=1&amp;b=2\" target=\"_blank\">link</a> \
+             <a href=\"javascript:alert(1)\">bad</a>\
+             <script>alert(1)</script><font color=\"red\">red</font></p>"
+        ),
+        "<p>Hi &amp; <b>bye</b><br>\
+         <a href=\"https://example.org/?a=1&amp;b=2\">link</a> <a>bad</a>red</p>"
+    );
+}
@@ -0,0 +1,335 @@
+//! Where a [`Fetcher`](crate::Fetcher) gets its pages from: the network, a disk cache in front of
+//! it, or fixtures recorded from it so the parsing can be tested offline against real archive
+//! pages.
+
+use crate::Page;
+use anyhow::{Context, Result};
+use reqwest::header::{self, HeaderName};
+use reqwest::StatusCode;
+use reqwest::{Client, Response};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// One attempt at getting a page for a [`Fetcher`](crate::Fetcher), which keeps the delays, budgets and
+/// retries around it.
+#[async_trait::async_trait]
+pub trait HttpFetcher: Send + Sync + std::fmt::Debug {
+    /// `url` fetched with `client`, the fetcher's pooled one. network failures are given as the
+    /// [`reqwest::Error`], they are retried depending on their [`ErrorClass`](crate::ErrorClass)
+    async fn get(&self, client: &Client, url: &str) -> Result<Page>;
+}
+
+/// Gets pages from the site, the default.
+#[derive(Debug)]
+pub struct Network;
+
+#[async_trait::async_trait]
+impl HttpFetcher for Network {
+    async fn get(&self, client: &Client, url: &str) -> Result<Page> {
+        read_page(client.get(url).send().await?).await
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpFetcher for Box<dyn HttpFetcher> {
+    async fn get(&self, client: &Client, url: &str) -> Result<Page> {
+        (**self).get(client, url).await
+    }
+}
+
+/// the value of the header `name` of `response`, if any
+fn header_text(response: &Response, name: HeaderName) -> Option<String> {
+    response
+        .headers()
+        .get(name)
+        .map(|value| String::from_utf8_lossy(value.as_bytes()).into_owned())
+}
+
+async fn read_page(response: Response) -> Result<Page> {
+    let content_type = header_text(&response, header::CONTENT_TYPE);
+    let retry_after = header_text(&response, header::RETRY_AFTER)
+        .and_then(|value| parse_retry_after(&value, chrono::Utc::now().naive_utc()));
+    Ok(Page {
+        status: response.status(),
+        content_type,
+        retry_after,
+        body: response.text().await?,
+    })
+}
+
+/// the wait a `Retry-After` header asks for, given in seconds or as an HTTP date after `now`
+fn parse_retry_after(value: &str, now: chrono::NaiveDateTime) -> Option<Duration> {
+    match value.trim().parse() {
+        Result::Ok(secs) => Some(Duration::from_secs(secs)),
+        Err(_) => {
+            let date = crate::feed::parse_http_date(value)?;
+            Some((date - now).to_std().unwrap_or(Duration::ZERO))
+        }
+    }
+}
+
+/// Gets pages from the site and keeps the ones that carry an `ETag` or a `Last-Modified` in
+/// `dir`. a page kept is asked for again conditionally, and served from `dir` when the site
+/// answers 304 Not Modified, so repeated runs only transfer the pages that changed.
+#[derive(Debug)]
+pub struct Cached {
+    dir: PathBuf,
+}
+
+/// a page kept by [`Cached`], with the validators to revalidate it
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPage {
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+    body: String,
+}
+
+impl Cached {
+    pub fn new(dir: &Path) -> Self {
+        Cached {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpFetcher for Cached {
+    async fn get(&self, client: &Client, url: &str) -> Result<Page> {
+        let path = self.dir.join(format!("{}.json", file_stem(url)));
+        // a cache entry that cannot be read is simply fetched again
+        let cached: Option<CachedPage> = std::fs::read(&path)
+            .ok()
+            .and_then(|json| serde_json::from_slice(&json).ok());
+        let mut request = client.get(url);
+        if let Some(cached) = &cached {
+            if let Some(etag) = &cached.etag {
+                request = request.header(header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &cached.last_modified {
+                request = request.header(header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let response = request.send().await?;
+        if let (StatusCode::NOT_MODIFIED, Some(cached)) = (response.status(), cached) {
+            return Ok(Page {
+                status: StatusCode::OK,
+                content_type: cached.content_type,
+                retry_after: None,
+                body: cached.body,
+            });
+        }
+
+        let etag = header_text(&response, header::ETAG);
+        let last_modified = header_text(&response, header::LAST_MODIFIED);
+        let page = read_page(response).await?;
+        if page.status == StatusCode::OK && (etag.is_some() || last_modified.is_some()) {
+            let cached = CachedPage {
+                etag,
+                last_modified,
+                content_type: page.content_type.clone(),
+                body: page.body.clone(),
+            };
+            std::fs::create_dir_all(&self.dir)
+                .and_then(|()| std::fs::write(&path, serde_json::to_vec(&cached)?))
+                .with_context(|| format!("failed to cache {url} in {}", path.display()))?;
+        }
+        Ok(page)
+    }
+}
+
+/// Gets pages from `inner` and saves each successful one as a fixture in `dir` for [`Replay`].
+#[derive(Debug)]
+pub struct Record<F> {
+    inner: F,
+    dir: PathBuf,
+}
+
+impl<F: HttpFetcher> Record<F> {
+    pub fn new(inner: F, dir: &Path) -> Self {
+        Record {
+            inner,
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<F: HttpFetcher> HttpFetcher for Record<F> {
+    async fn get(&self, client: &Client, url: &str) -> Result<Page> {
+        let page = self.inner.get(client, url).await?;
+        // a failed page is not worth replaying, the fetch is simply made again
+        if page.status.is_success() {
+            let is_html = page
+                .content_type
+                .as_deref()
+                .is_some_and(|content_type| content_type.contains("html"));
+            let path = fixture_path(&self.dir, url, is_html);
+            std::fs::create_dir_all(&self.dir)
+                .and_then(|()| std::fs::write(&path, &page.body))
+                .with_context(|| format!("failed to record {url} to {}", path.display()))?;
+        }
+        Ok(page)
+    }
+}
+
+/// Serves the pages recorded by [`Record`] in `dir`, without touching the network. a page that
+/// was not recorded is an error, so a test cannot quietly go online.
+#[derive(Debug)]
+pub struct Replay {
+    dir: PathBuf,
+}
+
+impl Replay {
+    pub fn new(dir: &Path) -> Self {
+        Replay {
+            dir: dir.to_path_buf(),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl HttpFetcher for Replay {
+    async fn get(&self, _: &Client, url: &str) -> Result<Page> {
+        for (is_html, content_type) in [(true, "text/html"), (false, "text/plain")] {
+            let path = fixture_path(&self.dir, url, is_html);
+            if path.exists() {
+                let body = std::fs::read_to_string(&path)
+                    .with_context(|| format!("failed to read {}", path.display()))?;
+                return Ok(Page {
+                    status: StatusCode::OK,
+                    content_type: Some(content_type.to_string()),
+                    retry_after: None,
+                    body,
+                });
+            }
+        }
+        anyhow::bail!("no fixture of {url} in {}", self.dir.display())
+    }
+}
+
+/// characters escaped in fixture names, all but the unreserved ones like in message-ids
+const FIXTURE_ESCAPES: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// the name of the file kept for `url`, after its path and query so the same page is found
+/// whatever site it came from, like `list%2Fpgsql-hackers%2Fsince%2F202501180000`
+fn file_stem(url: &str) -> String {
+    let path = url::Url::parse(url)
+        .map(|url| match url.query() {
+            Some(query) => format!("{}?{query}", url.path()),
+            None => url.path().to_string(),
+        })
+        .unwrap_or_else(|_| url.to_string());
+    percent_encoding::utf8_percent_encode(path.trim_start_matches('/'), FIXTURE_ESCAPES).to_string()
+}
+
+/// the fixture of `url` in `dir`. pages that are not HTML, like the raw view, get a `.txt` name
+fn fixture_path(dir: &Path, url: &str, is_html: bool) -> PathBuf {
+    let extension = if is_html { "html" } else { "txt" };
+    dir.join(format!("{}.{extension}", file_stem(url)))
+}
+
+#[tokio::test]
+async fn recorded_pages_replay_offline() {
+    use crate::mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use crate::{get_new_subjects_between, Fetcher};
+    use chrono::NaiveDate;
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            let thread: &[&str] = if id == "c" { &["c"] } else { &["a", "b"] };
+            return MockResponse::html(MessagePage::new(id).thread(thread).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Re: Topic a", "Bob", "10:00"),
+                ("c", "Topic c", "Carol", "11:00"),
+            ],
+        )]))
+    });
+    let dir = std::env::temp_dir().join(format!("pgdevhub-fixtures-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let (start, end) = (day.into(), day.and_hms_opt(23, 59, 59).unwrap());
+
+    let fetcher = Fetcher::new(&server.url()).with_http(Record::new(Network, &dir));
+    let recorded = get_new_subjects_between(&fetcher, start, end)
+        .await
+        .unwrap();
+    let requests = server.requests().len();
+    assert!(dir
+        .join("list%2Fpgsql-hackers%2Fsince%2F202501180000.html")
+        .exists());
+
+    let fetcher = Fetcher::new(&server.url()).with_http(Replay::new(&dir));
+    let replayed = get_new_subjects_between(&fetcher, start, end)
+        .await
+        .unwrap();
+    let unrecorded = fetcher
+        .get_document(&fetcher.message_url("elsewhere"))
+        .await
+        .is_err();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let ids = |threads: &[crate::EmailThread]| -> Vec<String> {
+        threads.iter().map(|thread| thread.id.clone()).collect()
+    };
+    assert_eq!(ids(&recorded), ["a", "c"]);
+    assert_eq!(ids(&replayed), ids(&recorded));
+    assert_eq!(server.requests().len(), requests);
+    assert!(unrecorded);
+}
+
+#[tokio::test]
+async fn cached_pages_are_revalidated() {
+    use crate::mock_server::{MessagePage, MockResponse, MockServer};
+    use crate::Fetcher;
+
+    let server = MockServer::start(|req| match req.header("If-None-Match") {
+        Some("\"v1\"") => MockResponse::new(304, "text/html", ""),
+        _ => MockResponse::html(MessagePage::new("a").render()).with_header("ETag", "\"v1\""),
+    });
+    let dir = std::env::temp_dir().join(format!("pgdevhub-http-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let fetcher = Fetcher::new(&server.url()).with_http(Cached::new(&dir));
+    let fetched = fetcher.get_page(&fetcher.message_url("a")).await.unwrap();
+    let revalidated = fetcher.get_page(&fetcher.message_url("a")).await.unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let requests = server.requests();
+    assert_eq!(requests[0].header("If-None-Match"), None);
+    assert_eq!(requests[1].header("If-None-Match"), Some("\"v1\""));
+    assert_eq!(revalidated.status, StatusCode::OK);
+    assert_eq!(revalidated.body, fetched.body);
+    assert_eq!(revalidated.content_type, fetched.content_type);
+}
+
+#[test]
+fn retry_after_in_seconds_or_as_a_date() {
+    let now = chrono::NaiveDate::from_ymd_opt(2025, 1, 18)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap();
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Sat, 18 Jan 2025 09:01:30 GMT", now),
+        Some(Duration::from_secs(90))
+    );
+    // a date already past means right away
+    assert_eq!(
+        parse_retry_after("Sat, 18 Jan 2025 08:00:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
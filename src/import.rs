@@ -0,0 +1,256 @@
+//! Import the mbox files postgresql.org offers for download, a month of a list each, into a
+//! [`Storage`], for working through the history of a list without crawling its pages.
+//!
+//! Each message is kept as if it had been scraped: its text body as preformatted content, like
+//! the archive shows messages without html, its headers, attachments and the ids of its thread.
+//! Threads are put together from the References and In-Reply-To headers, a thread started in an
+//! earlier month keeps the id of its starter first even though the file does not have it.
+//! Attachments have no `href`, the archive's attachment urls cannot be told from a file.
+
+use crate::storage::Storage;
+use crate::{
+    canonical_message_id, content_new_text, content_stats, extract_inline_patches,
+    extract_references, preformatted, EmailThread, EmailThreadDetail, MailingList, MessageId,
+    RawMessage,
+};
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use mail_parser::mailbox::mbox::MessageIterator;
+use std::collections::{BTreeMap, HashMap};
+use std::io::BufReader;
+use std::path::Path;
+
+/// how many `In-Reply-To` links are followed to the start of a thread, against loops
+const MAX_THREAD_DEPTH: usize = 1000;
+
+/// What an import kept.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Imported {
+    pub messages: usize,
+    pub threads: usize,
+    /// messages without a Message-ID or that could not be parsed, left out
+    pub skipped: usize,
+}
+
+/// a message of the file, with the canonical ids it replies to
+struct Parsed {
+    detail: EmailThreadDetail,
+    references: Vec<String>,
+    in_reply_to: Option<String>,
+}
+
+/// keep every message of the mbox file at `path` in `storage` as a message of `list`, and as a
+/// listing row, the starters of threads with their reply counts
+pub fn import_mbox(path: &Path, list: MailingList, storage: &dyn Storage) -> Result<Imported> {
+    let file =
+        std::fs::File::open(path).with_context(|| format!("failed to open {}", path.display()))?;
+    let mut imported = Imported::default();
+    let mut messages = Vec::new();
+    for message in MessageIterator::new(BufReader::new(file)) {
+        let message = message.with_context(|| format!("failed to read {}", path.display()))?;
+        match parse_message(message.contents(), message.internal_date()) {
+            Some(parsed) => messages.push(parsed),
+            None => imported.skipped += 1,
+        }
+    }
+
+    let by_id: HashMap<String, usize> = messages
+        .iter()
+        .enumerate()
+        .map(|(i, parsed)| (canonical_message_id(&parsed.detail.id), i))
+        .collect();
+    // the canonical id of the starter of the thread of message `i`: the first of its
+    // References, or else where its In-Reply-To leads
+    let starter = |mut i: usize| -> String {
+        for _ in 0..MAX_THREAD_DEPTH {
+            let parsed = &messages[i];
+            if let Some(first) = parsed.references.first() {
+                return first.clone();
+            }
+            match &parsed.in_reply_to {
+                None => break,
+                Some(parent) => match by_id.get(parent) {
+                    Some(&in_file) => i = in_file,
+                    // a reply to a message of another month
+                    None => return parent.clone(),
+                },
+            }
+        }
+        canonical_message_id(&messages[i].detail.id)
+    };
+    let mut threads: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for i in 0..messages.len() {
+        threads.entry(starter(i)).or_default().push(i);
+    }
+
+    for (starter_id, mut members) in threads {
+        members.sort_by_key(|&i| messages[i].detail.datetime);
+        let starter_slug =
+            MessageId::parse(&starter_id).map_or_else(|_| starter_id.clone(), |id| id.to_string());
+        let mut ids = vec![starter_slug];
+        ids.extend(
+            members
+                .iter()
+                .map(|&i| messages[i].detail.id.clone())
+                .filter(|id| canonical_message_id(id) != starter_id),
+        );
+        for &i in &members {
+            let detail = &mut messages[i].detail;
+            detail.replies = ids.clone();
+            let is_starter = canonical_message_id(&detail.id) == starter_id;
+            storage.upsert_message(list, detail)?;
+            storage.upsert_thread(&EmailThread {
+                id: detail.id.clone(),
+                list,
+                subject: detail.subject.clone(),
+                datetime: detail.datetime,
+                author: detail.author_name.clone(),
+                reply_count: is_starter.then(|| ids.len() - 1),
+                replies: None,
+            })?;
+            imported.messages += 1;
+        }
+        imported.threads += 1;
+    }
+    Ok(imported)
+}
+
+/// the message `raw` as a scraped message, dated by the `From ` line of the mbox when it has
+/// no Date header. `None` when it cannot be parsed or has no Message-ID
+fn parse_message(raw: &[u8], internal_date: u64) -> Option<Parsed> {
+    let message = RawMessage::parse(raw)?;
+    let id = MessageId::parse(message.message_id.as_deref()?).ok()?;
+    let datetime = match message.date {
+        Some(date) => date.naive_utc(),
+        None => DateTime::from_timestamp(internal_date as i64, 0)?.naive_utc(),
+    };
+    let content = preformatted(&message.text);
+    let content_missing = content.is_none();
+    let content = content.unwrap_or_default();
+    Some(Parsed {
+        detail: EmailThreadDetail {
+            id: id.to_string(),
+            subject: message.subject,
+            datetime,
+            author_name: message.author_name,
+            author_email: message.author_email,
+            stats: content_stats(&content),
+            inline_patches: extract_inline_patches(&content),
+            references: extract_references(&content),
+            content_new_text: content_new_text(&content),
+            content,
+            content_missing,
+            attachments: message.attachments,
+            replies: Vec::new(),
+            headers: message.headers,
+        },
+        references: message.references,
+        in_reply_to: message.in_reply_to,
+    })
+}
+
+#[test]
+fn mbox_messages_are_kept_in_threads() {
+    use crate::storage::Sqlite;
+
+    let mbox = "\
+From alice@example.org Mon Jan 20 09:00:00 2025
+From: Alice <alice@example.org>
+Subject: =?UTF-8?Q?Fix_crash_in_pg=5Fdump?=
+Date: Mon, 20 Jan 2025 09:00:00 +0000
+Message-ID: <a@x>
+MIME-Version: 1.0
+Content-Type: multipart/mixed; boundary=\"b\"
+
+--b
+Content-Type: text/plain
+
+Patch attached, see commit 1234567abc.
+--b
+Content-Type: text/x-patch; name=\"fix.patch\"
+Content-Disposition: attachment; filename=\"fix.patch\"
+
+--- a/x
++++ b/x
+--b--
+
+From bob@example.org Mon Jan 20 10:00:00 2025
+From: Bob <bob@example.org>
+Subject: Re: Fix crash in pg_dump
+Date: Mon, 20 Jan 2025 10:00:00 +0000
+Message-ID: <b@x>
+In-Reply-To: <a@x>
+References: <a@x>
+
+Looks good.
+
+From carol@example.org Mon Jan 20 11:00:00 2025
+From: Carol <carol@example.org>
+Subject: Re: Fix crash in pg_dump
+Date: Mon, 20 Jan 2025 11:00:00 +0000
+Message-ID: <c@x>
+In-Reply-To: <b@x>
+
+Agreed.
+
+From dave@example.org Tue Jan 21 08:00:00 2025
+From: Dave <dave@example.org>
+Subject: Re: Older thread
+Date: Tue, 21 Jan 2025 08:00:00 +0000
+Message-ID: <d@x>
+In-Reply-To: <old@x>
+
+Still broken.
+
+From eve@example.org Tue Jan 21 09:00:00 2025
+From: Eve <eve@example.org>
+Subject: No id
+
+Lost.
+";
+    let path = std::env::temp_dir().join(format!("pgdevhub-import-{}.mbox", std::process::id()));
+    std::fs::write(&path, mbox).unwrap();
+    let store = Sqlite::open_in_memory().unwrap();
+    let imported = import_mbox(&path, MailingList::Hackers, &store).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        imported,
+        Imported {
+            messages: 4,
+            threads: 2,
+            skipped: 1,
+        }
+    );
+
+    let starter = store.message("a%40x").unwrap().unwrap();
+    assert_eq!(starter.subject, "Fix crash in pg_dump");
+    assert_eq!(starter.author_name, "Alice");
+    assert_eq!(starter.author_email, "alice@example.org");
+    assert_eq!(starter.replies, ["a%40x", "b%40x", "c%40x"]);
+    assert_eq!(starter.references.commits, ["1234567abc"]);
+    assert_eq!(starter.attachments.len(), 1);
+    assert_eq!(starter.attachments[0].name, "fix.patch");
+    assert!(starter.content.starts_with("<pre>Patch attached"));
+    assert_eq!(starter.headers["Message-ID"], "<a@x>");
+
+    // In-Reply-To alone leads to the starter through the message it answers
+    let reply = store.message("c%40x").unwrap().unwrap();
+    assert_eq!(reply.replies, ["a%40x", "b%40x", "c%40x"]);
+    // the starter of another month comes first though it is not kept
+    let late = store.message("d%40x").unwrap().unwrap();
+    assert_eq!(late.replies, ["old%40x", "d%40x"]);
+
+    let day = |d| {
+        chrono::NaiveDate::from_ymd_opt(2025, 1, d)
+            .unwrap()
+            .and_hms_opt(0, 0, 0)
+            .unwrap()
+    };
+    let threads = store
+        .threads_between(MailingList::Hackers, day(20), day(22))
+        .unwrap();
+    assert_eq!(threads.len(), 4);
+    assert_eq!(threads[0].id, "a%40x");
+    assert_eq!(threads[0].reply_count, Some(2));
+    assert_eq!(threads[1].reply_count, None);
+}
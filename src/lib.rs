@@ -0,0 +1,7323 @@
+//! Scraping of the PostgreSQL mailing list archive at <https://www.postgresql.org/list/>.
+//!
+//! A [`Fetcher`] holds the site, the list and the settings of a run. It is passed to everything
+//! that fetches, so one fetcher can be cloned and shared between threads:
+//!
+//! ```no_run
+//! use chrono::TimeDelta;
+//! use pgdevhub::{get_new_subjects_between, Fetcher};
+//!
+//! # async fn run() -> anyhow::Result<()> {
+//! let fetcher = Fetcher::default();
+//! let (start, end) = fetcher.last(TimeDelta::days(1));
+//! for thread in get_new_subjects_between(&fetcher, start, end).await? {
+//!     println!("{thread}");
+//! }
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! The scraper is async, it runs on tokio with the async reqwest client. The [`api`] module
+//! serves it over HTTP.
+
+use anyhow::{bail, Context, Ok, Result};
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta, Timelike};
+use futures_util::StreamExt;
+use phf::phf_map;
+use reqwest::Client;
+use scraper::{Html, Selector};
+use sink::ThreadSink;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, oneshot};
+use tracing::Instrument;
+
+pub mod api;
+pub mod config;
+pub mod digest;
+mod feed;
+pub mod http;
+pub mod import;
+#[cfg(test)]
+mod mock_server;
+#[cfg(feature = "parquet")]
+pub mod parquet_sink;
+pub mod progress;
+pub mod records;
+pub mod scheduler;
+pub mod sink;
+mod starter_cache;
+pub mod storage;
+pub mod template;
+
+pub const PG_SITE: &str = "https://www.postgresql.org";
+const MESSAGE_PATH: &str = "/message-id";
+const LIST_PATH: &str = "/list";
+const RAW_VIEW_AUTH: (&str, &str) = ("archives", "antispam");
+
+// compile-time lookup table, from the first three letters of a month to its name
+static MONTHS_MAP: phf::Map<&'static str, &'static str> = phf_map! {
+    "jan" => "January",
+    "feb" => "February",
+    "mar" => "March",
+    "apr" => "April",
+    "may" => "May",
+    "jun" => "June",
+    "jul" => "July",
+    "aug" => "August",
+    "sep" => "September",
+    "oct" => "October",
+    "nov" => "November",
+    "dec" => "December",
+};
+
+/// the full name of a month written as a name or an abbreviation of it, like `Sept.`, `Sep`,
+/// `sept` or `September`
+fn month_name(month: &str) -> Option<&'static str> {
+    let month = month.trim_end_matches('.').to_lowercase();
+    let name = MONTHS_MAP.get(month.get(..3)?)?;
+    name.to_lowercase().starts_with(&month).then_some(name)
+}
+
+/// the date of a listing's day header, like `Jan. 18, 2025`
+fn transform_date(date_text: &str) -> Result<NaiveDate> {
+    let date_text = date_text.trim();
+    let (month, rest) = date_text.split_once(' ').unwrap_or((date_text, ""));
+    let month = month_name(month)
+        .with_context(|| format!("unknown month '{month}' in date header '{date_text}'"))?;
+    NaiveDate::parse_from_str(&format!("{month} {rest}"), "%B %d, %Y")
+        .with_context(|| format!("unparsable date header '{date_text}'"))
+}
+
+/// the mailing lists whose archives can be scraped
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, serde::Serialize)]
+pub enum MailingList {
+    #[default]
+    #[serde(rename = "pgsql-hackers")]
+    Hackers,
+    #[serde(rename = "pgsql-general")]
+    General,
+    #[serde(rename = "pgsql-bugs")]
+    Bugs,
+    #[serde(rename = "pgsql-committers")]
+    Committers,
+    #[serde(rename = "pgsql-announce")]
+    Announce,
+    #[serde(rename = "pgsql-performance")]
+    Performance,
+}
+
+impl MailingList {
+    const ALL: [MailingList; 6] = [
+        MailingList::Hackers,
+        MailingList::General,
+        MailingList::Bugs,
+        MailingList::Committers,
+        MailingList::Announce,
+        MailingList::Performance,
+    ];
+
+    /// the list's name in archive urls, like `pgsql-hackers`
+    pub fn name(self) -> &'static str {
+        match self {
+            MailingList::Hackers => "pgsql-hackers",
+            MailingList::General => "pgsql-general",
+            MailingList::Bugs => "pgsql-bugs",
+            MailingList::Committers => "pgsql-committers",
+            MailingList::Announce => "pgsql-announce",
+            MailingList::Performance => "pgsql-performance",
+        }
+    }
+}
+
+impl std::str::FromStr for MailingList {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        MailingList::ALL
+            .into_iter()
+            .find(|list| list.name() == name.trim())
+            .with_context(|| format!("unknown mailing list '{name}'"))
+    }
+}
+
+/// A row of a list's archive: a message listed with its subject, author and time. For new
+/// subjects it is the starter of a thread.
+#[derive(Debug, Clone)]
+pub struct EmailThread {
+    pub id: String,
+    /// the list whose archive the thread was listed in
+    pub list: MailingList,
+    pub subject: String,
+    pub datetime: NaiveDateTime,
+    pub author: String,
+    /// messages in the thread besides the starter, only known after `add_reply_counts`
+    pub reply_count: Option<usize>,
+    /// ids of those messages in the order of the thread dropdown, only known after `add_replies`
+    pub replies: Option<Vec<String>>,
+}
+
+impl std::fmt::Display for EmailThread {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Thread: {}\nAuthor: {}\nTime: {}\nURL: {}",
+            self.subject,
+            self.author,
+            self.datetime.format("%Y-%m-%d %H:%M:%S"),
+            permalink(&self.id)
+        )?;
+        if let Some(reply_count) = self.reply_count {
+            write!(f, "\nReplies: {reply_count}")?;
+        }
+        for reply in self.replies.iter().flatten() {
+            write!(f, "\nReply: {}", permalink(reply))?;
+        }
+        std::fmt::Result::Ok(())
+    }
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ThreadAttachment {
+    pub name: String,
+    /// url without domain name
+    pub href: String,
+    /// as given by the size column, `None` when it is missing or unreadable
+    pub size: Option<u64>,
+}
+
+/// name endings that mark an attachment as a patch
+const PATCH_EXTENSIONS: [&str; 4] = ["patch", "diff", "patch.gz", "diff.gz"];
+
+impl ThreadAttachment {
+    /// whether the name ends in one of `extensions`, given like `patch` or `.patch`, whatever
+    /// the case
+    pub fn has_extension(&self, extensions: &[impl AsRef<str>]) -> bool {
+        let name = self.name.to_lowercase();
+        extensions.iter().any(|extension| {
+            let extension = extension.as_ref().trim_start_matches('.').to_lowercase();
+            name.strip_suffix(&extension)
+                .is_some_and(|stem| stem.ends_with('.'))
+        })
+    }
+}
+
+/// A message as shown on its own page, with its body, attachments and the other messages of its
+/// thread.
+#[derive(Debug)]
+pub struct EmailThreadDetail {
+    pub id: String,
+    pub subject: String,
+    pub datetime: NaiveDateTime,
+    pub author_name: String,
+    pub author_email: String,
+    /// a html fragment
+    pub content: String,
+    /// neither the page nor the raw view had a body, `content` is empty
+    pub content_missing: bool,
+    /// the text the author wrote in `content`, without the messages it quotes
+    pub content_new_text: String,
+    /// size of the prose in `content`
+    pub stats: ContentStats,
+    /// the diffs pasted into `content` rather than attached
+    pub inline_patches: Vec<String>,
+    /// commits and bug reports mentioned in `content`
+    pub references: References,
+    /// name and url
+    pub attachments: Vec<ThreadAttachment>,
+    /// list of other messages' id
+    pub replies: Vec<String>,
+    /// all headers of the raw message, repeated ones joined by newlines. empty when the raw view
+    /// could not be fetched
+    pub headers: BTreeMap<String, String>,
+}
+
+impl EmailThreadDetail {
+    /// this message as a listing row
+    pub fn summary(&self) -> EmailThread {
+        EmailThread {
+            id: self.id.clone(),
+            // messages are looked up by id, whatever list they went to
+            list: MailingList::default(),
+            subject: self.subject.clone(),
+            datetime: self.datetime,
+            author: self.author_name.clone(),
+            reply_count: Some(self.replies.len().saturating_sub(1)),
+            replies: None,
+        }
+    }
+
+    pub fn category(&self) -> ThreadCategory {
+        classify_thread(&self.summary(), Some(self))
+    }
+
+    /// the size of all attachments whose size is known
+    pub fn total_attachment_bytes(&self) -> u64 {
+        self.attachments
+            .iter()
+            .filter_map(|attachment| attachment.size)
+            .sum()
+    }
+}
+
+impl std::fmt::Display for EmailThreadDetail {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Thread: {}\n\
+            Author Name: {}\n\
+            Author Email: {}\n\
+            Time: {}\n\
+            URL: {}\n\
+            Content Size: {}\n\
+            Total Attachments: {}\n\
+            Attachment Size: {} bytes\n\
+            Total replies: {}",
+            self.subject,
+            self.author_name,
+            self.author_email,
+            self.datetime.format("%Y-%m-%d %H:%M:%S"),
+            permalink(&self.id),
+            self.content.len(),
+            self.attachments.len(),
+            self.total_attachment_bytes(),
+            self.replies.len(),
+        )
+    }
+}
+
+/// the trimmed text of `elem`. scraper decodes entities once, but text that was escaped twice on
+/// its way into the archive, like some subjects, still has entities left after that
+fn element_text(elem: &scraper::ElementRef) -> String {
+    let text: String = elem.text().collect();
+    html_escape::decode_html_entities(text.trim())
+        .trim()
+        .to_string()
+}
+
+/// the number of bytes in a size as the archive prints it, like "56 bytes", "12 kB" or
+/// "3.4\u{a0}MB". the archive counts in powers of 1024 whatever it calls the unit
+fn parse_byte_size(size: &str) -> Option<u64> {
+    let size = size.trim();
+    let unit_start = size
+        .find(|c: char| !c.is_ascii_digit() && c != '.' && c != ',')
+        .unwrap_or(size.len());
+    let (number, unit) = size.split_at(unit_start);
+    let number: f64 = number.replace(',', "").parse().ok()?;
+    let exponent = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "b" | "byte" | "bytes" => 0,
+        "k" | "kb" | "kib" => 1,
+        "m" | "mb" | "mib" => 2,
+        "g" | "gb" | "gib" => 3,
+        "t" | "tb" | "tib" => 4,
+        _ => return None,
+    };
+    Some((number * 1024f64.powi(exponent)).round() as u64)
+}
+
+/// the name and address of a From header as the archive shows it, like
+/// `Jane Doe <jane(dot)doe(at)example(dot)org>`, with runs of whitespace collapsed. the address is
+/// in the last `<...>`, since a name may hold a '<' of its own
+fn parse_from_header(from: &str) -> (String, String) {
+    let from = from.split_whitespace().collect::<Vec<_>>().join(" ");
+    match from.rsplit_once('<') {
+        Some((name, address)) if address.ends_with('>') => (
+            name.trim().trim_matches('"').trim().to_string(),
+            address
+                .trim_end_matches('>')
+                .trim()
+                .replace("(dot)", ".")
+                .replace("(at)", "@"),
+        ),
+        _ => (from, String::new()),
+    }
+}
+
+/// the author in the From cell of a message page. the cell may hold line breaks or several
+/// elements, like an organization after the name, whose texts must not run together
+fn from_cell_author(td: &scraper::ElementRef) -> (String, String) {
+    let text = td.text().collect::<Vec<_>>().join(" ");
+    parse_from_header(&html_escape::decode_html_entities(&text))
+}
+
+fn clean_subject_title(title: &str) -> String {
+    let title = title.trim();
+    // remove unicode emoji
+    let title = title.split('📎').next().unwrap_or(title).trim().to_string();
+    // replace multiple spaces with single one
+    let mut new_title = String::new();
+    let mut prev_char = ' ';
+    for char in title.chars() {
+        if char.is_whitespace() && !prev_char.is_whitespace() {
+            new_title.push(' ');
+        } else if !char.is_whitespace() {
+            new_title.push(char);
+        }
+        prev_char = char;
+    }
+    new_title
+}
+
+fn handle_table(
+    fetcher: &Fetcher,
+    table: &scraper::ElementRef,
+    date: NaiveDate,
+    mut handle_email_thread: impl FnMut(EmailThread) -> bool,
+) -> bool {
+    let tr_selector = Selector::parse("tr").unwrap();
+    let th_selector = Selector::parse("th").unwrap();
+    let td_selector = Selector::parse("td").unwrap();
+    let a_selector = Selector::parse("a").unwrap();
+    let mut handle_ok = true;
+
+    // the archive lists every message of a day, with no "N more messages" link to expand. a link
+    // to anything but a message means rows are hidden behind one now, which are not followed
+    for a in table.select(&a_selector) {
+        let href = a.value().attr("href").unwrap_or("");
+        if !href.starts_with("/message-id/") {
+            fetcher.warn(format!(
+                "the listing of {date} links to '{}' ({href}), threads behind it are missing",
+                element_text(&a)
+            ));
+        }
+    }
+    // rows with a message link that are not in the usual shape, which would go uncounted
+    let mut unread_rows = 0;
+
+    for tr in table.select(&tr_selector) {
+        // Get the thread subject from th
+        let subject_th = tr.select(&th_selector).next();
+        // Get author and time from td
+        let tds: Vec<_> = tr.select(&td_selector).collect();
+
+        // Skip table header rows
+        if tds.is_empty() {
+            continue;
+        }
+
+        let (Some(subject_td), [author_td, time_td, ..]) = (subject_th, tds.as_slice()) else {
+            if tr.select(&a_selector).next().is_some() {
+                unread_rows += 1;
+            }
+            continue;
+        };
+
+        // Get subject and URL
+        if let Some(a) = subject_td.select(&a_selector).next() {
+            let text = element_text(&a);
+            let clean_subject = clean_subject_title(&text);
+
+            let href = a.value().attr("href").unwrap_or("");
+            let author = element_text(author_td);
+            let time_str = time_td.text().collect::<String>().trim().to_string();
+            let id = href.trim_start_matches("/message-id/");
+            // without a time the row still belongs to its day, rather than to 1970
+            let time = NaiveTime::parse_from_str(&time_str, "%H:%M").unwrap_or_else(|_| {
+                fetcher.warn(format!(
+                    "no time in '{time_str}' for {id} on {date}, listing it at midnight"
+                ));
+                NaiveTime::MIN
+            });
+
+            if !handle_email_thread(EmailThread {
+                id: id.to_string(),
+                list: fetcher.list,
+                subject: clean_subject,
+                datetime: date.and_time(time),
+                author,
+                reply_count: None,
+                replies: None,
+            }) {
+                handle_ok = false;
+                break;
+            }
+        }
+    }
+    if unread_rows > 0 {
+        fetcher.warn(format!(
+            "the listing of {date} has {unread_rows} rows with a link that could not be read"
+        ));
+    }
+    handle_ok
+}
+
+/// The kind of a failed request, each kind is retried by its own [`RetryPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorClass {
+    /// DNS failure or refused connection, likely a longer outage
+    Connect,
+    /// the site did not answer in time, likely transient
+    Timeout,
+    /// the connection broke while sending the request or reading the response
+    Request,
+    /// the site answered 429 Too Many Requests or a 5xx, likely busy or restarting
+    Unavailable,
+    /// anything else, which is not worth retrying
+    Other,
+}
+
+impl ErrorClass {
+    fn of(err: &reqwest::Error) -> Self {
+        // a connect timeout is a connect failure first
+        if err.is_connect() {
+            ErrorClass::Connect
+        } else if err.is_timeout() {
+            ErrorClass::Timeout
+        } else if err.is_request() {
+            ErrorClass::Request
+        } else {
+            ErrorClass::Other
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// total number of tries, including the first one
+    pub attempts: u32,
+    /// delay before the first retry, doubled for every further retry. each delay is cut by up
+    /// to half at random, so clients failing together do not retry together
+    pub backoff: Duration,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum ScrapeError {
+    #[error("failed to fetch {url} after {attempts} attempt(s), {class:?} error")]
+    Network {
+        url: String,
+        class: ErrorClass,
+        attempts: u32,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("{url} answered {status} after {attempts} attempt(s)")]
+    Unavailable {
+        url: String,
+        status: reqwest::StatusCode,
+        attempts: u32,
+    },
+    #[error("{url} returned {content_type} instead of HTML")]
+    NotHtml { url: String, content_type: String },
+    #[error("{url} returned an empty page")]
+    EmptyPage { url: String },
+    #[error("no message {0} in the archive")]
+    NotFound(String),
+    #[error("unexpected layout of message {id}, no From, Subject and Date among {rows} header rows: {html_snippet}")]
+    UnexpectedLayout {
+        id: String,
+        rows: usize,
+        // the start of the offending markup, to diagnose layout changes from the logs alone
+        html_snippet: String,
+    },
+    #[error("not fetching {url}, the budget of {limit} {resource} is used up")]
+    BudgetExceeded {
+        url: String,
+        resource: &'static str,
+        limit: u64,
+    },
+    #[error("no '{selector}' on the page of message {id}")]
+    MissingElement { id: String, selector: &'static str },
+    #[error("invalid date '{text}' on the page of message {id}")]
+    InvalidDate { id: String, text: String },
+}
+
+/// characters of markup kept in [`ScrapeError::UnexpectedLayout`]
+const LAYOUT_SNIPPET_CHARS: usize = 600;
+
+/// the dropdown of a message page listing every message of its thread
+const THREAD_SELECT: &str = "select#thread_select";
+
+impl ScrapeError {
+    fn missing_element(id: &str, selector: &'static str) -> Self {
+        ScrapeError::MissingElement {
+            id: id.to_string(),
+            selector,
+        }
+    }
+
+    fn unexpected_layout(id: &str, rows: usize, html: &str) -> Self {
+        let mut html_snippet: String = html.chars().take(LAYOUT_SNIPPET_CHARS).collect();
+        if html_snippet.len() < html.len() {
+            html_snippet.push('…');
+        }
+        ScrapeError::UnexpectedLayout {
+            id: id.to_string(),
+            rows,
+            html_snippet,
+        }
+    }
+}
+
+/// Tuning knobs of a [`Fetcher`].
+#[derive(Debug, Clone)]
+pub struct FetchConfig {
+    /// retries of page fetches that could not connect
+    pub connect_retry: RetryPolicy,
+    /// retries of page fetches that timed out
+    pub timeout_retry: RetryPolicy,
+    /// retries of page fetches whose connection broke
+    pub request_retry: RetryPolicy,
+    /// retries of page fetches answered with 429 or a 5xx. a `Retry-After` the site sends is
+    /// waited for instead of the backoff
+    pub unavailable_retry: RetryPolicy,
+    /// the longest `Retry-After` waited for, the fetch fails right away when asked to wait
+    /// longer
+    pub max_retry_after: Duration,
+    /// at most this many attachment downloads run at the same time, so a big patch series does
+    /// not hog the connections interactive requests need
+    pub attachment_concurrency: usize,
+    /// at most this many attachment downloads start per second, unlimited when `None`
+    pub attachment_rate: Option<f64>,
+    /// at most this many pages are fetched at the same time by batch steps like
+    /// [`add_reply_counts`]
+    pub page_concurrency: usize,
+    /// at most this many lists are traversed at the same time by [`new_subjects_multi`], each
+    /// with its own pages and starter checks, all under the same delay and budgets
+    pub list_concurrency: usize,
+    /// HTTP version spoken to the archive
+    pub http_version: HttpVersion,
+    /// idle connections kept open per host, so the next request can skip the TCP and TLS
+    /// handshakes
+    pub pool_max_idle_per_host: usize,
+    /// how long an idle connection stays in the pool, forever when `None`
+    pub pool_idle_timeout: Option<Duration>,
+    /// interval of TCP keep-alive probes on open connections, none when `None`
+    pub tcp_keepalive: Option<Duration>,
+    /// how long a request may take from connecting to the end of the response, after which it
+    /// fails as a timeout. no limit when `None`
+    pub request_timeout: Option<Duration>,
+    /// sent as `User-Agent` with every request, so the archive's admins can tell who is crawling
+    pub user_agent: String,
+    /// a page fetch starts at least this long after the previous one finished, a simple way to
+    /// go easy on the archive no matter how fast pages come back. fetches of clones running at
+    /// the same time start at least this far apart
+    pub min_delay_between_requests: Option<Duration>,
+    /// at most this many requests start per second on average, page fetches, their retries and
+    /// attachment downloads alike. unlimited when `None`
+    pub request_rate: Option<f64>,
+    /// requests that may start back to back under `request_rate` after a quiet spell, before
+    /// the rate kicks in
+    pub request_burst: u32,
+    /// requests, retries and attachment downloads included, after which fetching fails with
+    /// [`ScrapeError::BudgetExceeded`], so a pathological query cannot run away
+    pub max_total_requests: Option<u64>,
+    /// bytes received, after which fetching fails with [`ScrapeError::BudgetExceeded`]
+    pub max_total_bytes: Option<u64>,
+    /// since-pages fetched ahead of the one being processed, so slow per-thread work like
+    /// starter checks overlaps with fetching. 0 fetches each page only once the previous one is
+    /// done
+    pub prefetch_pages: usize,
+    /// how long before the newest new thread of a since-page the next page starts. tokens go by
+    /// the minute, so that thread's minute is read again even at zero; more covers rows listed
+    /// out of order at the cost of reading more of them twice
+    pub since_overlap: Duration,
+    /// headers sent with every request, like an `Authorization` for a private mirror. none for
+    /// the public archive
+    pub extra_headers: reqwest::header::HeaderMap,
+    /// keep the cookies the site sets and send them back, for mirrors with a login session
+    pub cookie_store: bool,
+}
+
+/// HTTP version preference of the [`Fetcher`]'s client
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum HttpVersion {
+    /// HTTP/2 when the server offers it during the TLS handshake, HTTP/1.1 otherwise
+    Auto,
+    Http1Only,
+    /// HTTP/2 without negotiation, for servers known to speak it
+    Http2PriorKnowledge,
+}
+
+impl Default for FetchConfig {
+    fn default() -> Self {
+        FetchConfig {
+            connect_retry: RetryPolicy {
+                attempts: 3,
+                backoff: Duration::from_secs(10),
+            },
+            timeout_retry: RetryPolicy {
+                attempts: 4,
+                backoff: Duration::from_secs(1),
+            },
+            request_retry: RetryPolicy {
+                attempts: 3,
+                backoff: Duration::from_millis(500),
+            },
+            unavailable_retry: RetryPolicy {
+                attempts: 4,
+                backoff: Duration::from_secs(2),
+            },
+            max_retry_after: Duration::from_secs(120),
+            attachment_concurrency: 4,
+            attachment_rate: None,
+            page_concurrency: 4,
+            list_concurrency: 3,
+            http_version: HttpVersion::Auto,
+            pool_max_idle_per_host: 8,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            tcp_keepalive: Some(Duration::from_secs(60)),
+            request_timeout: Some(Duration::from_secs(30)),
+            user_agent: concat!("pgdevhub/", env!("CARGO_PKG_VERSION")).to_string(),
+            min_delay_between_requests: None,
+            request_rate: None,
+            request_burst: 1,
+            max_total_requests: None,
+            max_total_bytes: None,
+            prefetch_pages: 0,
+            since_overlap: Duration::ZERO,
+            extra_headers: reqwest::header::HeaderMap::new(),
+            cookie_store: false,
+        }
+    }
+}
+
+impl FetchConfig {
+    /// how long to wait before retrying a request that failed with `class` on its `attempt`th
+    /// try, or `None` when it should not be retried anymore
+    fn retry_delay(&self, class: ErrorClass, attempt: u32) -> Option<Duration> {
+        let policy = match class {
+            ErrorClass::Connect => self.connect_retry,
+            ErrorClass::Timeout => self.timeout_retry,
+            ErrorClass::Request => self.request_retry,
+            ErrorClass::Unavailable => self.unavailable_retry,
+            ErrorClass::Other => return None,
+        };
+        (attempt < policy.attempts).then(|| policy.backoff * 2u32.pow(attempt - 1))
+    }
+
+    /// how long to wait before retrying after `class` on the `attempt`th try, when the site
+    /// asked for `retry_after`. `None` when it should not be retried anymore
+    fn retry_wait(
+        &self,
+        class: ErrorClass,
+        attempt: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        let backoff = self.retry_delay(class, attempt)?;
+        match retry_after {
+            Some(wait) => (wait <= self.max_retry_after).then_some(wait),
+            None => Some(with_jitter(backoff)),
+        }
+    }
+
+    fn build_client(&self) -> Client {
+        let builder = Client::builder()
+            .pool_max_idle_per_host(self.pool_max_idle_per_host)
+            .pool_idle_timeout(self.pool_idle_timeout)
+            .tcp_keepalive(self.tcp_keepalive)
+            .user_agent(&self.user_agent)
+            .default_headers(self.extra_headers.clone())
+            .cookie_store(self.cookie_store);
+        let builder = match self.request_timeout {
+            Some(timeout) => builder.timeout(timeout),
+            None => builder,
+        };
+        let builder = match self.http_version {
+            HttpVersion::Auto => builder,
+            HttpVersion::Http1Only => builder.http1_only(),
+            HttpVersion::Http2PriorKnowledge => builder.http2_prior_knowledge(),
+        };
+        builder.build().expect("failed to build the HTTP client")
+    }
+}
+
+/// Requests made and bytes received so far, counted against the budget of a [`FetchConfig`].
+#[derive(Debug, Default)]
+struct Spent {
+    requests: AtomicU64,
+    bytes: AtomicU64,
+}
+
+/// when the page fetches of a [`Fetcher`] and its clones may start under
+/// [`FetchConfig::min_delay_between_requests`]
+#[derive(Debug, Default)]
+struct FetchSpacing {
+    // when the last fetch to finish finished
+    last_end: Option<Instant>,
+    // the start reserved by the latest fetch
+    last_start: Option<Instant>,
+}
+
+impl FetchSpacing {
+    /// the earliest start of a fetch `delay` after the previous one finished and after the
+    /// last reserved start, which it then reserves
+    fn reserve_start(&mut self, delay: Duration) -> Instant {
+        let start = [self.last_end, self.last_start]
+            .into_iter()
+            .flatten()
+            .map(|previous| previous + delay)
+            .fold(Instant::now(), Instant::max);
+        self.last_start = Some(start);
+        start
+    }
+}
+
+/// `delay` cut by up to half at random
+fn with_jitter(delay: Duration) -> Duration {
+    use std::hash::{BuildHasher, Hasher};
+
+    // a freshly keyed hasher is random enough to spread retries, without a dependency for it
+    let random = std::collections::hash_map::RandomState::new()
+        .build_hasher()
+        .finish();
+    delay.mul_f64(1.0 - random as f64 / u64::MAX as f64 / 2.0)
+}
+
+/// Spaces out request starts so that no more than `rate` of them begin per second, a token
+/// bucket holding `burst` tokens. it keeps when the bucket would be full again rather than a
+/// token count, waiters reserve their tokens in turn
+#[derive(Debug, Default)]
+struct Pacer {
+    full_at: Mutex<Option<Instant>>,
+}
+
+impl Pacer {
+    async fn wait(&self, rate: Option<f64>, burst: u32) {
+        let Some(rate) = rate else {
+            return;
+        };
+        let interval = Duration::from_secs_f64(1.0 / rate);
+        let now = Instant::now();
+        let start = {
+            let mut full_at = self.full_at.lock().unwrap();
+            let full = full_at.map_or(now, |full| full.max(now));
+            // the token is there once the bucket is no more than `burst - 1` tokens short
+            let start = (full - interval * burst.saturating_sub(1)).max(now);
+            *full_at = Some(full + interval);
+            start
+        };
+        tokio::time::sleep(start - now).await;
+    }
+}
+
+/// Fetches archive pages from a postgresql.org-like site.
+///
+/// All page urls are built relative to `site`, so the scraper can be pointed at a mirror or a
+/// local test server instead of [`PG_SITE`].
+#[derive(Debug, Clone)]
+pub struct Fetcher {
+    site: String,
+    // the list whose archive is traversed
+    list: MailingList,
+    config: FetchConfig,
+    attachment_pacer: Arc<Pacer>,
+    // keeps `request_rate`, shared by all clones and runs
+    request_pacer: Arc<Pacer>,
+    // shared by all clones so they reuse pooled connections, built on first use
+    client: Arc<OnceLock<Client>>,
+    // where pages come from, the network unless replaying fixtures
+    http: Arc<dyn http::HttpFetcher>,
+    // told about fetches, threads and errors as the run goes, printing them unless replaced
+    progress: Arc<dyn progress::ProgressObserver>,
+    // when page fetches may start, shared by all clones
+    fetch_spacing: Arc<Mutex<FetchSpacing>>,
+    // shared by all clones, a run is everything fetched until the budget is renewed
+    spent: Arc<Spent>,
+    // since-page urls visited by traversals, only kept when recording is enabled
+    since_log: Option<Arc<Mutex<Vec<String>>>>,
+    // thread starters learned so far, shared by all clones and kept across runs when set
+    starter_cache: Option<Arc<starter_cache::StarterCache>>,
+    // where message details are kept as they are fetched, when set
+    store: Option<Arc<dyn storage::Storage>>,
+    // the starter of every message seen in a thread dropdown during the run, keyed by message-id.
+    // a run is everything fetched until the budget is renewed, like for `spent`
+    run_starters: Arc<Mutex<HashMap<String, String>>>,
+    // cross-check listing datetimes against detail pages
+    verify_datetimes: bool,
+    // keep moderation and bounce notices in new and active subjects
+    include_notices: bool,
+    // tell thread starters by their raw headers rather than their subjects
+    thread_by_headers: bool,
+    // warnings printed so far, only kept when recording is enabled
+    warning_log: Option<Arc<Mutex<Vec<String>>>>,
+    // where "now" comes from for default ranges, the local time unless a test fixes it
+    clock: fn() -> NaiveDateTime,
+}
+
+fn local_now() -> NaiveDateTime {
+    chrono::Local::now().naive_local()
+}
+
+impl Default for Fetcher {
+    fn default() -> Self {
+        Fetcher::new(PG_SITE)
+    }
+}
+
+impl Fetcher {
+    pub fn new(site: &str) -> Self {
+        Fetcher {
+            site: site.trim_end_matches('/').to_string(),
+            list: MailingList::default(),
+            config: FetchConfig::default(),
+            attachment_pacer: Arc::default(),
+            request_pacer: Arc::default(),
+            client: Arc::default(),
+            http: Arc::new(http::Network),
+            progress: Arc::new(progress::Log),
+            fetch_spacing: Arc::default(),
+            spent: Arc::default(),
+            since_log: None,
+            starter_cache: None,
+            store: None,
+            run_starters: Arc::default(),
+            verify_datetimes: false,
+            include_notices: false,
+            thread_by_headers: false,
+            warning_log: None,
+            clock: local_now,
+        }
+    }
+
+    pub fn with_list(mut self, list: MailingList) -> Self {
+        self.list = list;
+        self
+    }
+
+    pub fn with_config(mut self, config: FetchConfig) -> Self {
+        self.config = config;
+        self.client = Arc::default();
+        self
+    }
+
+    /// take the current time from `clock`, so ranges ending now can be tested
+    #[cfg(test)]
+    fn with_clock(mut self, clock: fn() -> NaiveDateTime) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn now(&self) -> NaiveDateTime {
+        (self.clock)()
+    }
+
+    /// the `span` up to now, as start and end. a span longer than the calendar starts at its
+    /// beginning
+    pub fn last(&self, span: TimeDelta) -> (NaiveDateTime, NaiveDateTime) {
+        let end = self.now();
+        (
+            end.checked_sub_signed(span).unwrap_or(NaiveDateTime::MIN),
+            end,
+        )
+    }
+
+    /// start counting the budget from zero again, for this fetcher and the clones made from it
+    /// from now on
+    fn with_new_budget(mut self) -> Self {
+        self.spent = Arc::default();
+        self.run_starters = Arc::default();
+        self
+    }
+
+    /// count a request about to be made to `url` and wait for its turn under the request rate,
+    /// or refuse it when the budget is used up
+    async fn spend_request(&self, url: &str) -> Result<(), ScrapeError> {
+        let exceeded = |resource, limit| ScrapeError::BudgetExceeded {
+            url: url.to_string(),
+            resource,
+            limit,
+        };
+        if let Some(max) = self.config.max_total_bytes {
+            if self.spent.bytes.load(Ordering::SeqCst) >= max {
+                return Err(exceeded("bytes", max));
+            }
+        }
+        let requests = self.spent.requests.fetch_add(1, Ordering::SeqCst);
+        if let Some(max) = self.config.max_total_requests {
+            if requests >= max {
+                return Err(exceeded("requests", max));
+            }
+        }
+        self.request_pacer
+            .wait(self.config.request_rate, self.config.request_burst)
+            .await;
+        std::result::Result::Ok(())
+    }
+
+    fn spend_bytes(&self, bytes: usize) {
+        self.spent.bytes.fetch_add(bytes as u64, Ordering::SeqCst);
+    }
+
+    fn client(&self) -> &Client {
+        self.client.get_or_init(|| self.config.build_client())
+    }
+
+    /// warn when a listing and the detail page of the same message disagree on its datetime
+    pub fn verify_datetimes(mut self) -> Self {
+        self.verify_datetimes = true;
+        self
+    }
+
+    /// list moderation and bounce notices among new and active subjects, see [`is_list_notice`]
+    pub fn include_notices(mut self) -> Self {
+        self.include_notices = true;
+        self
+    }
+
+    /// tell the messages starting threads by their In-Reply-To and References headers instead
+    /// of guessing from their subjects, so that forwards, replies under a new subject and
+    /// replies with prefixes like `AW:` are threaded as the archive threads them. costs a fetch
+    /// of the raw view of every listed message whose thread is not known yet, and a thread
+    /// dropdown only for replies to messages not seen before
+    pub fn thread_by_headers(mut self) -> Self {
+        self.thread_by_headers = true;
+        self
+    }
+
+    /// get pages through `http` instead of from the network, like [`http::Replay`] for tests
+    pub fn with_http(mut self, http: impl http::HttpFetcher + 'static) -> Self {
+        self.http = Arc::new(http);
+        self
+    }
+
+    /// report the progress of runs to `progress` instead of printing it
+    pub fn with_progress(mut self, progress: impl progress::ProgressObserver + 'static) -> Self {
+        self.progress = Arc::new(progress);
+        self
+    }
+
+    /// keep every message detail fetched in `store`
+    pub fn with_store(mut self, store: Arc<dyn storage::Storage>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// look up thread starters in the cache file at `path` before fetching message pages, and
+    /// add the ones fetched to it
+    pub fn with_starter_cache(mut self, path: &std::path::Path) -> Result<Self> {
+        self.starter_cache = Some(Arc::new(starter_cache::StarterCache::open(path)?));
+        Ok(self)
+    }
+
+    /// remember every since-page url visited, so a run can be reproduced page by page
+    pub fn record_since_urls(mut self) -> Self {
+        self.since_log = Some(Arc::default());
+        self
+    }
+
+    /// since-page urls visited so far, in visiting order
+    pub fn since_urls(&self) -> Vec<String> {
+        self.since_log
+            .as_ref()
+            .map(|log| log.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// keep every warning printed, besides printing it
+    #[cfg(test)]
+    fn record_warnings(mut self) -> Self {
+        self.warning_log = Some(Arc::default());
+        self
+    }
+
+    /// warnings printed so far, in order
+    #[cfg(test)]
+    fn warnings(&self) -> Vec<String> {
+        self.warning_log
+            .as_ref()
+            .map(|log| log.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    /// report something odd about the pages that does not stop the run
+    fn warn(&self, warning: String) {
+        self.progress.error(&warning);
+        if let Some(log) = &self.warning_log {
+            log.lock().unwrap().push(warning);
+        }
+    }
+
+    pub fn message_url(&self, id: &str) -> String {
+        format!("{}{MESSAGE_PATH}/{id}", self.site)
+    }
+
+    /// the page showing every message of the thread of `id`
+    fn flat_thread_url(&self, id: &str) -> String {
+        format!("{}{MESSAGE_PATH}/flat/{id}", self.site)
+    }
+
+    fn since_url(&self, since: NaiveDateTime) -> String {
+        format!(
+            "{}{LIST_PATH}/{}/since/{}",
+            self.site,
+            self.list.name(),
+            since.format("%Y%m%d%H%M")
+        )
+    }
+
+    /// the archive page of the month `month` is in, which lists its first messages
+    fn month_url(&self, month: NaiveDate) -> String {
+        format!(
+            "{}{LIST_PATH}/{}/{}/",
+            self.site,
+            self.list.name(),
+            month.format("%Y-%m")
+        )
+    }
+
+    /// the listing page of the messages right before `before`
+    fn before_url(&self, before: NaiveDateTime) -> String {
+        format!(
+            "{}{LIST_PATH}/{}/before/{}",
+            self.site,
+            self.list.name(),
+            before.format("%Y%m%d%H%M")
+        )
+    }
+
+    pub fn attachment_url(&self, attachment: &ThreadAttachment) -> String {
+        format!("{}{}", self.site, attachment.href)
+    }
+
+    /// the raw view sits behind basic auth with public credentials, to keep crawlers out
+    fn raw_message_url(&self, id: &str) -> String {
+        let mut url = format!("{}{MESSAGE_PATH}/raw/{id}", self.site);
+        if let Some(mut with_auth) = url::Url::parse(&url).ok().filter(|url| url.has_host()) {
+            let _ = with_auth.set_username(RAW_VIEW_AUTH.0);
+            let _ = with_auth.set_password(Some(RAW_VIEW_AUTH.1));
+            url = with_auth.into();
+        }
+        url
+    }
+
+    async fn get_document(&self, url: &str) -> Result<Html> {
+        let Page {
+            content_type, body, ..
+        } = self.get_page(url).await?;
+
+        // a JSON error page or a PDF parses into a DOM just fine, only one where every selector
+        // silently comes up empty
+        if let Some(content_type) = content_type {
+            let mime = content_type.split(';').next().unwrap_or("").trim();
+            if !mime.eq_ignore_ascii_case("text/html")
+                && !mime.eq_ignore_ascii_case("application/xhtml+xml")
+            {
+                return Err(ScrapeError::NotHtml {
+                    url: url.to_string(),
+                    content_type,
+                }
+                .into());
+            }
+        }
+        if body.trim().is_empty() {
+            return Err(ScrapeError::EmptyPage {
+                url: url.to_string(),
+            }
+            .into());
+        }
+
+        let document = Html::parse_document(&body);
+        Ok(document)
+    }
+
+    /// fetch `url`, keeping the polite delay
+    async fn get_page(&self, url: &str) -> Result<Page> {
+        let Some(delay) = self.config.min_delay_between_requests else {
+            return self.fetch_page(url).await;
+        };
+        // only the start is reserved under the lock, so a slow fetch holds back no other
+        let start = self.fetch_spacing.lock().unwrap().reserve_start(delay);
+        tokio::time::sleep_until(start.into()).await;
+        let result = self.fetch_page(url).await;
+        let mut spacing = self.fetch_spacing.lock().unwrap();
+        spacing.last_end = spacing.last_end.max(Some(Instant::now()));
+        result
+    }
+
+    async fn fetch_page(&self, url: &str) -> Result<Page> {
+        self.fetch_page_with_retries(url)
+            .instrument(tracing::debug_span!("fetch", url))
+            .await
+    }
+
+    async fn fetch_page_with_retries(&self, url: &str) -> Result<Page> {
+        let client = self.client();
+        let start_time = std::time::Instant::now();
+        let mut attempt = 1;
+        let page = loop {
+            self.spend_request(url).await?;
+            let (class, retry_after, err) = match self.http.get(client, url).await {
+                Result::Ok(page) => {
+                    self.spend_bytes(page.body.len());
+                    let status = page.status;
+                    if !(status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                        || status.is_server_error())
+                    {
+                        break page;
+                    }
+                    let err = ScrapeError::Unavailable {
+                        url: url.to_string(),
+                        status,
+                        attempts: attempt,
+                    };
+                    (ErrorClass::Unavailable, page.retry_after, err)
+                }
+                // only network errors are worth retrying
+                Err(err) => {
+                    let err = err.downcast::<reqwest::Error>()?;
+                    let class = ErrorClass::of(&err);
+                    let err = ScrapeError::Network {
+                        url: url.to_string(),
+                        class,
+                        attempts: attempt,
+                        source: err,
+                    };
+                    (class, None, err)
+                }
+            };
+            let Some(delay) = self.config.retry_wait(class, attempt, retry_after) else {
+                return Err(err.into());
+            };
+            self.warn(format!(
+                "get document from {url} failed ({class:?}), retrying in {delay:?}"
+            ));
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        };
+        self.progress.page_fetched(url, start_time.elapsed());
+        Ok(page)
+    }
+}
+
+/// a fetched page
+#[derive(Debug)]
+pub struct Page {
+    pub status: reqwest::StatusCode,
+    pub content_type: Option<String>,
+    /// how long the site asked to wait before trying again, with a 429 or a 503
+    pub retry_after: Option<Duration>,
+    pub body: String,
+}
+
+/// an attachment's bytes as served by the archive
+#[derive(Debug)]
+struct AttachmentContent {
+    content_type: String,
+    bytes: Vec<u8>,
+}
+
+/// download `attachment`, or return `None` without reading it all when it is larger than
+/// `max_bytes`
+async fn download_attachment(
+    fetcher: &Fetcher,
+    attachment: &ThreadAttachment,
+    max_bytes: usize,
+) -> Result<Option<AttachmentContent>> {
+    let url = fetcher.attachment_url(attachment);
+    fetcher
+        .attachment_pacer
+        .wait(fetcher.config.attachment_rate, 1)
+        .await;
+    fetcher.spend_request(&url).await?;
+    let mut response = fetcher
+        .client()
+        .get(&url)
+        .send()
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("failed to download {url}"))?;
+    if response
+        .content_length()
+        .is_some_and(|len| len > max_bytes as u64)
+    {
+        return Ok(None);
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+
+    // the length header may be missing, so stop reading at the chunk that goes past the limit
+    let mut bytes = Vec::new();
+    while let Some(chunk) = response
+        .chunk()
+        .await
+        .with_context(|| format!("failed to read {url}"))?
+    {
+        bytes.extend_from_slice(&chunk);
+        if bytes.len() > max_bytes {
+            break;
+        }
+    }
+    fetcher.spend_bytes(bytes.len());
+    if bytes.len() > max_bytes {
+        return Ok(None);
+    }
+    Ok(Some(AttachmentContent {
+        content_type,
+        bytes,
+    }))
+}
+
+/// download all `attachments` like [`download_attachment`], running at most
+/// `attachment_concurrency` downloads at a time. results are in the order of `attachments`.
+async fn download_attachments(
+    fetcher: &Fetcher,
+    attachments: &[ThreadAttachment],
+    max_bytes: usize,
+) -> Vec<Result<Option<AttachmentContent>>> {
+    map_concurrently(
+        attachments,
+        fetcher.config.attachment_concurrency,
+        |attachment| download_attachment(fetcher, attachment, max_bytes),
+    )
+    .await
+}
+
+/// `f` of every item, at most `limit` of them under way at a time, in the order of `items`
+async fn map_concurrently<'a, T, F: std::future::Future>(
+    items: &'a [T],
+    limit: usize,
+    f: impl FnMut(&'a T) -> F,
+) -> Vec<F::Output> {
+    // a future does nothing until it is polled, so they can all be made up front
+    let futures: Vec<F> = items.iter().map(f).collect();
+    futures_util::stream::iter(futures)
+        .buffered(limit.clamp(1, items.len().max(1)))
+        .collect()
+        .await
+}
+
+/// fill in the `reply_count` of each thread from the thread dropdown of its page, fetching at
+/// most `page_concurrency` pages at a time. a thread whose page cannot be fetched keeps `None`.
+pub async fn add_reply_counts(fetcher: &Fetcher, threads: &mut [EmailThread]) {
+    let counts = map_concurrently(
+        threads,
+        fetcher.config.page_concurrency,
+        |thread| async move {
+            thread_message_ids(fetcher, &thread.id)
+                .await
+                .inspect_err(|err| {
+                    fetcher.warn(format!(
+                        "failed to count the replies of {}: {err:#}",
+                        thread.id
+                    ))
+                })
+                .ok()
+                .map(|ids| ids.len().saturating_sub(1))
+        },
+    )
+    .await;
+    for (thread, count) in threads.iter_mut().zip(counts) {
+        thread.reply_count = count;
+    }
+}
+
+/// fill in the `replies` of each thread, and so its `reply_count`, like [`add_reply_counts`]
+pub async fn add_replies(fetcher: &Fetcher, threads: &mut [EmailThread]) {
+    let replies = map_concurrently(
+        threads,
+        fetcher.config.page_concurrency,
+        |thread| async move {
+            thread_message_ids(fetcher, &thread.id)
+                .await
+                .inspect_err(|err| {
+                    fetcher.warn(format!(
+                        "failed to list the replies of {}: {err:#}",
+                        thread.id
+                    ))
+                })
+                .ok()
+                .map(|ids| {
+                    ids.into_iter()
+                        .filter(|id| *id != thread.id)
+                        .collect::<Vec<_>>()
+                })
+        },
+    )
+    .await;
+    for (thread, replies) in threads.iter_mut().zip(replies) {
+        thread.reply_count = replies.as_ref().map(Vec::len);
+        thread.replies = replies;
+    }
+}
+
+/// handle threads of each day found in the page.
+/// when `handle` returns `false`, the processing is stopped.
+async fn for_each_thread(
+    fetcher: &Fetcher,
+    url: &str,
+    handle: impl FnMut(EmailThread) -> bool,
+) -> Result<()> {
+    let document = fetcher.get_document(url).await?;
+    for_each_thread_in(fetcher, &document, url, handle);
+    Ok(())
+}
+
+/// like [`for_each_thread`], for the listing `document` fetched from `url`
+fn for_each_thread_in(
+    fetcher: &Fetcher,
+    document: &Html,
+    url: &str,
+    mut handle: impl FnMut(EmailThread) -> bool,
+) {
+    let mut handle = |thread: EmailThread| {
+        tracing::trace!(id = thread.id, subject = thread.subject, datetime = %thread.datetime, "thread listed");
+        fetcher.progress.thread_found(&thread);
+        handle(thread)
+    };
+    // each day is a date header followed by the table of its threads
+    let h2_selector = Selector::parse("h2").unwrap();
+    for h2 in document.select(&h2_selector) {
+        let Some(table) = h2
+            .next_siblings()
+            .find_map(scraper::ElementRef::wrap)
+            .filter(|next| next.value().name() == "table")
+        else {
+            continue;
+        };
+        let date_text = h2.text().collect::<String>();
+        let date = match transform_date(&date_text) {
+            Result::Ok(date) => date,
+            Err(err) => {
+                // a whole day of threads would go missing without a word
+                fetcher.warn(format!("skipping the threads of a day on {url}: {err:#}"));
+                continue;
+            }
+        };
+        if !handle_table(fetcher, &table, date, &mut handle) {
+            break;
+        }
+    }
+}
+
+/// whether a since-page is past the newest message: the archive answers a since-token beyond it
+/// with an empty page, or redirects it to a page without any day of threads
+fn is_caught_up(page: &Result<Html>) -> bool {
+    match page {
+        Result::Ok(document) => !document
+            .select(&Selector::parse("h2").unwrap())
+            .any(|h2| transform_date(&h2.text().collect::<String>()).is_ok()),
+        Err(err) => matches!(
+            err.downcast_ref::<ScrapeError>(),
+            Some(ScrapeError::EmptyPage { .. })
+        ),
+    }
+}
+
+/// print the threads of one since-page at a time, starting at `since`, and wait for Enter
+/// before fetching the next page. `q` or the end of `input` quits.
+pub async fn browse(
+    fetcher: &Fetcher,
+    since: NaiveDateTime,
+    mut input: impl std::io::BufRead,
+    mut output: impl std::io::Write,
+) -> Result<()> {
+    let mut since = since;
+    // the first threads of a page may be the last ones of the previous page
+    let mut seen_ids = HashSet::new();
+    let mut number = 0;
+    loop {
+        let mut page = Vec::new();
+        let mut newest_listed = None;
+        for_each_thread(fetcher, &fetcher.since_url(since), |thread| {
+            newest_listed = newest_listed.max(Some(thread.datetime));
+            if seen_ids.insert(thread.id.clone()) {
+                page.push(thread);
+            }
+            true
+        })
+        .await?;
+        let Some(newest_listed) = newest_listed else {
+            writeln!(output, "No more threads.")?;
+            return Ok(());
+        };
+        let Some(last) = page.last() else {
+            // a page full of threads of the minute it starts at, all seen already. the later
+            // ones are past that minute
+            since = newest_listed.max(since).with_second(0).unwrap() + TimeDelta::minutes(1);
+            continue;
+        };
+        since = last.datetime;
+
+        for thread in &page {
+            number += 1;
+            writeln!(
+                output,
+                "{number:>4}. {}  {}  {}",
+                thread.datetime.format("%Y-%m-%d %H:%M"),
+                thread.author,
+                thread.subject
+            )?;
+        }
+        write!(output, "Press Enter for the next page, q to quit: ")?;
+        output.flush()?;
+
+        let mut answer = String::new();
+        if input.read_line(&mut answer)? == 0 || answer.trim().eq_ignore_ascii_case("q") {
+            return Ok(());
+        }
+    }
+}
+
+// NaiveDateTime is copyable
+async fn get_threads_between<T>(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    mut handle: impl FnMut(EmailThread) -> Result<Option<T>>,
+) -> Result<Vec<T>> {
+    let mut threads: Vec<T> = Vec::new();
+    let mut pages = SincePages::new(fetcher, start_date, end_date);
+    while let Some(page) = pages.next().await? {
+        for thread in page {
+            threads.extend(handle(thread)?);
+        }
+    }
+    Ok(threads)
+}
+
+/// The since-pages between `start_date` and `end_date`, walked one at a time, each giving the
+/// threads in the range that were not on an earlier page.
+struct SinceWalk {
+    fetcher: Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    // where the next page starts
+    since: NaiveDateTime,
+    // a page usually starts with threads of the previous page, as the next page starts at the
+    // minute of the last thread seen. For example, we get some threads published parallelly at
+    // 20250212-13:58, and get next page from '/list/pgsql-hackers/since/202502121358', then we
+    // will get the same threads again of time 20250212-13:58. We need to remove the duplicates.
+    seen_ids: HashSet<String>,
+    overlap: TimeDelta,
+    // threads of the previous page from the minute the current one starts at, which it has to
+    // list again if the listing is in order
+    expected_again: Vec<String>,
+    // the range is done, the archive caught up with or a page failed
+    done: bool,
+}
+
+impl SinceWalk {
+    fn new(fetcher: &Fetcher, start_date: NaiveDateTime, end_date: NaiveDateTime) -> Self {
+        // nothing can have been posted yet. a day of slack as the archive's clock may be ahead
+        let done = start_date > fetcher.now() + TimeDelta::days(1);
+        if done {
+            fetcher.progress.info(&format!(
+                "since={start_date:#?} is in the future, nothing to fetch"
+            ));
+        }
+        SinceWalk {
+            fetcher: fetcher.clone(),
+            start_date,
+            end_date,
+            since: start_date,
+            seen_ids: HashSet::new(),
+            overlap: TimeDelta::from_std(fetcher.config.since_overlap).unwrap_or(TimeDelta::MAX),
+            expected_again: Vec::new(),
+            done,
+        }
+    }
+
+    /// the new threads of the next since-page, `None` once the range is done
+    async fn next(&mut self) -> Result<Option<Vec<EmailThread>>> {
+        if self.done {
+            return Ok(None);
+        }
+        // until this page turns out to have a next one
+        self.done = true;
+        let fetcher = &self.fetcher;
+        let (start_date, end_date) = (self.start_date, self.end_date);
+        tracing::debug!(since = %self.since, %end_date, "traversing a since-page");
+        let current_url = fetcher.since_url(self.since);
+        if let Some(log) = &fetcher.since_log {
+            log.lock().unwrap().push(current_url.clone());
+        }
+
+        let document = fetcher.get_document(&current_url).await;
+        if is_caught_up(&document) {
+            fetcher.progress.info(&format!(
+                "{current_url} lists no day, caught up with the archive"
+            ));
+            return Ok(None);
+        }
+        let document = document.context("Failed to process email threads")?;
+
+        // rows are not trusted to be in time order, so look at the whole page and move on from
+        // the newest new thread on it
+        let mut listed = HashSet::new();
+        let mut oldest = NaiveDateTime::MAX;
+        let mut page = Vec::new();
+        let mut past_end = false;
+        for_each_thread_in(fetcher, &document, &current_url, |thread| {
+            listed.insert(thread.id.clone());
+            oldest = oldest.min(thread.datetime);
+            if thread.datetime > end_date {
+                past_end = true;
+            } else if thread.datetime >= start_date && self.seen_ids.insert(thread.id.clone()) {
+                page.push(thread);
+            }
+            true
+        });
+        let missing = self
+            .expected_again
+            .iter()
+            .filter(|id| !listed.contains(*id))
+            .count();
+        if missing > 0 {
+            fetcher.warn(format!(
+                "{current_url} does not list {missing} threads the previous page had from its \
+                 start on, the listing is out of order or changed and threads may be missed"
+            ));
+        }
+
+        // a page with nothing new means we are done, even if the site keeps answering
+        let Some(newest) = page.iter().map(|thread| thread.datetime).max() else {
+            return Ok(None);
+        };
+        // `since_overlap` before it, but past the oldest minute of this page, whose rows would
+        // all come back
+        let next = newest
+            .checked_sub_signed(self.overlap)
+            .map(|next| next.with_second(0).unwrap())
+            .filter(|next| *next > oldest)
+            .unwrap_or(newest.with_second(0).unwrap());
+        self.expected_again = page
+            .iter()
+            .filter(|thread| thread.datetime >= next)
+            .map(|thread| thread.id.clone())
+            .collect();
+        self.since = next;
+        self.done = past_end;
+        Ok(Some(page))
+    }
+}
+
+/// The pages of a [`SinceWalk`]. With [`FetchConfig::prefetch_pages`] they are walked by a task
+/// of their own, which fetches ahead while the pages already there are worked through, in the
+/// same order and without the duplicates.
+enum SincePages {
+    Walked(Box<SinceWalk>),
+    Prefetched {
+        pages: mpsc::Receiver<Result<Vec<EmailThread>>>,
+        walker: tokio::task::JoinHandle<()>,
+    },
+}
+
+impl SincePages {
+    fn new(fetcher: &Fetcher, start_date: NaiveDateTime, end_date: NaiveDateTime) -> Self {
+        let mut walk = SinceWalk::new(fetcher, start_date, end_date);
+        let prefetch_pages = fetcher.config.prefetch_pages;
+        if prefetch_pages == 0 {
+            return SincePages::Walked(Box::new(walk));
+        }
+        let (page_tx, pages) = mpsc::channel(prefetch_pages);
+        let walker = tokio::spawn(async move {
+            // a page is only fetched once there is room for it. a failure ends the walk, and so
+            // does dropping the receiver
+            while let Result::Ok(permit) = page_tx.reserve().await {
+                let Some(page) = walk.next().await.transpose() else {
+                    break;
+                };
+                let failed = page.is_err();
+                permit.send(page);
+                if failed {
+                    break;
+                }
+            }
+        });
+        SincePages::Prefetched { pages, walker }
+    }
+
+    /// the new threads of the next since-page, `None` once the range is done
+    async fn next(&mut self) -> Result<Option<Vec<EmailThread>>> {
+        match self {
+            SincePages::Walked(walk) => walk.next().await,
+            SincePages::Prefetched { pages, .. } => pages.recv().await.transpose(),
+        }
+    }
+}
+
+impl Drop for SincePages {
+    fn drop(&mut self) {
+        // the page being fetched is for nobody anymore
+        if let SincePages::Prefetched { walker, .. } = self {
+            walker.abort();
+        }
+    }
+}
+
+/// fetch and parse exactly the since-pages of `tokens`, in order, instead of working out the
+/// next page from the last thread seen. a token is either the `YYYYMMDDhhmm` part of a since
+/// url or a whole url as printed by `--print-since-urls`, so a recorded run can be replayed
+/// page by page.
+pub async fn replay_since_pages<T>(
+    fetcher: &Fetcher,
+    tokens: &[String],
+    mut handle: impl FnMut(EmailThread) -> Option<T>,
+) -> Result<Vec<T>> {
+    let mut seen_ids = HashSet::new();
+    let mut threads = Vec::new();
+    for token in tokens {
+        let token = token.trim_end_matches('/');
+        let token = token.rsplit('/').next().unwrap_or(token);
+        let since = NaiveDateTime::parse_from_str(token, "%Y%m%d%H%M")
+            .with_context(|| format!("'{token}' is not a since-page token"))?;
+        let url = fetcher.since_url(since);
+        if let Some(log) = &fetcher.since_log {
+            log.lock().unwrap().push(url.clone());
+        }
+        for_each_thread(fetcher, &url, |thread| {
+            // consecutive pages overlap on their boundary minute
+            if seen_ids.insert(thread.id.clone()) {
+                threads.extend(handle(thread));
+            }
+            true
+        })
+        .await
+        .with_context(|| format!("failed to replay {url}"))?;
+    }
+    Ok(threads)
+}
+
+/// list the threads of the fetcher's list posted since the last sync, keeping them in `storage`
+/// with where the sync got to, so that running it again, say from cron, only fetches the
+/// since-pages from there on. the first sync of a list starts at `first_since`. the threads are
+/// returned in listing order, without the ones of the previous sync that its since-page lists
+/// again. a sync that fails leaves the cursor where it was.
+pub async fn sync(
+    fetcher: &Fetcher,
+    storage: &dyn storage::Storage,
+    first_since: NaiveDateTime,
+) -> Result<Vec<EmailThread>> {
+    let cursor = storage.cursor(fetcher.list)?;
+    // listings are to the minute, the cursor's since-page starts with the threads it was set to
+    let kept: HashSet<String> = match cursor {
+        Some(cursor) => storage
+            .threads_between(fetcher.list, cursor, cursor)?
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect(),
+        None => HashSet::new(),
+    };
+    // a day of slack as the archive's clock may be ahead
+    let end_date = fetcher.now() + TimeDelta::days(1);
+    let threads = get_threads_between(fetcher, cursor.unwrap_or(first_since), end_date, |thread| {
+        if kept.contains(&thread.id) {
+            return Ok(None);
+        }
+        storage.upsert_thread(&thread)?;
+        Ok(Some(thread))
+    })
+    .await?;
+    if let Some(newest) = threads.iter().map(|thread| thread.datetime).max() {
+        storage.set_cursor(fetcher.list, newest)?;
+    }
+    Ok(threads)
+}
+
+/// keep in `storage` every thread of the fetcher's list listed from `since` up to `until`, which
+/// is left out, a month at a time: the archive page of the month, then the since-pages after it
+/// to the end of the month. every month done is checkpointed in `storage`, so an interrupted
+/// backfill from the same `since` resumes with the month it stopped in. returns the number of
+/// threads kept by this run.
+pub async fn backfill(
+    fetcher: &Fetcher,
+    storage: &dyn storage::Storage,
+    since: NaiveDateTime,
+    until: NaiveDateTime,
+) -> Result<usize> {
+    let mut start = storage.backfilled(fetcher.list, since)?.unwrap_or(since);
+    let mut kept = 0;
+    while start < until {
+        let month = start.date().with_day(1).unwrap();
+        let chunk_end = until.min(
+            month
+                .checked_add_months(chrono::Months::new(1))
+                .context("backfill past the end of time")?
+                .into(),
+        );
+        let last = chunk_end - TimeDelta::seconds(1);
+        let mut seen = HashSet::new();
+
+        let url = fetcher.month_url(month);
+        let document = fetcher.get_document(&url).await;
+        // a month without messages has nothing to continue from either
+        if !is_caught_up(&document) {
+            let document = document.with_context(|| format!("failed to backfill {url}"))?;
+            let mut listed = Vec::new();
+            for_each_thread_in(fetcher, &document, &url, |thread| {
+                if (start..=last).contains(&thread.datetime) && seen.insert(thread.id.clone()) {
+                    listed.push(thread);
+                }
+                true
+            });
+            drop(document);
+            // the month page may not list the whole month, the since-pages go on from its
+            // newest thread
+            let resume = listed.iter().map(|thread| thread.datetime).max();
+            for thread in &listed {
+                storage.upsert_thread(thread)?;
+            }
+            get_threads_between(fetcher, resume.unwrap_or(start), last, |thread| {
+                if !seen.insert(thread.id.clone()) {
+                    return Ok(None);
+                }
+                storage.upsert_thread(&thread)?;
+                Ok(Some(()))
+            })
+            .await?;
+        }
+
+        storage.set_backfilled(fetcher.list, since, chunk_end)?;
+        fetcher.progress.info(&format!(
+            "backfilled {} up to {chunk_end}, {} threads",
+            month.format("%Y-%m"),
+            seen.len()
+        ));
+        kept += seen.len();
+        start = chunk_end;
+    }
+    Ok(kept)
+}
+
+/// like [`get_threads_between`], but walk the before-pages back from `end_date`, so `handle`
+/// sees the threads newest first as they are fetched, without buffering the whole range
+pub async fn get_threads_between_newest_first<T>(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    mut handle: impl FnMut(EmailThread) -> Result<Option<T>>,
+) -> Result<Vec<T>> {
+    let mut threads = Vec::new();
+    let mut pages = NewestFirst::new(fetcher, start_date, end_date);
+    while let Some(page) = pages.next().await? {
+        for thread in page {
+            threads.extend(handle(thread)?);
+        }
+    }
+    Ok(threads)
+}
+
+/// The threads between `start_date` and `end_date`, newest first, a before-page at a time.
+struct NewestFirst {
+    fetcher: Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    // where the next page ends
+    before: NaiveDateTime,
+    seen_ids: HashSet<String>,
+    // the range is done or a page failed
+    done: bool,
+}
+
+impl NewestFirst {
+    fn new(fetcher: &Fetcher, start_date: NaiveDateTime, end_date: NaiveDateTime) -> Self {
+        NewestFirst {
+            fetcher: fetcher.clone(),
+            start_date,
+            end_date,
+            // the listings only go down to the minute, start right after the minute of `end_date`
+            before: end_date.with_second(0).unwrap() + TimeDelta::minutes(1),
+            seen_ids: HashSet::new(),
+            done: false,
+        }
+    }
+
+    /// the threads of the next before-page that were not on a later one, newest first. `None`
+    /// once the range is done
+    async fn next(&mut self) -> Result<Option<Vec<EmailThread>>> {
+        if self.done {
+            return Ok(None);
+        }
+        // until this page turns out to have an earlier one
+        self.done = true;
+        let url = self.fetcher.before_url(self.before);
+        if let Some(log) = &self.fetcher.since_log {
+            log.lock().unwrap().push(url.clone());
+        }
+        // a page lists its threads oldest first
+        let mut page = Vec::new();
+        for_each_thread(&self.fetcher, &url, |thread| {
+            page.push(thread);
+            true
+        })
+        .await
+        .context("Failed to process email threads")?;
+
+        let Some(oldest) = page.iter().map(|thread| thread.datetime).min() else {
+            return Ok(None);
+        };
+        let mut new = Vec::new();
+        for thread in page.into_iter().rev() {
+            if thread.datetime > self.end_date || !self.seen_ids.insert(thread.id.clone()) {
+                continue;
+            }
+            if thread.datetime < self.start_date {
+                return Ok(Some(new));
+            }
+            new.push(thread);
+        }
+        if new.is_empty() {
+            return Ok(None);
+        }
+        // the page may have stopped in the middle of its oldest minute, so look at that minute
+        // again; threads seen already are skipped
+        self.before = oldest + TimeDelta::minutes(1);
+        self.done = false;
+        Ok(Some(new))
+    }
+}
+
+/// the `limit` newest thread starters, newest first, fetching only as many pages as needed
+async fn latest_new_threads(fetcher: &Fetcher, limit: usize) -> Result<Vec<EmailThread>> {
+    let mut threads = Vec::new();
+    let mut pages = NewestFirst::new(fetcher, NaiveDateTime::MIN, fetcher.now());
+    while threads.len() < limit {
+        let Some(page) = pages.next().await? else {
+            break;
+        };
+        for thread in page {
+            if is_thread_starter(fetcher, &thread).await? {
+                threads.push(thread);
+            }
+            if threads.len() == limit {
+                break;
+            }
+        }
+    }
+    Ok(threads)
+}
+
+/// The threads started between `start_date` and `end_date`, both included, in the order the
+/// archive lists them. Replies are left out, as are moderation and bounce notices unless the
+/// fetcher includes them.
+pub async fn get_new_subjects_between(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+) -> Result<Vec<EmailThread>> {
+    get_new_subjects_between_by(fetcher, start_date, end_date, RangeBy::ThreadStart).await
+}
+
+/// the first `limit` new subjects between `start_date` and `end_date`, oldest first. the walk
+/// stops at the since-page that completes them, and the candidates after them are not checked,
+/// so the cost depends on `limit` rather than on the size of the range
+async fn first_new_subjects_between(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    limit: usize,
+) -> Result<Vec<EmailThread>> {
+    let mut threads = Vec::new();
+    if limit == 0 {
+        return Ok(threads);
+    }
+    let mut walk = SinceWalk::new(fetcher, start_date, end_date);
+    while threads.len() < limit {
+        let Some(page) = walk.next().await? else {
+            break;
+        };
+        for thread in page {
+            if is_new_subject(fetcher, &thread, RangeBy::ThreadStart).await? {
+                threads.push(thread);
+            }
+            if threads.len() == limit {
+                break;
+            }
+        }
+    }
+    threads.sort_by_key(|thread| thread.datetime);
+    Ok(threads)
+}
+
+/// new subjects of each of `lists` between `start_date` and `end_date`, fetched
+/// `list_concurrency` lists at a time and merged in time order
+async fn new_subjects_multi(
+    fetcher: &Fetcher,
+    lists: &[MailingList],
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+) -> Result<Vec<EmailThread>> {
+    let concurrency = fetcher.config.list_concurrency.clamp(1, lists.len().max(1));
+    let per_list = map_concurrently(lists, concurrency, |list| async move {
+        let fetcher = fetcher.clone().with_list(*list);
+        get_new_subjects_between(&fetcher, start_date, end_date)
+            .await
+            .with_context(|| format!("failed to get new subjects of {}", list.name()))
+    })
+    .await;
+    let mut threads = Vec::new();
+    for list_threads in per_list {
+        threads.extend(list_threads?);
+    }
+    threads.sort_by_key(|thread| thread.datetime);
+    Ok(threads)
+}
+
+/// which datetime decides whether a subject falls into a date range
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum RangeBy {
+    /// when its thread was started. every candidate is checked to be its thread's own starter,
+    /// one page fetch each
+    ThreadStart,
+    /// when the listed message was posted, trusting the subject to tell starters from replies
+    Message,
+}
+
+pub async fn get_new_subjects_between_by(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    range_by: RangeBy,
+) -> Result<Vec<EmailThread>> {
+    let mut threads = Vec::new();
+    let mut pages = SincePages::new(fetcher, start_date, end_date);
+    while let Some(page) = pages.next().await? {
+        for thread in page {
+            if is_new_subject(fetcher, &thread, range_by).await? {
+                threads.push(thread);
+            }
+        }
+    }
+    Ok(threads)
+}
+
+/// write the new subjects between `start_date` and `end_date` to `sink` as each since-page is
+/// checked, rather than once the whole range is, and flush it at the end. returns how many were
+/// written
+pub async fn write_new_subjects_between(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+    range_by: RangeBy,
+    sink: &mut dyn ThreadSink,
+) -> Result<usize> {
+    let mut written = 0;
+    let mut walk = SinceWalk::new(fetcher, start_date, end_date);
+    while let Some(page) = walk.next().await? {
+        for thread in page {
+            if !is_new_subject(fetcher, &thread, range_by).await? {
+                continue;
+            }
+            sink.write(&thread).context("failed to write a thread")?;
+            written += 1;
+        }
+    }
+    sink.flush()?;
+    Ok(written)
+}
+
+/// whether the listed `thread` starts a discussion at its listed datetime
+pub async fn is_new_subject(
+    fetcher: &Fetcher,
+    thread: &EmailThread,
+    range_by: RangeBy,
+) -> Result<bool> {
+    if !fetcher.include_notices && is_list_notice(&thread.author, &thread.subject, "") {
+        return Ok(false);
+    }
+    match range_by {
+        RangeBy::Message => is_thread_starter(fetcher, thread).await,
+        // a reply under a fresh subject looks like a starter, but its thread may have started
+        // long before the range. only a thread's own starter was posted when the thread began
+        RangeBy::ThreadStart => Ok(subject_prefix(&thread.subject) != Some(SubjectPrefix::Re)
+            && is_thread_starter_by_id(fetcher, &thread.id).await?),
+    }
+}
+
+/// The subjects under discussion between `start_date` and `end_date`: every thread a message
+/// was posted to in the range, new or replied to, once with the details of its starter. the
+/// starters are fetched up to `page_concurrency` at a time, and listed in the order their threads
+/// were first seen in the range.
+pub async fn get_active_subjects_between(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+) -> Result<Vec<EmailThreadDetail>> {
+    let mut seen_keys = HashSet::new();
+    let mut starters = Vec::new();
+    let mut pages = SincePages::new(fetcher, start_date, end_date);
+    while let Some(page) = pages.next().await? {
+        for thread in page {
+            if !fetcher.include_notices && is_list_notice(&thread.author, &thread.subject, "") {
+                continue;
+            }
+            let Some(key) = unless_not_found(fetcher, thread_key(fetcher, &thread.id).await)?
+            else {
+                continue;
+            };
+            if seen_keys.contains(&key) {
+                continue;
+            }
+            let id = match MessageId::parse(&key) {
+                Result::Ok(id) => id,
+                Err(err) => {
+                    fetcher.warn(format!("skipping thread {}: {err:#}", thread.id));
+                    continue;
+                }
+            };
+            seen_keys.insert(key);
+            starters.push((thread, id));
+        }
+    }
+    let details = map_concurrently(&starters, fetcher.config.page_concurrency, |(_, id)| {
+        get_thread_by_id(fetcher, id)
+    })
+    .await;
+
+    let mut active = Vec::new();
+    for ((thread, _), detail) in starters.into_iter().zip(details) {
+        let Some(t) = unless_not_found(fetcher, detail)? else {
+            continue;
+        };
+        let sender = format!("{} {}", t.author_name, t.author_email);
+        if !fetcher.include_notices && is_list_notice(&sender, &t.subject, &t.content) {
+            continue;
+        }
+        if fetcher.verify_datetimes && t.id == thread.id {
+            if let Some(warning) = datetime_mismatch(&thread, &t) {
+                fetcher.warn(warning);
+            }
+        }
+        active.push(t);
+    }
+    Ok(active)
+}
+
+/// the attachments of every message posted between `start_date` and `end_date`, each with the
+/// message it came with, one page fetch per message. a file linked again by a later message, same
+/// name and url, is listed once
+pub async fn attachments_between(
+    fetcher: &Fetcher,
+    start_date: NaiveDateTime,
+    end_date: NaiveDateTime,
+) -> Result<Vec<(MessageId, ThreadAttachment)>> {
+    let ids = get_threads_between(fetcher, start_date, end_date, |thread| {
+        Ok(MessageId::parse(&thread.id)
+            .inspect_err(|err| fetcher.warn(format!("skipping message {}: {err:#}", thread.id)))
+            .ok())
+    })
+    .await?;
+    let options = ThreadDetailOptions {
+        fetch_content: false,
+        fetch_attachments: true,
+    };
+    let details = map_concurrently(&ids, fetcher.config.page_concurrency, |id| {
+        get_thread_detail(fetcher, id, options)
+    })
+    .await;
+
+    let mut seen = HashSet::new();
+    let mut attachments = Vec::new();
+    for (id, detail) in ids.into_iter().zip(details) {
+        let detail = match detail {
+            Result::Ok(detail) => detail,
+            // gone from the archive since it was listed
+            Err(err) if is_not_found(&err) => {
+                fetcher.warn(format!("{err:#}"));
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+        for attachment in detail.attachments {
+            if seen.insert((attachment.name.clone(), attachment.href.clone())) {
+                attachments.push((id.clone(), attachment));
+            }
+        }
+    }
+    Ok(attachments)
+}
+
+/// the listing only shows HH:MM while the detail page has seconds, so compare them to the minute.
+/// a mismatch hints at a parsing bug or a timezone difference between the two pages.
+fn datetime_mismatch(listed: &EmailThread, detail: &EmailThreadDetail) -> Option<String> {
+    let detail_minute = detail.datetime.with_second(0).unwrap();
+    if listed.datetime == detail_minute {
+        None
+    } else {
+        Some(format!(
+            "message {} is listed at {} but its page says {}",
+            listed.id,
+            listed.datetime.format("%Y-%m-%d %H:%M"),
+            detail.datetime.format("%Y-%m-%d %H:%M:%S"),
+        ))
+    }
+}
+
+/// the message `id` as the archive's raw view serves it, headers and all
+async fn get_raw_source(fetcher: &Fetcher, id: &str) -> Result<String> {
+    let url = fetcher.raw_message_url(id);
+    let page = fetcher.get_page(&url).await?;
+    if page.status == reqwest::StatusCode::NOT_FOUND {
+        return Err(ScrapeError::NotFound(id.to_string()).into());
+    }
+    if !page.status.is_success() {
+        bail!("{url} answered {}", page.status);
+    }
+    if let Some(content_type) = page.content_type.filter(|ct| ct.contains("html")) {
+        bail!("{url} returned {content_type} instead of a raw message");
+    }
+    Ok(page.body)
+}
+
+/// A message as its sender wrote it, from the archive's raw view or an mbox: the headers the
+/// message page leaves out of its table, and the body decoded from its MIME parts, transfer
+/// encodings and charsets.
+#[derive(Debug, Clone)]
+pub struct RawMessage {
+    /// without angle brackets, `None` for the few messages without one, like some notices
+    pub message_id: Option<String>,
+    /// the id of the message this one answers
+    pub in_reply_to: Option<String>,
+    /// the ids of the thread before this message, the starter first
+    pub references: Vec<String>,
+    pub subject: String,
+    pub author_name: String,
+    pub author_email: String,
+    /// in the timezone of the sender
+    pub date: Option<chrono::DateTime<chrono::FixedOffset>>,
+    /// the plain text body, or the text of the html one when there is none
+    pub text: String,
+    /// with no `href`, the raw message does not say where the archive keeps them
+    pub attachments: Vec<ThreadAttachment>,
+    /// every header, repeated ones joined by newlines
+    pub headers: BTreeMap<String, String>,
+}
+
+impl RawMessage {
+    /// the message `raw`, `None` when it has no headers
+    pub fn parse(raw: &[u8]) -> Option<RawMessage> {
+        use mail_parser::MimeHeaders;
+
+        let message = mail_parser::MessageParser::default().parse(raw)?;
+        let headers = raw_header_map(&String::from_utf8_lossy(raw).replace("\r\n", "\n"));
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        };
+        let (author_name, author_email) = message
+            .from()
+            .and_then(|from| from.first())
+            .map(|from| {
+                (
+                    from.name().unwrap_or_default().to_string(),
+                    from.address().unwrap_or_default().to_string(),
+                )
+            })
+            .unwrap_or_default();
+        Some(RawMessage {
+            message_id: message.message_id().map(str::to_string),
+            in_reply_to: header("In-Reply-To")
+                .and_then(|value| header_message_ids(value).into_iter().next()),
+            references: header("References")
+                .map(header_message_ids)
+                .unwrap_or_default(),
+            subject: message.subject().unwrap_or_default().to_string(),
+            author_name,
+            author_email,
+            date: message
+                .date()
+                .and_then(|date| chrono::DateTime::parse_from_rfc3339(&date.to_rfc3339()).ok()),
+            text: message.body_text(0).unwrap_or_default().into_owned(),
+            attachments: message
+                .attachments()
+                .map(|part| ThreadAttachment {
+                    name: part.attachment_name().unwrap_or("attachment").to_string(),
+                    href: String::new(),
+                    size: Some(part.len() as u64),
+                })
+                .collect(),
+            headers,
+        })
+    }
+}
+
+/// The message `id` as its raw view has it, with the headers and body its sender wrote. Fails
+/// with [`ScrapeError::NotFound`] when the archive has no such message.
+pub async fn get_raw_message(fetcher: &Fetcher, id: &MessageId) -> Result<RawMessage> {
+    let id = id.to_string();
+    let source = get_raw_source(fetcher, &id).await?;
+    RawMessage::parse(source.as_bytes())
+        .with_context(|| format!("the raw view of {id} is not a message"))
+}
+
+/// a plain text body as the html of a message's content, `None` when it is blank
+fn preformatted(body: &str) -> Option<String> {
+    let body = body.trim();
+    (!body.is_empty()).then(|| {
+        let escaped = body
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        format!("<pre>{escaped}</pre>")
+    })
+}
+
+/// which parts of a message [`get_thread_detail`] scrapes besides its metadata
+#[derive(Debug, Clone, Copy)]
+struct ThreadDetailOptions {
+    /// the body, and the raw view for its fallback and the full headers. without it `content`
+    /// and `headers` stay empty and only the message page is fetched
+    fetch_content: bool,
+    fetch_attachments: bool,
+}
+
+impl ThreadDetailOptions {
+    /// author, subject, datetime and the ids in the thread, for callers that need no more
+    const COMPACT: ThreadDetailOptions = ThreadDetailOptions {
+        fetch_content: false,
+        fetch_attachments: false,
+    };
+}
+
+impl Default for ThreadDetailOptions {
+    fn default() -> Self {
+        ThreadDetailOptions {
+            fetch_content: true,
+            fetch_attachments: true,
+        }
+    }
+}
+
+/// reading speed assumed by [`content_stats`], a common figure for prose read on a screen
+const WORDS_PER_MINUTE: usize = 200;
+
+/// the size of a message's own prose, for showing things like "~3 min read"
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Serialize)]
+pub struct ContentStats {
+    pub words: usize,
+    /// letters and punctuation, whitespace not counted
+    pub chars: usize,
+    pub est_read_secs: usize,
+}
+
+/// the text of the `content` html outside its blockquotes, a line per `<br>` or block
+fn unquoted_text(content: &str) -> String {
+    text_outside_quotes(content, false)
+}
+
+/// the text of the `content` html outside its blockquotes, a line per `<br>` or block. with
+/// `mark_quotes`, each outermost blockquote leaves a line of just `>` where it was
+fn text_outside_quotes(content: &str, mark_quotes: bool) -> String {
+    let fragment = Html::parse_fragment(content);
+    let is_blockquote = |node: &scraper::Node| {
+        node.as_element()
+            .is_some_and(|element| element.name() == "blockquote")
+    };
+    let mut text = String::new();
+    let mut after_break = false;
+    for node in fragment.root_element().descendants() {
+        let quoted = node
+            .ancestors()
+            .any(|ancestor| is_blockquote(ancestor.value()));
+        match node.value() {
+            scraper::Node::Element(element)
+                if mark_quotes && element.name() == "blockquote" && !quoted =>
+            {
+                text.push_str("\n>\n");
+                after_break = true;
+            }
+            scraper::Node::Element(element)
+                if matches!(element.name(), "br" | "p" | "div" | "pre" | "blockquote") =>
+            {
+                text.push('\n');
+                after_break = true;
+            }
+            // the newline often written after a `<br>` ends the same line
+            scraper::Node::Text(part) if !quoted => {
+                let part = match after_break {
+                    true => part.strip_prefix('\n').unwrap_or(part),
+                    false => part,
+                };
+                text.push_str(part);
+                after_break = false;
+            }
+            _ => {}
+        }
+    }
+    text
+}
+
+/// words of the line introducing a quoted message, like "On Mon, Alice wrote:" or "Am Mo.
+/// schrieb Bob:", in the languages seen on the lists
+const ATTRIBUTION_WORDS: [&str; 4] = ["wrote", "writes", "schrieb", "écrit"];
+
+/// how such a line starts in those languages, when it starts with the date
+const ATTRIBUTION_STARTS: [&str; 3] = ["on", "am", "le"];
+
+/// whether `line` introduces a quoted message: it ends with a colon and has one of
+/// [`ATTRIBUTION_WORDS`], and a quote follows it or it starts like one
+fn is_attribution(line: &str, quote_follows: bool) -> bool {
+    let line = line.trim().to_lowercase();
+    let mut words = line
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty());
+    let starts_like_one = words
+        .clone()
+        .next()
+        .is_some_and(|word| ATTRIBUTION_STARTS.contains(&word));
+    line.ends_with(':')
+        && words.any(|word| ATTRIBUTION_WORDS.contains(&word))
+        && (quote_follows || starts_like_one)
+}
+
+/// what the author of the `content` html wrote, as text. quoted messages, in blockquotes or on
+/// lines starting with `>`, are left out with the lines introducing them, and the blank lines
+/// they leave become one
+fn content_new_text(content: &str) -> String {
+    let text = text_outside_quotes(content, true);
+    let all: Vec<&str> = text.lines().map(str::trim_end).collect();
+    let is_quoted = |line: &str| line.trim_start().starts_with('>');
+    let mut lines: Vec<&str> = Vec::new();
+    for (i, line) in all.iter().enumerate() {
+        let quote_follows = all[i + 1..]
+            .iter()
+            .find(|next| !next.trim().is_empty())
+            .is_some_and(|next| is_quoted(next));
+        if is_quoted(line) || is_attribution(line, quote_follows) {
+            continue;
+        }
+        if line.trim().is_empty() && lines.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        lines.push(line);
+    }
+    while lines.last().is_some_and(|last| last.is_empty()) {
+        lines.pop();
+    }
+    lines.join("\n")
+}
+
+/// the unified diffs pasted into the text of the `content` html, like a change a reviewer
+/// suggests, each run of consecutive file diffs as one string. a file diff is found by its
+/// `--- `, `+++ ` and `@@` lines, maybe after a `diff` line and its index lines, and its hunks
+/// are read for as many lines as their headers say, so blank context lines do not cut them
+/// short. quoted diffs are left out, they belong to the message they quote
+fn extract_inline_patches(content: &str) -> Vec<String> {
+    let text = unquoted_text(content);
+    let lines: Vec<&str> = text.lines().collect();
+    let mut patches = Vec::new();
+    let mut start = 0;
+    while start < lines.len() {
+        let Some(mut end) = diff_file_end(&lines, start) else {
+            start += 1;
+            continue;
+        };
+        while let Some(next_end) = diff_file_end(&lines, end) {
+            end = next_end;
+        }
+        patches.push(lines[start..end].join("\n"));
+        start = end;
+    }
+    patches
+}
+
+/// the line after the diff of a file starting at `lines[start]`, if one starts there
+fn diff_file_end(lines: &[&str], start: usize) -> Option<usize> {
+    let mut i = start;
+    if lines.get(i)?.starts_with("diff ") {
+        i += 1;
+        // index, mode and rename lines
+        while lines.get(i).is_some_and(|line| {
+            !line.starts_with("--- ") && !line.starts_with("diff ") && !line.trim().is_empty()
+        }) {
+            i += 1;
+        }
+    }
+    let is_header = lines.get(i)?.starts_with("--- ")
+        && lines.get(i + 1)?.starts_with("+++ ")
+        && hunk_sizes(lines.get(i + 2)?).is_some();
+    if !is_header {
+        return None;
+    }
+    i += 2;
+    while let Some((mut old, mut new)) = lines.get(i).and_then(|line| hunk_sizes(line)) {
+        i += 1;
+        while old > 0 || new > 0 {
+            match lines.get(i).map(|line| line.chars().next()) {
+                Some(Some('+')) => new = new.saturating_sub(1),
+                Some(Some('-')) => old = old.saturating_sub(1),
+                // context, its leading space maybe trimmed away or turned into a nbsp
+                Some(None | Some(' ' | '\u{a0}')) => {
+                    old = old.saturating_sub(1);
+                    new = new.saturating_sub(1);
+                }
+                Some(Some('\\')) => {}
+                _ => break,
+            }
+            i += 1;
+        }
+        // "\ No newline at end of file" after the last line of a hunk
+        while lines.get(i).is_some_and(|line| line.starts_with('\\')) {
+            i += 1;
+        }
+    }
+    Some(i)
+}
+
+/// commits and bug reports a message mentions, each once in the order first mentioned
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct References {
+    /// lowercase hashes, full or abbreviated as written
+    pub commits: Vec<String>,
+    /// bug numbers, like `18234` for BUG #18234
+    pub bugs: Vec<String>,
+}
+
+/// the commits and bug reports mentioned in the text of the `content` html. to keep hex words and
+/// numbers out, a commit is a full 40-digit hash anywhere, or an abbreviated one of at least 7
+/// digits right after the word "commit", and a bug is a number of 4 to 6 digits after `bug #`
+fn extract_references(content: &str) -> References {
+    static COMMIT: OnceLock<regex::Regex> = OnceLock::new();
+    static BUG: OnceLock<regex::Regex> = OnceLock::new();
+    let commit = COMMIT.get_or_init(|| {
+        regex::Regex::new(r"(?:\b(?i:commits?)\s+([0-9a-f]{7,40})\b)|\b([0-9a-f]{40})\b").unwrap()
+    });
+    let bug = BUG.get_or_init(|| regex::Regex::new(r"\b(?i:bug)\s?#\s?([0-9]{4,6})\b").unwrap());
+
+    let text: String = Html::parse_fragment(content)
+        .root_element()
+        .text()
+        .collect();
+    let mut references = References::default();
+    for captures in commit.captures_iter(&text) {
+        let hash = captures.get(1).or(captures.get(2)).unwrap().as_str();
+        if !references.commits.iter().any(|known| known == hash) {
+            references.commits.push(hash.to_string());
+        }
+    }
+    for captures in bug.captures_iter(&text) {
+        let number = &captures[1];
+        if !references.bugs.iter().any(|known| known == number) {
+            references.bugs.push(number.to_string());
+        }
+    }
+    references
+}
+
+/// the old and new line counts of a hunk header like `@@ -12,7 +12,9 @@ fn name`
+fn hunk_sizes(line: &str) -> Option<(usize, usize)> {
+    let mut ranges = line.strip_prefix("@@ ")?.split_whitespace();
+    let size = |range: Option<&str>, sign: char| -> Option<usize> {
+        match range?.strip_prefix(sign)?.split_once(',') {
+            Some((_, size)) => size.parse().ok(),
+            None => Some(1),
+        }
+    };
+    let old = size(ranges.next(), '-')?;
+    let new = size(ranges.next(), '+')?;
+    (ranges.next() == Some("@@")).then_some((old, new))
+}
+
+/// word and character counts of the `content` html, and how long reading it takes at
+/// [`WORDS_PER_MINUTE`]. quoted text is not counted, neither is code: lines indented by a tab or
+/// four spaces, fenced ``` blocks and diffs
+fn content_stats(content: &str) -> ContentStats {
+    let text = unquoted_text(content);
+    let mut stats = ContentStats::default();
+    let mut in_fence = false;
+    let mut in_diff = false;
+    for line in text.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if trimmed.starts_with("diff --git") {
+            in_diff = true;
+        } else if trimmed.is_empty() {
+            in_diff = false;
+        }
+        let is_code = in_fence
+            || in_diff
+            || line.starts_with('\t')
+            || line.starts_with("    ")
+            || trimmed.starts_with('>');
+        if is_code {
+            continue;
+        }
+        for word in trimmed.split_whitespace() {
+            stats.words += 1;
+            stats.chars += word.chars().count();
+        }
+    }
+    stats.est_read_secs = (stats.words * 60).div_ceil(WORDS_PER_MINUTE);
+    stats
+}
+
+/// The message `id` with its body and attachments. Fails with [`ScrapeError::NotFound`] when
+/// the archive has no such message.
+pub async fn get_thread_by_id(fetcher: &Fetcher, id: &MessageId) -> Result<EmailThreadDetail> {
+    get_thread_detail(fetcher, id, ThreadDetailOptions::default()).await
+}
+
+async fn get_thread_detail(
+    fetcher: &Fetcher,
+    id: &MessageId,
+    options: ThreadDetailOptions,
+) -> Result<EmailThreadDetail> {
+    let id = &id.to_string();
+    fetch_thread_detail(fetcher, id, options)
+        .instrument(tracing::debug_span!("thread_detail", id))
+        .await
+}
+
+async fn fetch_thread_detail(
+    fetcher: &Fetcher,
+    id: &str,
+    options: ThreadDetailOptions,
+) -> Result<EmailThreadDetail> {
+    let message_url = fetcher.message_url(id);
+    // the parsed page cannot be held while the raw view is fetched, so take what is needed first
+    let fields = {
+        let doc = get_message_document(fetcher, id)
+            .await
+            .context("failed to get the email")?;
+        message_page_fields(fetcher, id, &doc, options)
+    };
+    // the page shows only a few headers, the raw view has them all
+    let raw = match options.fetch_content {
+        true => get_raw_source(fetcher, id)
+            .await
+            .inspect_err(|err| fetcher.warn(format!("failed to get the raw message {id}: {err:#}")))
+            .ok(),
+        false => None,
+    };
+    let headers = raw.as_deref().map(raw_header_map).unwrap_or_default();
+
+    let MessagePageFields {
+        header:
+            HeaderFields {
+                author_name,
+                author_email,
+                subject,
+                datetime,
+            },
+        replies,
+        content,
+        attachments,
+    } = fields?;
+    let content = if options.fetch_content {
+        content.or_else(|| {
+            fetcher.warn(format!(
+                "no tag '{MESSAGE_CONTENT}' found in {message_url}, using the raw view"
+            ));
+            raw.as_deref()
+                .and_then(|raw| RawMessage::parse(raw.as_bytes()))
+                .and_then(|message| preformatted(&message.text))
+        })
+    } else {
+        Some(String::new())
+    };
+    let content_missing = content.is_none();
+    let content = content.unwrap_or_default();
+    let content_new_text = content_new_text(&content);
+    let stats = content_stats(&content);
+    let inline_patches = extract_inline_patches(&content);
+    let references = extract_references(&content);
+
+    let detail = EmailThreadDetail {
+        id: id.to_string(),
+        subject,
+        datetime,
+        author_name,
+        author_email,
+        content,
+        content_missing,
+        content_new_text,
+        stats,
+        inline_patches,
+        references,
+        attachments,
+        replies,
+        headers,
+    };
+    tracing::debug!(
+        subject = detail.subject,
+        author = detail.author_name,
+        replies = detail.replies.len(),
+        attachments = detail.attachments.len(),
+        content_missing = detail.content_missing,
+        "message parsed"
+    );
+    if let Some(store) = &fetcher.store {
+        store.upsert_message(fetcher.list, &detail)?;
+    }
+    fetcher.progress.detail_fetched(&detail);
+    Ok(detail)
+}
+
+const MESSAGE_CONTENT: &str = "#pgContentWrap div.message-content";
+
+/// what a message page shows, taken out of its html
+struct MessagePageFields {
+    header: HeaderFields,
+    /// the thread dropdown
+    replies: Vec<String>,
+    /// the html of the body, `None` when the page has no content div
+    content: Option<String>,
+    attachments: Vec<ThreadAttachment>,
+}
+
+fn message_page_fields(
+    fetcher: &Fetcher,
+    id: &str,
+    doc: &Html,
+    options: ThreadDetailOptions,
+) -> Result<MessagePageFields> {
+    let table_tag_name = "#pgContentWrap table";
+    let table_tag = Selector::parse(table_tag_name).unwrap();
+    let select_tag = Selector::parse(THREAD_SELECT).unwrap();
+    let option_tag = Selector::parse("option").unwrap();
+    let content_tag = Selector::parse(MESSAGE_CONTENT).unwrap();
+    let attchm_tag = Selector::parse("#pgContentWrap table.message-attachments").unwrap();
+
+    let table = doc
+        .select(&table_tag)
+        .next()
+        .ok_or_else(|| ScrapeError::unexpected_layout(id, 0, &doc.root_element().html()))
+        .context(format!("no tag '{table_tag_name}' found in the page"))?;
+
+    let replies: Vec<_> = doc
+        .select(&select_tag)
+        .next()
+        .ok_or_else(|| ScrapeError::missing_element(id, THREAD_SELECT))?
+        .select(&option_tag)
+        .map(|opt_elem| opt_elem.value().attr("value").unwrap_or("").to_string())
+        .collect();
+    learn_starters(fetcher, id, &replies);
+
+    let content = options
+        .fetch_content
+        .then(|| doc.select(&content_tag).next())
+        .flatten()
+        .map(|content_elem| content_elem.inner_html());
+    let attachments = options
+        .fetch_attachments
+        .then(|| doc.select(&attchm_tag).next())
+        .flatten()
+        .map(listed_attachments)
+        .unwrap_or_default();
+
+    Ok(MessagePageFields {
+        header: header_fields(id, table)?,
+        replies,
+        content,
+        attachments,
+    })
+}
+
+/// the author, subject and time a message page shows in its header table
+struct HeaderFields {
+    author_name: String,
+    author_email: String,
+    subject: String,
+    datetime: NaiveDateTime,
+}
+
+/// the row labelled `label`, like `From:`, of the header `table` of a message page, its value
+/// cell. rows come and go, like Cc, so each field is found by its label
+fn header_row<'a>(table: scraper::ElementRef<'a>, label: &str) -> Option<scraper::ElementRef<'a>> {
+    let tr_tag = Selector::parse("tr").unwrap();
+    let th_tag = Selector::parse("th").unwrap();
+    let td_tag = Selector::parse("td").unwrap();
+    table
+        .select(&tr_tag)
+        .find(|tr| {
+            tr.select(&th_tag).next().is_some_and(|th| {
+                element_text(&th)
+                    .trim_end_matches(':')
+                    .eq_ignore_ascii_case(label)
+            })
+        })
+        .and_then(|tr| tr.select(&td_tag).next())
+}
+
+/// the fields of the header `table` of message `id`
+fn header_fields(id: &str, table: scraper::ElementRef) -> Result<HeaderFields> {
+    let (Some(from_td), Some(subject_td), Some(datetime_td)) = (
+        header_row(table, "From"),
+        header_row(table, "Subject"),
+        header_row(table, "Date"),
+    ) else {
+        let rows = table.select(&Selector::parse("tr").unwrap()).count();
+        return Err(ScrapeError::unexpected_layout(id, rows, &table.html()).into());
+    };
+    let (author_name, author_email) = from_cell_author(&from_td);
+
+    let subject = clean_subject_title(&element_text(&subject_td));
+
+    let datetime_str = datetime_td.text().collect::<String>().trim().to_string();
+    let datetime =
+        NaiveDateTime::parse_from_str(&datetime_str, "%Y-%m-%d %H:%M:%S").map_err(|_| {
+            ScrapeError::InvalidDate {
+                id: id.to_string(),
+                text: datetime_str.clone(),
+            }
+        })?;
+    Ok(HeaderFields {
+        author_name,
+        author_email,
+        subject,
+        datetime,
+    })
+}
+
+/// the attachments listed in a `table.message-attachments`, a row per attachment with the link
+/// in its th and the size in its last td
+fn listed_attachments(table: scraper::ElementRef) -> Vec<ThreadAttachment> {
+    let tr_tag = Selector::parse("tr").unwrap();
+    let th_tag = Selector::parse("th").unwrap();
+    let td_tag = Selector::parse("td").unwrap();
+    let a_tag = Selector::parse("a").unwrap();
+    table
+        .select(&tr_tag)
+        .filter_map(|att| {
+            let link = att
+                .select(&th_tag)
+                .find_map(|th| th.select(&a_tag).next())?;
+            Some(ThreadAttachment {
+                name: element_text(&link),
+                href: link.value().attr("href").unwrap_or("").to_string(),
+                size: att
+                    .select(&td_tag)
+                    .last()
+                    .and_then(|td| parse_byte_size(&element_text(&td))),
+            })
+        })
+        .collect()
+}
+
+/// The thread of `starter_id` from the archive's flat view, which shows every message of it on
+/// one page, in thread order. One request instead of one per message, but like the message
+/// pages the view has no raw headers, so `headers` stays empty. Fails with
+/// [`ScrapeError::NotFound`] when the archive has no such message.
+pub async fn get_thread_flat(
+    fetcher: &Fetcher,
+    starter_id: &MessageId,
+) -> Result<Vec<EmailThreadDetail>> {
+    let starter_id = &starter_id.to_string();
+    let span = tracing::debug_span!("thread_flat", id = starter_id);
+    let doc = fetcher
+        .get_document(&fetcher.flat_thread_url(starter_id))
+        .instrument(span.clone())
+        .await?;
+    let _span = span.entered();
+    let part_tag = Selector::parse(
+        "#pgContentWrap table.message-header, #pgContentWrap table.message-attachments, \
+         #pgContentWrap div.message-content",
+    )
+    .unwrap();
+
+    // a message starts with its header table, its attachments and content follow it
+    let mut parts: Vec<(scraper::ElementRef, Option<String>, Vec<ThreadAttachment>)> = Vec::new();
+    for part in doc.select(&part_tag) {
+        let classes = part.value();
+        if classes.has_class("message-header", scraper::CaseSensitivity::CaseSensitive) {
+            parts.push((part, None, Vec::new()));
+        } else if let Some((_, content, attachments)) = parts.last_mut() {
+            if classes.name() == "div" {
+                *content = Some(part.inner_html());
+            } else {
+                *attachments = listed_attachments(part);
+            }
+        }
+    }
+    if parts.is_empty() {
+        let heading = Selector::parse("#pgContentWrap h1").unwrap();
+        if doc
+            .select(&heading)
+            .any(|h1| element_text(&h1).to_ascii_lowercase().contains("not found"))
+        {
+            return Err(ScrapeError::NotFound(starter_id.to_string()).into());
+        }
+        return Err(ScrapeError::missing_element(starter_id, "table.message-header").into());
+    }
+
+    let ids = parts
+        .iter()
+        .map(|(table, ..)| {
+            let id = header_row(*table, "Message-ID")
+                .map(|td| element_text(&td))
+                .ok_or_else(|| ScrapeError::unexpected_layout(starter_id, 0, &table.html()))?;
+            Ok(MessageId::parse(&id)?.to_string())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    learn_starters(fetcher, starter_id, &ids);
+
+    let mut details = Vec::new();
+    for ((table, content, attachments), id) in parts.into_iter().zip(&ids) {
+        let HeaderFields {
+            author_name,
+            author_email,
+            subject,
+            datetime,
+        } = header_fields(id, table)?;
+        let content_missing = content.is_none();
+        let content = content.unwrap_or_default();
+        let detail = EmailThreadDetail {
+            id: id.clone(),
+            subject,
+            datetime,
+            author_name,
+            author_email,
+            stats: content_stats(&content),
+            inline_patches: extract_inline_patches(&content),
+            references: extract_references(&content),
+            content_new_text: content_new_text(&content),
+            content,
+            content_missing,
+            attachments,
+            replies: ids.clone(),
+            headers: BTreeMap::new(),
+        };
+        if let Some(store) = &fetcher.store {
+            // a copy kept with its raw headers is better than this one
+            let kept = store.message(id)?;
+            if kept.is_none_or(|kept| kept.headers.is_empty()) {
+                store.upsert_message(fetcher.list, &detail)?;
+            }
+        }
+        fetcher.progress.detail_fetched(&detail);
+        details.push(detail);
+    }
+    tracing::debug!(messages = details.len(), "thread parsed");
+    Ok(details)
+}
+
+/// the page of message `id`, or [`ScrapeError::NotFound`] when the archive has no such message.
+/// the archive answers unknown ids with a page of its own, not necessarily with a 404
+async fn get_message_document(fetcher: &Fetcher, id: &str) -> Result<Html> {
+    let doc = fetcher.get_document(&fetcher.message_url(id)).await?;
+    let thread_select = Selector::parse(THREAD_SELECT).unwrap();
+    let heading = Selector::parse("#pgContentWrap h1").unwrap();
+    let not_found = doc.select(&thread_select).next().is_none()
+        && doc
+            .select(&heading)
+            .any(|h1| element_text(&h1).to_ascii_lowercase().contains("not found"));
+    if not_found {
+        return Err(ScrapeError::NotFound(id.to_string()).into());
+    }
+    Ok(doc)
+}
+
+/// whether `err` is, or was caused by, a [`ScrapeError::NotFound`]
+fn is_not_found(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::NotFound(_))
+    )
+}
+
+/// senders of notices, matched against the lowercased sender name or address
+const NOTICE_SENDERS: [&str; 6] = [
+    "mailer-daemon",
+    "postmaster@",
+    "pglister",
+    "majordomo",
+    "-owner@",
+    "mailing list bot",
+];
+
+/// subject and body phrases of moderation and bounce notices, lowercase
+const NOTICE_PHRASES: [&str; 9] = [
+    "held for moderation",
+    "held until the list moderator",
+    "awaits moderator approval",
+    "awaiting moderator approval",
+    "undelivered mail returned to sender",
+    "delivery status notification",
+    "mail delivery failed",
+    "could not be delivered to one or more recipients",
+    "returned mail: ",
+];
+
+/// whether a message from `sender`, a name and/or address, is an administrative notice rather
+/// than a post: a message held for moderation or a bounce, sent by a list or mail robot or
+/// saying so in its `subject` or `body`, which may be empty or html
+fn is_list_notice(sender: &str, subject: &str, body: &str) -> bool {
+    let sender = sender.to_lowercase();
+    if NOTICE_SENDERS.iter().any(|marker| sender.contains(marker)) {
+        return true;
+    }
+    let subject = subject.to_lowercase();
+    let body = body.to_lowercase();
+    NOTICE_PHRASES
+        .iter()
+        .any(|phrase| subject.contains(phrase) || body.contains(phrase))
+}
+
+/// the reply or forward marker a subject starts with
+#[derive(Debug, PartialEq, Eq)]
+enum SubjectPrefix {
+    Re,
+    Fwd,
+}
+
+fn subject_prefix(subject: &str) -> Option<SubjectPrefix> {
+    // some clients write a full-width colon
+    let (word, _) = subject.trim_start().split_once([':', '：'])?;
+    match word.to_ascii_lowercase().as_str() {
+        "re" => Some(SubjectPrefix::Re),
+        "fwd" | "fw" => Some(SubjectPrefix::Fwd),
+        _ => None,
+    }
+}
+
+/// coarse triage category of a thread, see [`classify_thread`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThreadCategory {
+    Patch,
+    Bug,
+    Question,
+    Discussion,
+}
+
+/// guess the category of `thread` from its subject, minus any Re:/Fwd: prefixes, and the
+/// attachments of its `detail` when known. the first matching rule wins:
+/// 1. Patch: a bracketed tag mentioning PATCH, like `[PATCH v2]` or `[RFC PATCH]`, a
+///    `.patch`/`.diff` attachment or a diff pasted into the body
+/// 2. Bug: a subject starting with `BUG #`, as sent by the bug report form
+/// 3. Question: a subject ending in `?`
+/// 4. Discussion: anything else
+fn classify_thread(thread: &EmailThread, detail: Option<&EmailThreadDetail>) -> ThreadCategory {
+    let mut subject = thread.subject.trim();
+    while subject_prefix(subject).is_some() {
+        subject = subject.split_once([':', '：']).unwrap().1.trim_start();
+    }
+
+    let patch_tag = subject.split('[').skip(1).any(|tag| {
+        tag.split(']')
+            .next()
+            .unwrap_or("")
+            .split(|c: char| !c.is_alphanumeric())
+            .any(|word| word.eq_ignore_ascii_case("patch"))
+    });
+    let patch_attachment = detail.is_some_and(|detail| {
+        !detail.inline_patches.is_empty()
+            || detail
+                .attachments
+                .iter()
+                .any(|attachment| attachment.has_extension(&PATCH_EXTENSIONS))
+    });
+
+    if patch_tag || patch_attachment {
+        ThreadCategory::Patch
+    } else if subject.starts_with("BUG #") {
+        ThreadCategory::Bug
+    } else if subject.ends_with('?') {
+        ThreadCategory::Question
+    } else {
+        ThreadCategory::Discussion
+    }
+}
+
+async fn is_thread_starter(fetcher: &Fetcher, thread: &EmailThread) -> Result<bool> {
+    if fetcher.thread_by_headers {
+        return is_thread_starter_by_headers(fetcher, &thread.id).await;
+    }
+    // only the outermost prefix counts: "Fwd: Re: ..." forwards a reply into a new discussion,
+    // "Re: Fwd: ..." answers a forwarded one
+    match subject_prefix(&thread.subject) {
+        Some(SubjectPrefix::Re) => return Ok(false),
+        Some(SubjectPrefix::Fwd) => return Ok(true),
+        None => {}
+    }
+
+    if !thread.subject.to_lowercase().contains("re:") {
+        return Ok(true);
+    }
+
+    is_thread_starter_by_id(fetcher, &thread.id).await
+}
+
+/// ids of all messages in the thread of `id`, in the order of the thread dropdown
+async fn thread_message_ids(fetcher: &Fetcher, id: &str) -> Result<Vec<String>> {
+    let message_url = fetcher.message_url(id);
+    let select_tag = Selector::parse(THREAD_SELECT).unwrap();
+    let option_tag = Selector::parse("option").unwrap();
+
+    fetcher
+        .get_document(&message_url)
+        .await
+        .context("failed to get document")?
+        .select(&select_tag)
+        .next()
+        .ok_or_else(|| ScrapeError::missing_element(id, THREAD_SELECT).into())
+        .map(|select| {
+            let ids = select
+                .select(&option_tag)
+                .map(|opt_elem| opt_elem.value().attr("value").unwrap_or("").to_string())
+                .collect::<Vec<_>>();
+            learn_starters(fetcher, id, &ids);
+            ids
+        })
+}
+
+/// where `id` is among the `ids` of its thread as given by [`thread_message_ids`]: its position
+/// from 1 for the starter, and the number of messages in the thread. for "message 4 of 12"
+fn message_position(ids: &[String], id: &str) -> Result<(usize, usize)> {
+    let key = canonical_message_id(id);
+    let index = ids
+        .iter()
+        .position(|other| canonical_message_id(other) == key)
+        .with_context(|| {
+            format!(
+                "{id} is not in its own thread, which has {} messages",
+                ids.len()
+            )
+        })?;
+    Ok((index + 1, ids.len()))
+}
+
+/// the starter of the thread of `id` if a thread dropdown of this run or the starter cache told
+fn known_starter_id(fetcher: &Fetcher, id: &str) -> Option<String> {
+    // a message never moves to another thread, so what the caches know is still true
+    let key = canonical_message_id(id);
+    if let Some(starter_id) = fetcher.run_starters.lock().unwrap().get(&key) {
+        return Some(starter_id.clone());
+    }
+    fetcher
+        .starter_cache
+        .as_ref()
+        .and_then(|cache| cache.get(&key))
+}
+
+async fn get_thread_starter_id(fetcher: &Fetcher, id: &str) -> Result<String> {
+    if let Some(starter_id) = known_starter_id(fetcher, id) {
+        return Ok(starter_id);
+    }
+
+    let select_tag = Selector::parse(THREAD_SELECT).unwrap();
+    let option_tag = Selector::parse("option").unwrap();
+
+    let doc = get_message_document(fetcher, id)
+        .await
+        .context("failed to get document")?;
+    let thread_ids: Vec<_> = doc
+        .select(&select_tag)
+        .next()
+        .ok_or_else(|| ScrapeError::missing_element(id, THREAD_SELECT))?
+        .select(&option_tag)
+        .filter_map(|option| option.value().attr("value"))
+        .collect();
+    let starter_id = *thread_ids
+        .first()
+        .context("no 'option' tag with a 'value' in 'select' tag")?;
+    learn_starters(fetcher, id, &thread_ids);
+    Ok(starter_id.to_string())
+}
+
+/// remember the first of `thread_ids`, the thread dropdown of the page of `id`, as the starter
+/// of all of them and of `id`. the dropdown lists the whole thread, so the other replies of a
+/// busy thread need no fetch, whichever lookup fetched the page
+fn learn_starters(fetcher: &Fetcher, id: &str, thread_ids: &[impl AsRef<str>]) {
+    let Some(starter_id) = thread_ids.first().map(AsRef::as_ref) else {
+        return;
+    };
+    let mut run_starters = fetcher.run_starters.lock().unwrap();
+    for thread_id in thread_ids.iter().map(AsRef::as_ref).chain([id]) {
+        if thread_id.is_empty() {
+            continue;
+        }
+        let thread_key = canonical_message_id(thread_id);
+        if run_starters.contains_key(&thread_key) {
+            continue;
+        }
+        if let Some(cache) = &fetcher.starter_cache {
+            if let Err(err) = cache.insert(&thread_key, starter_id) {
+                fetcher.warn(format!(
+                    "failed to cache the starter of {thread_id}: {err:#}"
+                ));
+            }
+        }
+        run_starters.insert(thread_key, starter_id.to_string());
+    }
+}
+
+/// the outcome of looking up a listed message, or `None` with a warning when the message is
+/// gone from the archive by now. other errors are kept, to abort the traversal
+fn unless_not_found<T>(fetcher: &Fetcher, result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Result::Ok(value) => Ok(Some(value)),
+        Err(err) if is_not_found(&err) => {
+            fetcher.warn(format!("{err:#}"));
+            Ok(None)
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/// the message-id header value behind a url slug, e.g. `abc@example.org` for `abc%40example.org`
+fn canonical_message_id(slug: &str) -> String {
+    percent_encoding::percent_decode_str(slug)
+        .decode_utf8_lossy()
+        .trim()
+        .trim_start_matches('<')
+        .trim_end_matches('>')
+        .to_string()
+}
+
+/// characters the archive escapes in the message-id path segment of its links: everything but
+/// the unreserved ones, e.g. `abc%3D1%40example.org` for `abc=1@example.org`
+const MESSAGE_ID_ESCAPES: &percent_encoding::AsciiSet = &percent_encoding::NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// a valid message-id, like `abc@example.org`. it displays as the url slug the archive uses
+/// for it, so it can go straight into a url.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct MessageId(String);
+
+impl MessageId {
+    /// a message-id from any of the forms [`parse_message_ref`] understands
+    pub fn parse(reference: &str) -> Result<Self> {
+        parse_message_ref(reference).map(MessageId)
+    }
+}
+
+/// the postgresql.org address of message `id`, given as a slug or in any form
+/// [`MessageId::parse`] takes, encoded the way the archive links it. an id that does not parse is
+/// linked as it is
+fn permalink(id: &str) -> String {
+    let id = MessageId::parse(id).map_or_else(|_| id.to_string(), |id| id.to_string());
+    format!("{PG_SITE}{MESSAGE_PATH}/{id}")
+}
+
+impl std::fmt::Display for MessageId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        percent_encoding::utf8_percent_encode(&self.0, MESSAGE_ID_ESCAPES).fmt(f)
+    }
+}
+
+impl TryFrom<&str> for MessageId {
+    type Error = anyhow::Error;
+
+    fn try_from(reference: &str) -> Result<Self> {
+        MessageId::parse(reference)
+    }
+}
+
+impl TryFrom<String> for MessageId {
+    type Error = anyhow::Error;
+
+    fn try_from(reference: String) -> Result<Self> {
+        MessageId::parse(&reference)
+    }
+}
+
+impl From<MessageId> for String {
+    fn from(id: MessageId) -> Self {
+        id.0
+    }
+}
+
+/// extract the message-id from the ways messages get cited, like a commit's
+/// `Discussion: https://postgr.es/m/<id>` line, an archive url, `<id@host>` or a bare id.
+/// the returned id is canonical, see [`canonical_message_id`].
+fn parse_message_ref(reference: &str) -> Result<String> {
+    let reference = reference.trim();
+    let reference = reference
+        .strip_prefix("Discussion:")
+        .unwrap_or(reference)
+        .trim();
+    let id = match reference.split_once("://") {
+        Some((_, rest)) => {
+            let path = rest.split_once('/').map_or("", |(_, path)| path);
+            let path = path.split(['?', '#']).next().unwrap_or("");
+            let id = ["m/", "message-id/flat/", "message-id/raw/", "message-id/"]
+                .iter()
+                .find_map(|prefix| path.strip_prefix(prefix))
+                .with_context(|| format!("'{reference}' is not a message url"))?;
+            id.trim_end_matches('/')
+        }
+        None => reference,
+    };
+    let id = canonical_message_id(id);
+    if !id.contains('@') || id.contains(char::is_whitespace) {
+        bail!("'{reference}' does not look like a message-id");
+    }
+    Ok(id)
+}
+
+/// the thread a commit's `Discussion:` link (or any other message reference) points to.
+/// the link may cite any message of the thread, the thread's starter is returned.
+async fn thread_for_discussion_link(
+    fetcher: &Fetcher,
+    url_or_id: &str,
+) -> Result<EmailThreadDetail> {
+    let id = MessageId::parse(url_or_id)?;
+    let detail = get_thread_by_id(fetcher, &id).await?;
+    match detail.replies.first() {
+        Some(starter_id) => {
+            let starter_id = MessageId::parse(starter_id)?;
+            if starter_id == id {
+                Ok(detail)
+            } else {
+                get_thread_by_id(fetcher, &starter_id).await
+            }
+        }
+        None => Ok(detail),
+    }
+}
+
+/// a key identifying the thread of `id` across runs: the canonical message-id of its starter.
+/// slugs of the same starter may be encoded differently, its message-id is not
+async fn thread_key(fetcher: &Fetcher, id: &str) -> Result<String> {
+    Ok(canonical_message_id(
+        &get_thread_starter_id(fetcher, id).await?,
+    ))
+}
+
+async fn is_thread_starter_by_id(fetcher: &Fetcher, id: &str) -> Result<bool> {
+    Ok(
+        unless_not_found(fetcher, get_thread_starter_id(fetcher, id).await)?
+            .is_some_and(|starter| canonical_message_id(&starter) == canonical_message_id(id)),
+    )
+}
+
+/// whether message `id` starts its thread according to its raw headers. one answering no
+/// message does. one answering a message whose thread is known already joins that thread.
+/// otherwise what it answers may be older than the run or missing from the archive, which
+/// only its thread dropdown tells
+async fn is_thread_starter_by_headers(fetcher: &Fetcher, id: &str) -> Result<bool> {
+    if let Some(starter_id) = known_starter_id(fetcher, id) {
+        return Ok(canonical_message_id(&starter_id) == canonical_message_id(id));
+    }
+    let Some(source) = unless_not_found(fetcher, get_raw_source(fetcher, id).await)? else {
+        return Ok(false);
+    };
+    let message = RawMessage::parse(source.as_bytes())
+        .with_context(|| format!("the raw view of {id} is not a message"))?;
+    // the nearest parent first, as a message may answer one the archive does not have
+    let mut parents = message
+        .in_reply_to
+        .iter()
+        .chain(message.references.iter().rev());
+    let Some(nearest) = parents.next() else {
+        learn_starters(fetcher, id, &[id]);
+        return Ok(true);
+    };
+    let known_starter = std::iter::once(nearest)
+        .chain(parents)
+        .find_map(|parent| known_starter_id(fetcher, parent));
+    if let Some(starter_id) = known_starter {
+        learn_starters(fetcher, id, &[starter_id]);
+        return Ok(false);
+    }
+    is_thread_starter_by_id(fetcher, id).await
+}
+
+/// headers of a raw message, with folded lines unfolded, in order
+fn parse_raw_headers(raw: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = Vec::new();
+    for line in raw.lines() {
+        if line.trim().is_empty() {
+            break;
+        }
+        if line.starts_with([' ', '\t']) {
+            if let Some((_, value)) = headers.last_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+        } else if let Some((name, value)) = line.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    headers
+}
+
+/// the headers of the raw message `raw`, repeated ones joined by newlines
+fn raw_header_map(raw: &str) -> BTreeMap<String, String> {
+    let mut headers = BTreeMap::new();
+    for (name, value) in parse_raw_headers(raw) {
+        headers
+            .entry(name)
+            .and_modify(|values: &mut String| {
+                values.push('\n');
+                values.push_str(&value);
+            })
+            .or_insert(value);
+    }
+    headers
+}
+
+fn raw_header<'a>(headers: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    headers
+        .iter()
+        .find(|(key, _)| key.eq_ignore_ascii_case(name))
+        .map(|(_, value)| value.as_str())
+}
+
+/// the message-ids in a header like References, in order
+fn header_message_ids(value: &str) -> Vec<String> {
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .map(|(id, _)| id.trim().to_string())
+        .collect()
+}
+
+/// a message in the reply tree of a thread
+#[derive(Debug)]
+struct ThreadNode {
+    // canonical message-id
+    id: String,
+    author: String,
+    datetime: Option<NaiveDateTime>,
+    // the message this one replies to, `None` for the starter and for replies to messages
+    // outside the thread
+    parent: Option<String>,
+}
+
+/// the reply tree of a thread, or of its first messages when it has too many to fetch
+#[derive(Debug)]
+struct ThreadTree {
+    nodes: Vec<ThreadNode>,
+    // messages were left out because of the cap
+    truncated: bool,
+    // messages in the whole thread
+    total: usize,
+}
+
+/// the messages of the thread of `id` in thread order, each linked to the message it replies to
+/// according to the In-Reply-To or, failing that, References header of its raw view. as every
+/// message costs a fetch, only the first `max_messages` are taken when given.
+async fn build_thread_tree(
+    fetcher: &Fetcher,
+    id: &str,
+    max_messages: Option<usize>,
+) -> Result<ThreadTree> {
+    let mut slugs = thread_message_ids(fetcher, id).await?;
+    let total = slugs.len();
+    slugs.truncate(max_messages.unwrap_or(total));
+    let ids: HashSet<String> = slugs
+        .iter()
+        .map(|slug| canonical_message_id(slug))
+        .collect();
+    let mut nodes = Vec::new();
+    for slug in &slugs {
+        let source = get_raw_source(fetcher, slug).await?;
+        let message = RawMessage::parse(source.as_bytes())
+            .with_context(|| format!("the raw view of {slug} is not a message"))?;
+        let parent = message
+            .in_reply_to
+            .into_iter()
+            .chain(message.references.into_iter().rev())
+            .find(|parent| ids.contains(parent));
+        nodes.push(ThreadNode {
+            id: canonical_message_id(slug),
+            author: match message.author_name.is_empty() {
+                true => message.author_email,
+                false => message.author_name,
+            },
+            datetime: message.date.map(|date| date.naive_utc()),
+            parent,
+        });
+    }
+
+    // broken headers could make messages each other's ancestors; cut such a loop where it
+    // closes so the result stays a tree
+    let mut parents: std::collections::HashMap<String, String> = nodes
+        .iter()
+        .filter_map(|node| Some((node.id.clone(), node.parent.clone()?)))
+        .collect();
+    for node in &mut nodes {
+        let mut seen = HashSet::new();
+        let mut current = node.parent.clone();
+        while let Some(id) = current {
+            if id == node.id {
+                node.parent = None;
+                parents.remove(&node.id);
+                break;
+            }
+            if !seen.insert(id.clone()) {
+                // a loop further up, it is cut at one of its own messages
+                break;
+            }
+            current = parents.get(&id).cloned();
+        }
+    }
+    Ok(ThreadTree {
+        truncated: nodes.len() < total,
+        nodes,
+        total,
+    })
+}
+
+/// the reply tree of the thread of `starter_id` as a Graphviz digraph, with a node per message
+/// labeled with its author and time, and an edge from each message to its replies. messages
+/// whose parent is unknown hang off the starter with a dashed edge.
+pub async fn thread_to_dot(
+    fetcher: &Fetcher,
+    starter_id: &MessageId,
+    max_messages: Option<usize>,
+) -> Result<String> {
+    let ThreadTree {
+        nodes,
+        truncated,
+        total,
+    } = build_thread_tree(fetcher, &starter_id.to_string(), max_messages).await?;
+    let quote = |s: &str| {
+        let escaped = s
+            .replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n");
+        format!("\"{escaped}\"")
+    };
+
+    let mut dot = String::from("digraph thread {\n");
+    if truncated {
+        dot.push_str(&format!(
+            "    // the first {} of {total} messages\n",
+            nodes.len()
+        ));
+    }
+    dot.push_str("    node [shape=box];\n");
+    for node in &nodes {
+        let time = node
+            .datetime
+            .map(|datetime| datetime.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default();
+        dot.push_str(&format!(
+            "    {} [label={}];\n",
+            quote(&node.id),
+            quote(&format!("{}\n{time}", node.author))
+        ));
+    }
+    let root = nodes.first().map(|node| node.id.as_str());
+    for node in &nodes {
+        match (&node.parent, root) {
+            (Some(parent), _) => {
+                dot.push_str(&format!("    {} -> {};\n", quote(parent), quote(&node.id)))
+            }
+            (None, Some(root)) if root != node.id => dot.push_str(&format!(
+                "    {} -> {} [style=dashed];\n",
+                quote(root),
+                quote(&node.id)
+            )),
+            _ => {}
+        }
+    }
+    dot.push_str("}\n");
+    Ok(dot)
+}
+
+/// save the raw source of every message in the thread of `starter_id` to `out` as an mboxrd
+/// file, oldest first by their Date headers. each message gets a `From ` line with its sender
+/// and date, lines of it starting with `From `, after any `>`s, get another `>`, and a blank
+/// line ends it
+pub async fn archive_thread_mbox(
+    fetcher: &Fetcher,
+    starter_id: &str,
+    out: &std::path::Path,
+) -> Result<()> {
+    let mut messages = Vec::new();
+    for slug in thread_message_ids(fetcher, starter_id).await? {
+        let raw = get_raw_source(fetcher, &slug).await?.replace("\r\n", "\n");
+        let headers = parse_raw_headers(&raw);
+        let sender = raw_header(&headers, "From")
+            .map(|from| parse_from_header(from).1)
+            .filter(|email| !email.is_empty() && !email.contains(char::is_whitespace))
+            .unwrap_or_else(|| "MAILER-DAEMON".to_string());
+        let datetime = raw_header(&headers, "Date")
+            .and_then(|date| chrono::DateTime::parse_from_rfc2822(date).ok())
+            .map(|date| date.naive_utc());
+        messages.push((datetime, sender, raw));
+    }
+    // messages without a readable date stay where the thread has them, after the dated ones
+    messages.sort_by_key(|(datetime, ..)| (datetime.is_none(), *datetime));
+    let mut mbox = String::new();
+    for (datetime, sender, raw) in &messages {
+        push_mbox_message(&mut mbox, sender, *datetime, raw);
+    }
+    std::fs::write(out, mbox).with_context(|| format!("failed to write {}", out.display()))
+}
+
+/// append the message `raw`, with `\n` line endings, to the mboxrd `mbox`: a `From ` line with
+/// `sender` and `datetime`, the message with a `>` before lines starting with `From ` after any
+/// `>`s, and a blank line
+fn push_mbox_message(mbox: &mut String, sender: &str, datetime: Option<NaiveDateTime>, raw: &str) {
+    let date = datetime.unwrap_or_default().format("%a %b %e %H:%M:%S %Y");
+    mbox.push_str(&format!("From {sender} {date}\n"));
+    for line in raw.lines() {
+        if line.trim_start_matches('>').starts_with("From ") {
+            mbox.push('>');
+        }
+        mbox.push_str(line);
+        mbox.push('\n');
+    }
+    mbox.push('\n');
+}
+
+/// the message `id` as the fetcher's store kept it, when it kept its content, scraped otherwise
+async fn kept_or_scraped(fetcher: &Fetcher, id: &str) -> Result<EmailThreadDetail> {
+    let id = MessageId::parse(id)?;
+    match kept_message(fetcher, &id)? {
+        Some(detail) => Ok(detail),
+        None => get_thread_by_id(fetcher, &id).await,
+    }
+}
+
+/// the message `id` as the fetcher's store kept it with its content, if it did
+fn kept_message(fetcher: &Fetcher, id: &MessageId) -> Result<Option<EmailThreadDetail>> {
+    let Some(store) = &fetcher.store else {
+        return Ok(None);
+    };
+    // details scraped without their content are kept too
+    Ok(store
+        .message(&id.to_string())?
+        .filter(|detail| detail.content_missing || !detail.content.is_empty()))
+}
+
+/// every message of the thread of message `id`, in the order the archive lists them, the
+/// starter first. those the fetcher's store kept are not fetched again. when more than one is
+/// missing they all come from the thread's flat view, a single page instead of a page and a raw
+/// view each, and have no raw headers
+pub async fn thread_messages(fetcher: &Fetcher, id: &MessageId) -> Result<Vec<EmailThreadDetail>> {
+    let detail = kept_or_scraped(fetcher, &id.to_string()).await?;
+    if detail.replies.is_empty() {
+        return Ok(vec![detail]);
+    }
+    let ids = detail.replies.clone();
+    let mut known = HashMap::new();
+    known.insert(canonical_message_id(&detail.id), detail);
+    for other in &ids {
+        let key = canonical_message_id(other);
+        if known.contains_key(&key) {
+            continue;
+        }
+        if let Some(kept) = kept_message(fetcher, &MessageId::parse(other)?)? {
+            known.insert(key, kept);
+        }
+    }
+    let missing = ids
+        .iter()
+        .filter(|other| !known.contains_key(&canonical_message_id(other)))
+        .count();
+    if missing > 1 {
+        for detail in get_thread_flat(fetcher, &MessageId::parse(&ids[0])?).await? {
+            known
+                .entry(canonical_message_id(&detail.id))
+                .or_insert(detail);
+        }
+    }
+    let mut messages = Vec::new();
+    for other in &ids {
+        messages.push(match known.remove(&canonical_message_id(other)) {
+            Some(detail) => detail,
+            None => get_thread_by_id(fetcher, &MessageId::parse(other)?).await?,
+        });
+    }
+    Ok(messages)
+}
+
+/// `text` as a header value, in RFC 2047 encoded words when it is not plain ASCII
+fn header_text(text: &str) -> String {
+    if text.is_ascii() {
+        return text.to_string();
+    }
+    use base64::Engine;
+
+    // encoded words are at most 75 characters, 45 bytes of text make 60 of base64
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in text.chars() {
+        if word.len() + c.len_utf8() > 45 {
+            words.push(std::mem::take(&mut word));
+        }
+        word.push(c);
+    }
+    words.push(word);
+    words
+        .iter()
+        .map(|word| {
+            let encoded = base64::engine::general_purpose::STANDARD.encode(word);
+            format!("=?UTF-8?B?{encoded}?=")
+        })
+        .collect::<Vec<_>>()
+        .join("\n ")
+}
+
+/// the text of the `content` html as the body of a plain text mail, a line per `<br>` or block,
+/// the lines of blockquotes behind a `>` per level
+fn mail_body(content: &str) -> String {
+    let fragment = Html::parse_fragment(content);
+    // each line with the number of blockquotes it is in
+    let mut lines: Vec<(usize, String)> = vec![(0, String::new())];
+    let mut after_break = false;
+    for node in fragment.root_element().descendants() {
+        let depth = node
+            .ancestors()
+            .filter(|ancestor| {
+                ancestor
+                    .value()
+                    .as_element()
+                    .is_some_and(|element| element.name() == "blockquote")
+            })
+            .count();
+        match node.value() {
+            scraper::Node::Element(element)
+                if matches!(element.name(), "br" | "p" | "div" | "pre" | "blockquote") =>
+            {
+                lines.push((depth, String::new()));
+                after_break = true;
+            }
+            scraper::Node::Text(part) => {
+                // the newline often written after a `<br>` ends the same line
+                let part = match after_break {
+                    true => part.strip_prefix('\n').unwrap_or(part),
+                    false => part,
+                };
+                after_break = false;
+                for (i, piece) in part.split('\n').enumerate() {
+                    let (line_depth, line) = lines.last_mut().unwrap();
+                    if i == 0 && (line.is_empty() || *line_depth == depth) {
+                        *line_depth = depth;
+                        line.push_str(piece);
+                    } else {
+                        lines.push((depth, piece.to_string()));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let mut body: Vec<String> = lines
+        .into_iter()
+        .map(|(depth, line)| match depth {
+            0 => line.trim_end().to_string(),
+            _ => format!("{} {}", ">".repeat(depth), line.trim_end())
+                .trim_end()
+                .to_string(),
+        })
+        .collect();
+    // one blank line at most between paragraphs, none around them
+    body.dedup_by(|line, previous| line == previous && line.trim_start_matches('>').is_empty());
+    let body = body.join("\n");
+    format!("{}\n", body.trim_matches('\n'))
+}
+
+/// `detail` as an RFC 2822 message, with headers made up from what was scraped of it. a reply
+/// without its In-Reply-To and References headers kept gets ones pointing at `starter_id`, so a
+/// mail client still threads it
+fn synthesized_message(detail: &EmailThreadDetail, starter_id: &str) -> String {
+    let message_id = |id: &str| match MessageId::parse(id) {
+        Result::Ok(id) => format!("<{}>", id.0),
+        Err(_) => format!("<{id}>"),
+    };
+    let kept_header = |name: &str| {
+        detail
+            .headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.replace('\n', " "))
+    };
+    let from = match (detail.author_name.as_str(), detail.author_email.as_str()) {
+        ("", email) => email.to_string(),
+        (name, "") => header_text(name),
+        (name, email) if name.is_ascii() && name.contains(|c| ",;:<>@\"()[]\\.".contains(c)) => {
+            format!(
+                "\"{}\" <{email}>",
+                name.replace('\\', "\\\\").replace('"', "\\\"")
+            )
+        }
+        (name, email) => format!("{} <{email}>", header_text(name)),
+    };
+    let mut headers = vec![
+        ("Message-ID", message_id(&detail.id)),
+        ("Date", detail.datetime.and_utc().to_rfc2822()),
+        ("From", from),
+        ("Subject", header_text(&detail.subject)),
+    ];
+    let is_reply = canonical_message_id(&detail.id) != canonical_message_id(starter_id);
+    let starter = is_reply.then(|| message_id(starter_id));
+    if let Some(in_reply_to) = kept_header("In-Reply-To").or(starter.clone()) {
+        headers.push(("In-Reply-To", in_reply_to));
+    }
+    if let Some(references) = kept_header("References").or(starter) {
+        headers.push(("References", references));
+    }
+    headers.extend([
+        ("MIME-Version", "1.0".to_string()),
+        ("Content-Type", "text/plain; charset=utf-8".to_string()),
+        ("Content-Transfer-Encoding", "8bit".to_string()),
+    ]);
+    let mut message = String::new();
+    for (name, value) in headers {
+        message.push_str(&format!("{name}: {value}\n"));
+    }
+    message.push('\n');
+    message.push_str(&mail_body(&detail.content));
+    message
+}
+
+/// write every message of the threads of `ids` to `out` as an mboxrd file, for a mail client
+/// like mutt or notmuch, with headers made up from what was scraped of each message rather than
+/// their raw source. messages the fetcher's store kept are not fetched again. returns how many
+/// messages were written
+pub async fn export_threads_mbox(
+    fetcher: &Fetcher,
+    ids: &[MessageId],
+    out: &mut impl std::io::Write,
+) -> Result<usize> {
+    let mut written = 0;
+    for id in ids {
+        let messages = thread_messages(fetcher, id).await?;
+        let starter_id = messages[0].id.clone();
+        let mut mbox = String::new();
+        for detail in &messages {
+            let sender = match detail.author_email.as_str() {
+                "" => "MAILER-DAEMON",
+                email => email,
+            };
+            let message = synthesized_message(detail, &starter_id);
+            push_mbox_message(&mut mbox, sender, Some(detail.datetime), &message);
+        }
+        out.write_all(mbox.as_bytes())?;
+        written += messages.len();
+    }
+    Ok(written)
+}
+
+/// write every message of the threads of `ids` into the tree `dir`, with a maildir per thread
+/// named after the slug of its starter and a file per message named after its date and slug, for
+/// tools like mu or mblaze. the messages are the ones [`export_threads_mbox`] writes. messages
+/// already there are left as they are, so exporting again only adds the new replies and a
+/// backup copies only those. returns how many messages were added
+pub async fn export_threads_maildir(
+    fetcher: &Fetcher,
+    ids: &[MessageId],
+    dir: &std::path::Path,
+) -> Result<usize> {
+    let slug = |id: &str| MessageId::parse(id).map_or_else(|_| id.to_string(), |id| id.to_string());
+    let mut written = 0;
+    for id in ids {
+        let messages = thread_messages(fetcher, id).await?;
+        let starter_id = messages[0].id.clone();
+        let maildir = dir.join(slug(&starter_id));
+        for sub in ["cur", "new", "tmp"] {
+            let sub = maildir.join(sub);
+            std::fs::create_dir_all(&sub)
+                .with_context(|| format!("failed to create {}", sub.display()))?;
+        }
+        for detail in &messages {
+            // slugs have no `/` or `:`, which would end the unique part of the name
+            let name = format!(
+                "{}.{}",
+                detail.datetime.and_utc().timestamp(),
+                slug(&detail.id)
+            );
+            let path = maildir.join("cur").join(format!("{name}:2,"));
+            if path.exists() {
+                continue;
+            }
+            // written in tmp and moved, so a reader never sees half a message
+            let tmp = maildir.join("tmp").join(&name);
+            std::fs::write(&tmp, synthesized_message(detail, &starter_id))
+                .with_context(|| format!("failed to write {}", tmp.display()))?;
+            std::fs::rename(&tmp, &path)
+                .with_context(|| format!("failed to move {} to cur", tmp.display()))?;
+            written += 1;
+        }
+    }
+    Ok(written)
+}
+
+/// handle of a running [`watch_thread`] poller.
+/// the poller also stops when the handle is dropped.
+pub struct WatchHandle {
+    stop_tx: oneshot::Sender<()>,
+    join_handle: tokio::task::JoinHandle<()>,
+}
+
+impl WatchHandle {
+    /// stop polling and wait for the poller to finish its current round
+    pub async fn stop(self) {
+        let _ = self.stop_tx.send(());
+        let _ = self.join_handle.await;
+    }
+}
+
+/// poll the thread of `starter_id` every `interval` and call `on_new` with the details of each
+/// message that was not in the thread yet when watching started.
+pub async fn watch_thread(
+    fetcher: &Fetcher,
+    starter_id: &MessageId,
+    interval: Duration,
+    mut on_new: impl FnMut(EmailThreadDetail) + Send + 'static,
+) -> Result<WatchHandle> {
+    let fetcher = fetcher.clone();
+    let starter_id = starter_id.to_string();
+    // messages already in the thread are not news
+    let mut seen_ids: HashSet<String> = thread_message_ids(&fetcher, &starter_id)
+        .await?
+        .into_iter()
+        .collect();
+
+    let (stop_tx, mut stop_rx) = oneshot::channel();
+    let join_handle = tokio::spawn(async move {
+        loop {
+            // a dropped handle closes the channel, which stops the poller too
+            tokio::select! {
+                _ = &mut stop_rx => break,
+                _ = tokio::time::sleep(interval) => {}
+            }
+            let ids = thread_message_ids(&fetcher, &starter_id)
+                .await
+                .unwrap_or_else(|err| {
+                    fetcher.warn(format!("failed to poll thread {starter_id}: {err:#}"));
+                    Vec::new()
+                });
+            for id in ids {
+                if !seen_ids.insert(id.clone()) {
+                    continue;
+                }
+                match MessageId::parse(&id) {
+                    Result::Ok(id) => match get_thread_by_id(&fetcher, &id).await {
+                        Result::Ok(reply) => on_new(reply),
+                        Err(err) => fetcher
+                            .warn(format!("failed to get reply {id} of {starter_id}: {err:#}")),
+                    },
+                    Err(err) => {
+                        fetcher.warn(format!("skipping reply {id} of {starter_id}: {err:#}"))
+                    }
+                }
+            }
+        }
+    });
+    Ok(WatchHandle {
+        stop_tx,
+        join_handle,
+    })
+}
+
+/// a file name for `attachment` that cannot leave the download directory. attachments of
+/// different messages may share a name, so it starts with the archive's number for the file
+pub fn attachment_file_name(attachment: &ThreadAttachment) -> String {
+    let number = attachment.href.rsplit('/').nth(1).unwrap_or("0");
+    let name = std::path::Path::new(&attachment.name)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or("attachment");
+    format!("{number}-{name}")
+}
+
+/// download the `attachments` with one of `extensions`, or all of them when there are none, into
+/// `dir`, and return how many were saved. a failed download is reported and skipped
+pub async fn save_attachments(
+    fetcher: &Fetcher,
+    attachments: &[ThreadAttachment],
+    dir: &std::path::Path,
+    extensions: &[String],
+) -> Result<usize> {
+    let wanted: Vec<_> = attachments
+        .iter()
+        .filter(|attachment| extensions.is_empty() || attachment.has_extension(extensions))
+        .cloned()
+        .collect();
+    std::fs::create_dir_all(dir)?;
+    let mut saved = 0;
+    let contents = download_attachments(fetcher, &wanted, usize::MAX).await;
+    for (attachment, content) in wanted.iter().zip(contents) {
+        match content {
+            Result::Ok(Some(content)) => {
+                std::fs::write(dir.join(attachment_file_name(attachment)), content.bytes)?;
+                saved += 1;
+            }
+            Result::Ok(None) => unreachable!("no attachment is over the limit"),
+            Err(err) => fetcher.warn(format!("failed to download {}: {err:#}", attachment.name)),
+        }
+    }
+    Ok(saved)
+}
+
+/// a fetcher serving the archive pages kept in `fixtures/`, which `--record fixtures` writes
+#[cfg(test)]
+fn fixture_fetcher() -> Fetcher {
+    Fetcher::default().with_http(http::Replay::new(std::path::Path::new("fixtures")))
+}
+
+#[tokio::test]
+async fn test1() {
+    // has Chinese ':' in the subject title, like this: 'Re：Limit length of queryies in pg_stat_statement extension'
+    let fetcher = fixture_fetcher();
+    let start_day = "20250118";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    println!("Fetching emails from: {} ~ {}", start_date, end_date);
+    let thread_emails = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+    assert!(thread_emails.len() == 1);
+
+    println!("\nFirst emails in each thread:");
+    println!("----------------------------");
+    for thread in thread_emails {
+        println!("{}", thread);
+        println!();
+    }
+}
+
+#[tokio::test]
+async fn test2() {
+    // has Re: in subject title, like this: 'Fwd: Re: A new look at old NFS readdir() problems?'
+    let fetcher = fixture_fetcher();
+    let start_day = "20250102";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    println!("Fetching emails from: {} ~ {}", start_date, end_date);
+    let thread_emails = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+    assert!(thread_emails
+        .iter()
+        .any(|thread| thread.subject.contains("Re:")));
+
+    println!("\nFirst emails in each thread:");
+    println!("----------------------------");
+    for thread in thread_emails {
+        println!("{}", thread);
+        println!();
+    }
+}
+
+#[tokio::test]
+async fn test3() {
+    // has unicode emoji and '\n' in the subject title
+    let fetcher = fixture_fetcher();
+    let start_day = "20250106";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    println!("Fetching emails from: {} ~ {}", start_date, end_date);
+    let thread_emails = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+    assert!(thread_emails
+        .iter()
+        .any(|thread| !thread.subject.contains('\n')));
+
+    println!("\nFirst emails in each thread:");
+    println!("----------------------------");
+    for thread in thread_emails {
+        println!("{}", thread);
+        println!();
+    }
+}
+
+#[tokio::test]
+async fn test4() {
+    let fetcher = fixture_fetcher();
+    let start_day = "20240104";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    let thread_emails_20240104 = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+    let start_day = "20240105";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    let thread_emails_20240105 = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+    let start_day = "20240106";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
+    let thread_emails_20240106 = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+
+    let start_day = "20240104";
+    let start_date = NaiveDate::parse_from_str(start_day, "%Y%m%d").unwrap();
+    let end_day = "20240106";
+    let end_date = NaiveDate::parse_from_str(end_day, "%Y%m%d").unwrap();
+    let end_date = end_date.and_hms_opt(23, 59, 59).unwrap();
+    let thread_emails = get_new_subjects_between(&fetcher, start_date.into(), end_date)
+        .await
+        .unwrap();
+
+    assert!(
+        thread_emails_20240104.len() + thread_emails_20240105.len() + thread_emails_20240106.len()
+            == thread_emails.len()
+    );
+    assert!(thread_emails.iter().all(|thread| {
+        thread_emails_20240104.iter().any(|t| t.id == thread.id)
+            || thread_emails_20240105.iter().any(|t| t.id == thread.id)
+            || thread_emails_20240106.iter().any(|t| t.id == thread.id)
+    }));
+}
+
+#[tokio::test]
+async fn get_email_thread_detail() {
+    let detail = get_thread_by_id(
+        &Fetcher::default(),
+        &MessageId::parse(
+            "CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com",
+        )
+        .unwrap(),
+    )
+    .await
+    .unwrap();
+    println!("{detail:#?}");
+    assert_eq!(
+        detail.id,
+        "CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com"
+    );
+    assert_eq!(detail.subject, "Enhance 'pg_createsubscriber' to retrieve databases automatically when no database is provided.");
+
+    assert_eq!(
+        detail.datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
+        "2025-01-22 13:59:09"
+    );
+    assert_eq!(detail.author_name, "Shubham Khanna");
+    assert_eq!(detail.author_email, "khannashubham1197@gmail.com");
+    assert!(detail.content.contains("<br>"));
+    assert_eq!(detail.attachments.len(), 1);
+    assert_eq!(
+        detail.attachments[0].name,
+        "v1-0001-Enhance-pg_createsubscriber-to-fetch-and-append-a.patch"
+    );
+    assert_eq!(detail.attachments[0].href, "/message-id/attachment/170920/v1-0001-Enhance-pg_createsubscriber-to-fetch-and-append-a.patch");
+    assert_eq!(detail.replies.len(), 34);
+}
+
+#[tokio::test]
+async fn watch_thread_reports_new_replies() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+    use std::sync::{Arc, Mutex};
+
+    let thread_ids = Arc::new(Mutex::new(vec!["starter@x", "reply-1@x"]));
+    let server_ids = thread_ids.clone();
+    let server = MockServer::start(move |req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let ids = server_ids.lock().unwrap().clone();
+        MockResponse::html(MessagePage::new(id).thread(&ids).render())
+    });
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let handle = watch_thread(
+        &Fetcher::new(&server.url()),
+        &MessageId::parse("starter@x").unwrap(),
+        Duration::from_millis(20),
+        move |reply| tx.send(reply.id).unwrap(),
+    )
+    .await
+    .unwrap();
+    thread_ids.lock().unwrap().push("reply-2@x");
+
+    let new_id = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(new_id, "reply-2%40x");
+    handle.stop().await;
+    assert!(rx.try_recv().is_err());
+}
+
+#[tokio::test]
+async fn records_visited_since_urls() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(mock_server::MessagePage::new(id).render());
+        }
+        let token = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let rows: &[_] = match token {
+            "202501180000" => &[
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second topic", "Bob", "12:30"),
+            ],
+            "202501181230" => &[
+                ("second", "Second topic", "Bob", "12:30"),
+                ("third", "Third topic", "Carol", "15:45"),
+            ],
+            "202501181545" => &[("third", "Third topic", "Carol", "15:45")],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+
+    let fetcher = Fetcher::new(&server.url()).record_since_urls();
+    let start_date = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let threads = get_new_subjects_between(
+        &fetcher,
+        start_date.into(),
+        start_date.and_hms_opt(23, 59, 59).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(threads.len(), 3);
+    let expected: Vec<_> = ["202501180000", "202501181230", "202501181545"]
+        .iter()
+        .map(|token| format!("{}/list/pgsql-hackers/since/{token}", server.url()))
+        .collect();
+    assert_eq!(fetcher.since_urls(), expected);
+}
+
+#[tokio::test]
+async fn active_subjects_within_hours() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501181000" => MockResponse::html(listing_page(&[(
+                "Jan. 18, 2025",
+                &[
+                    ("morning%40x", "Morning topic", "Alice", "10:30"),
+                    ("noon%40x", "Noon topic", "Bob", "12:00"),
+                    ("evening%40x", "Evening topic", "Carol", "17:00"),
+                ],
+            )])),
+            _ => MockResponse::not_found(),
+        }
+    });
+
+    let fetcher = Fetcher::new(&server.url()).record_since_urls();
+    let end_date = NaiveDate::from_ymd_opt(2025, 1, 18)
+        .unwrap()
+        .and_hms_opt(16, 0, 0)
+        .unwrap();
+    let threads = get_active_subjects_between(&fetcher, end_date - TimeDelta::hours(6), end_date)
+        .await
+        .unwrap();
+
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["morning%40x", "noon%40x"]);
+    assert_eq!(
+        fetcher.since_urls(),
+        [format!(
+            "{}/list/pgsql-hackers/since/202501181000",
+            server.url()
+        )]
+    );
+}
+
+#[test]
+fn listing_and_detail_datetime_mismatch() {
+    let date = NaiveDate::from_ymd_opt(2025, 1, 22).unwrap();
+    let listed = EmailThread {
+        id: "starter".to_string(),
+        list: MailingList::default(),
+        subject: "Some topic".to_string(),
+        datetime: date.and_hms_opt(13, 59, 0).unwrap(),
+        author: "Jane Doe".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+    let mut detail = EmailThreadDetail {
+        id: "starter".to_string(),
+        subject: "Some topic".to_string(),
+        datetime: date.and_hms_opt(13, 59, 9).unwrap(),
+        author_name: "Jane Doe".to_string(),
+        author_email: "jane.doe@example.org".to_string(),
+        content: String::new(),
+        content_missing: false,
+        content_new_text: String::new(),
+        stats: ContentStats::default(),
+        inline_patches: Vec::new(),
+        references: References::default(),
+        attachments: Vec::new(),
+        replies: Vec::new(),
+        headers: BTreeMap::new(),
+    };
+    assert_eq!(datetime_mismatch(&listed, &detail), None);
+
+    // e.g. the detail page rendered in another timezone
+    detail.datetime = date.and_hms_opt(14, 59, 9).unwrap();
+    let warning = datetime_mismatch(&listed, &detail).unwrap();
+    assert!(warning.contains("listed at 2025-01-22 13:59"));
+    assert!(warning.contains("says 2025-01-22 14:59:09"));
+}
+
+#[tokio::test]
+async fn thread_key_ignores_slug_encoding() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let page = match req.path.as_str() {
+            "/message-id/reply-1" => {
+                MessagePage::new("reply-1").thread(&["starter%40example.org", "reply-1", "reply-2"])
+            }
+            "/message-id/reply-2" => {
+                MessagePage::new("reply-2").thread(&["starter@example.org", "reply-1", "reply-2"])
+            }
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(page.render())
+    });
+
+    let fetcher = Fetcher::new(&server.url());
+    let key = thread_key(&fetcher, "reply-1").await.unwrap();
+    assert_eq!(key, "starter@example.org");
+    assert_eq!(thread_key(&fetcher, "reply-2").await.unwrap(), key);
+    assert_eq!(canonical_message_id("<starter%40example.org>"), key);
+}
+
+#[tokio::test]
+async fn attachment_downloads_respect_concurrency_limit() {
+    use mock_server::{MockResponse, MockServer};
+    use std::sync::atomic::AtomicUsize;
+
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let (in_flight_, max_in_flight_) = (in_flight.clone(), max_in_flight.clone());
+    let server = MockServer::start(move |req| {
+        let current = in_flight_.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight_.fetch_max(current, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        in_flight_.fetch_sub(1, Ordering::SeqCst);
+        MockResponse::new(200, "text/x-diff", req.path.clone())
+    });
+
+    let attachments: Vec<_> = (0..8)
+        .map(|i| ThreadAttachment {
+            name: format!("v1-000{i}.patch"),
+            href: format!("/message-id/attachment/{i}/v1-000{i}.patch"),
+            size: None,
+        })
+        .collect();
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        attachment_concurrency: 2,
+        ..FetchConfig::default()
+    });
+    let results = download_attachments(&fetcher, &attachments, 1024).await;
+
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+    for (attachment, result) in attachments.iter().zip(results) {
+        let content = result.unwrap().unwrap();
+        assert_eq!(content.bytes, attachment.href.as_bytes());
+    }
+}
+
+#[test]
+fn parse_message_refs() {
+    let id = "CAHv8RjKhA=_h5vAbozzJ1Opnv=KXYQHQ-fJyaMfqfRqPpnC2bA@mail.gmail.com";
+    for reference in [
+        "Discussion: https://postgr.es/m/CAHv8RjKhA=_h5vAbozzJ1Opnv=KXYQHQ-fJyaMfqfRqPpnC2bA@mail.gmail.com",
+        "https://postgr.es/m/CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com/",
+        "https://www.postgresql.org/message-id/CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com",
+        "https://www.postgresql.org/message-id/flat/CAHv8RjKhA=_h5vAbozzJ1Opnv=KXYQHQ-fJyaMfqfRqPpnC2bA@mail.gmail.com",
+        "<CAHv8RjKhA=_h5vAbozzJ1Opnv=KXYQHQ-fJyaMfqfRqPpnC2bA@mail.gmail.com>",
+        id,
+    ] {
+        assert_eq!(parse_message_ref(reference).unwrap(), id, "{reference}");
+    }
+
+    assert!(parse_message_ref("https://www.postgresql.org/list/pgsql-hackers/").is_err());
+    assert!(parse_message_ref("not a message id").is_err());
+}
+
+#[test]
+fn message_id_round_trips() {
+    let id = MessageId::parse("https://postgr.es/m/a/b%23c=1@example.org/").unwrap();
+    assert_eq!(String::from(id.clone()), "a/b#c=1@example.org");
+    assert_eq!(id.to_string(), "a%2Fb%23c%3D1%40example.org");
+    assert_eq!(MessageId::try_from("<a/b#c=1@example.org>").unwrap(), id);
+    assert!(MessageId::try_from("a b@example.org".to_string()).is_err());
+    assert!(MessageId::parse("https://postgr.es/list/pgsql-hackers/").is_err());
+}
+
+#[tokio::test]
+async fn resolve_discussion_link_to_thread_starter() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let mut page = MessagePage::new(id).thread(&["starter@example.org", "reply@example.org"]);
+        page.subject = format!("Re: topic ({id})");
+        MockResponse::html(page.render())
+    });
+
+    let detail = thread_for_discussion_link(
+        &Fetcher::new(&server.url()),
+        "Discussion: https://postgr.es/m/reply@example.org",
+    )
+    .await
+    .unwrap();
+    assert_eq!(detail.id, "starter%40example.org");
+    assert_eq!(
+        server.paths(),
+        [
+            "/message-id/reply%40example.org",
+            "/message-id/raw/reply%40example.org",
+            "/message-id/starter%40example.org",
+            "/message-id/raw/starter%40example.org"
+        ]
+    );
+}
+
+#[test]
+fn retry_decision_per_error_class() {
+    let config = FetchConfig::default();
+    assert_eq!(
+        config.retry_delay(ErrorClass::Connect, 1),
+        Some(Duration::from_secs(10))
+    );
+    assert_eq!(
+        config.retry_delay(ErrorClass::Connect, 2),
+        Some(Duration::from_secs(20))
+    );
+    assert_eq!(config.retry_delay(ErrorClass::Connect, 3), None);
+    assert_eq!(
+        config.retry_delay(ErrorClass::Timeout, 3),
+        Some(Duration::from_secs(4))
+    );
+    assert_eq!(config.retry_delay(ErrorClass::Timeout, 4), None);
+    assert_eq!(
+        config.retry_delay(ErrorClass::Request, 1),
+        Some(Duration::from_millis(500))
+    );
+    assert_eq!(config.retry_delay(ErrorClass::Other, 1), None);
+}
+
+#[tokio::test]
+async fn classify_request_errors() {
+    use mock_server::{MockResponse, MockServer};
+    use std::net::TcpListener;
+
+    // nothing listens on a port that was just released
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let err = Client::new()
+        .get(format!("http://127.0.0.1:{port}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorClass::of(&err), ErrorClass::Connect);
+
+    let slow = MockServer::start(|_| {
+        std::thread::sleep(Duration::from_millis(500));
+        MockResponse::html("<html></html>")
+    });
+    let err = Client::builder()
+        .timeout(Duration::from_millis(50))
+        .build()
+        .unwrap()
+        .get(slow.url())
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorClass::of(&err), ErrorClass::Timeout);
+
+    // a server hanging up without answering
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let addr = listener.local_addr().unwrap();
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            std::io::Read::read(&mut stream, &mut [0; 1024]).unwrap();
+        }
+    });
+    let err = Client::new()
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(ErrorClass::of(&err), ErrorClass::Request);
+}
+
+#[tokio::test]
+async fn get_document_gives_up_with_classified_error() {
+    use std::net::TcpListener;
+
+    let port = TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let fetcher = Fetcher::new(&format!("http://127.0.0.1:{port}")).with_config(FetchConfig {
+        connect_retry: RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        },
+        ..FetchConfig::default()
+    });
+
+    let err = fetcher
+        .get_document(&fetcher.message_url("missing"))
+        .await
+        .unwrap_err();
+    match err.downcast_ref::<ScrapeError>() {
+        Some(ScrapeError::Network {
+            class, attempts, ..
+        }) => {
+            assert_eq!(*class, ErrorClass::Connect);
+            assert_eq!(*attempts, 3);
+        }
+        other => panic!("unexpected error {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn browse_one_page_at_a_time() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" => &[
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second topic", "Bob", "12:30"),
+            ],
+            "202501181230" => &[
+                ("second", "Second topic", "Bob", "12:30"),
+                ("third", "Third topic", "Carol", "15:45"),
+            ],
+            _ => &[("fourth", "Fourth topic", "Dave", "18:00")],
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+
+    let mut output = Vec::new();
+    browse(
+        &Fetcher::new(&server.url()),
+        NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        "\nq\n".as_bytes(),
+        &mut output,
+    )
+    .await
+    .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("   1. 2025-01-18 09:00  Alice  First topic"));
+    assert!(output.contains("   2. 2025-01-18 12:30  Bob  Second topic"));
+    assert!(output.contains("   3. 2025-01-18 15:45  Carol  Third topic"));
+    assert!(!output.contains("Fourth topic"));
+    assert_eq!(server.requests().len(), 2);
+}
+
+#[tokio::test]
+async fn browse_moves_past_a_page_of_repeats() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    // more threads of 09:00 than a page holds, the page starting at 09:00 has only those
+    let server = MockServer::start(|req| {
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" | "202501180900" => &[
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second topic", "Bob", "09:00"),
+            ],
+            "202501180901" => &[("third", "Third topic", "Carol", "15:45")],
+            _ => &[],
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+
+    let mut output = Vec::new();
+    browse(
+        &Fetcher::new(&server.url()),
+        NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        "\n\n\n".as_bytes(),
+        &mut output,
+    )
+    .await
+    .unwrap();
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.contains("   2. 2025-01-18 09:00  Bob  Second topic"));
+    assert!(output.contains("   3. 2025-01-18 15:45  Carol  Third topic"));
+    assert!(output.ends_with("No more threads.\n"));
+}
+
+#[tokio::test]
+async fn get_document_rejects_non_html() {
+    use mock_server::{MockResponse, MockServer};
+
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/message-id/json" => MockResponse::new(200, "application/json", r#"{"error":"gone"}"#),
+        "/message-id/empty" => MockResponse::html(""),
+        _ => MockResponse::html(mock_server::MessagePage::new("ok").render()),
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let err = fetcher
+        .get_document(&fetcher.message_url("json"))
+        .await
+        .unwrap_err();
+    match err.downcast_ref::<ScrapeError>() {
+        Some(ScrapeError::NotHtml { content_type, .. }) => {
+            assert_eq!(content_type, "application/json")
+        }
+        other => panic!("unexpected error {other:?}"),
+    }
+    assert!(err.to_string().contains("application/json"));
+
+    let err = fetcher
+        .get_document(&fetcher.message_url("empty"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::EmptyPage { .. })
+    ));
+
+    assert!(fetcher
+        .get_document(&fetcher.message_url("ok"))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn forwarded_subjects_start_threads() {
+    use mock_server::{MockResponse, MockServer};
+
+    let server = MockServer::start(|_| MockResponse::not_found());
+    let fetcher = Fetcher::new(&server.url());
+    let thread = |subject: &str| EmailThread {
+        id: "id".to_string(),
+        list: MailingList::default(),
+        subject: subject.to_string(),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+
+    assert!(is_thread_starter(&fetcher, &thread("Fwd: Re: Proposal"))
+        .await
+        .unwrap());
+    assert!(is_thread_starter(&fetcher, &thread("FW: Re: Proposal"))
+        .await
+        .unwrap());
+    assert!(!is_thread_starter(&fetcher, &thread("Re: Fwd: Proposal"))
+        .await
+        .unwrap());
+    assert!(!is_thread_starter(&fetcher, &thread("RE： Proposal"))
+        .await
+        .unwrap());
+    // classified from the subject alone
+    assert_eq!(server.requests().len(), 0);
+}
+
+#[tokio::test]
+async fn thread_starters_told_by_headers() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    // (id, subject, the headers naming what it answers, its thread)
+    const MESSAGES: [(&str, &str, &str, &[&str]); 7] = [
+        (
+            "fwd",
+            "Fwd: Re: Proposal",
+            "In-Reply-To: <s@x>",
+            &["s%40x", "fwd%40x"],
+        ),
+        (
+            "aw",
+            "AW: Proposal",
+            "References: <s@x>",
+            &["s%40x", "aw%40x"],
+        ),
+        (
+            "renamed",
+            "Idea (was: Proposal)",
+            "In-Reply-To: <s@x>",
+            &["s%40x", "renamed%40x"],
+        ),
+        ("fresh", "Re: Proposal", "", &["fresh%40x"]),
+        (
+            "fresh-reply",
+            "Re: Proposal",
+            "In-Reply-To: <fresh@x>",
+            &["fresh%40x", "fresh-reply%40x"],
+        ),
+        (
+            "fwd-reply",
+            "Re: Fwd: Re: Proposal",
+            "In-Reply-To: <missing@x>\r\nReferences: <s@x> <fwd@x>",
+            &["s%40x", "fwd%40x", "fwd-reply%40x"],
+        ),
+        (
+            "orphan",
+            "Proposal",
+            "In-Reply-To: <private@y>",
+            &["orphan%40x"],
+        ),
+    ];
+    let server = MockServer::start(|req| {
+        let (raw, id) = match req.path.strip_prefix("/message-id/raw/") {
+            Some(id) => (true, id),
+            None => (false, req.path.trim_start_matches("/message-id/")),
+        };
+        let Some((name, _, parents, thread)) = MESSAGES
+            .iter()
+            .find(|(name, ..)| id == format!("{name}%40x"))
+        else {
+            return MockResponse::not_found();
+        };
+        if raw {
+            let raw = format!(
+                "From: Alice <alice@example.org>\r\nMessage-ID: <{name}@x>\r\n{parents}\r\n\
+                 \r\nbody\r\n"
+            );
+            return MockResponse::new(200, "text/plain", raw);
+        }
+        MockResponse::html(MessagePage::new(id).thread(thread).render())
+    });
+    let fetcher = Fetcher::new(&server.url()).thread_by_headers();
+    let starts = async |name: &str| {
+        let (_, subject, ..) = MESSAGES.iter().find(|(other, ..)| *other == name).unwrap();
+        let thread = EmailThread {
+            id: format!("{name}%40x"),
+            list: MailingList::default(),
+            subject: subject.to_string(),
+            datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+            author: "Alice".to_string(),
+            reply_count: None,
+            replies: None,
+        };
+        is_thread_starter(&fetcher, &thread).await.unwrap()
+    };
+
+    assert!(!starts("fwd").await);
+    assert!(!starts("aw").await);
+    assert!(!starts("renamed").await);
+    assert!(starts("fresh").await);
+    assert_eq!(server.hits("/message-id/fresh%40x"), 0);
+    // the thread of what these answer is known by now, so no dropdown is asked for
+    assert!(!starts("fresh-reply").await);
+    assert!(!starts("fwd-reply").await);
+    assert_eq!(server.hits("/message-id/fresh-reply%40x"), 0);
+    assert_eq!(server.hits("/message-id/fwd-reply%40x"), 0);
+    assert_eq!(
+        known_starter_id(&fetcher, "fresh-reply%40x").as_deref(),
+        Some("fresh%40x")
+    );
+    assert_eq!(
+        known_starter_id(&fetcher, "fwd-reply%40x").as_deref(),
+        Some("s%40x")
+    );
+    // what it answers is not in the archive, so the archive starts a thread with it
+    assert!(starts("orphan").await);
+}
+
+#[tokio::test]
+async fn sequential_fetches_reuse_one_connection() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).render())
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        http_version: HttpVersion::Http1Only,
+        ..FetchConfig::default()
+    });
+
+    for i in 0..5 {
+        // clones share the pool too
+        let fetcher = fetcher.clone();
+        fetcher
+            .get_document(&fetcher.message_url(&i.to_string()))
+            .await
+            .unwrap();
+    }
+    assert_eq!(server.requests().len(), 5);
+    assert_eq!(server.connections(), 1);
+}
+
+#[test]
+fn classify_threads_by_subject_and_attachments() {
+    let thread = |subject: &str| EmailThread {
+        id: "id".to_string(),
+        list: MailingList::default(),
+        subject: subject.to_string(),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+    let classify = |subject| classify_thread(&thread(subject), None);
+
+    assert_eq!(classify("[PATCH v3] Add a GUC"), ThreadCategory::Patch);
+    assert_eq!(
+        classify("Re: [RFC PATCH] Faster COPY"),
+        ThreadCategory::Patch
+    );
+    assert_eq!(classify("BUG #18765: crash in VACUUM"), ThreadCategory::Bug);
+    assert_eq!(
+        classify("Fwd: BUG #18766: wrong result"),
+        ThreadCategory::Bug
+    );
+    assert_eq!(
+        classify("Should we drop support for AIX?"),
+        ThreadCategory::Question
+    );
+    assert_eq!(
+        classify("Proposal: [DISPATCH] hooks"),
+        ThreadCategory::Discussion
+    );
+    assert_eq!(
+        classify("Improve planner estimates"),
+        ThreadCategory::Discussion
+    );
+
+    let detail = EmailThreadDetail {
+        id: "id".to_string(),
+        subject: "Improve planner estimates".to_string(),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author_name: "Alice".to_string(),
+        author_email: "alice@example.org".to_string(),
+        content: String::new(),
+        content_missing: false,
+        content_new_text: String::new(),
+        stats: ContentStats::default(),
+        inline_patches: Vec::new(),
+        references: References::default(),
+        attachments: vec![ThreadAttachment {
+            name: "v1-0001-Improve-estimates.patch".to_string(),
+            href: "/message-id/attachment/1/v1-0001-Improve-estimates.patch".to_string(),
+            size: None,
+        }],
+        replies: Vec::new(),
+        headers: BTreeMap::new(),
+    };
+    assert_eq!(
+        classify_thread(&thread("Improve planner estimates?"), Some(&detail)),
+        ThreadCategory::Patch
+    );
+}
+
+#[tokio::test]
+async fn replay_fetches_only_the_given_pages() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" => &[
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second topic", "Bob", "12:30"),
+            ],
+            "202501181230" => &[
+                ("second", "Second topic", "Bob", "12:30"),
+                ("third", "Third topic", "Carol", "15:45"),
+            ],
+            _ => &[("fourth", "Fourth topic", "Dave", "18:00")],
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let tokens = [
+        "202501180000".to_string(),
+        fetcher.since_url(
+            NaiveDate::from_ymd_opt(2025, 1, 18)
+                .unwrap()
+                .and_hms_opt(12, 30, 0)
+                .unwrap(),
+        ),
+    ];
+    let threads = replay_since_pages(&fetcher, &tokens, Some).await.unwrap();
+
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["first", "second", "third"]);
+    assert_eq!(
+        server.paths(),
+        [
+            "/list/pgsql-hackers/since/202501180000",
+            "/list/pgsql-hackers/since/202501181230"
+        ]
+    );
+    assert!(
+        replay_since_pages(&fetcher, &["yesterday".to_string()], Some)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn polite_delay_between_fetches() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let arrivals = Arc::new(Mutex::new(Vec::new()));
+    let server_arrivals = arrivals.clone();
+    let server = MockServer::start(move |req| {
+        server_arrivals.lock().unwrap().push(Instant::now());
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).render())
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        min_delay_between_requests: Some(Duration::from_millis(200)),
+        ..FetchConfig::default()
+    });
+
+    let start = Instant::now();
+    fetcher
+        .get_document(&fetcher.message_url("a"))
+        .await
+        .unwrap();
+    fetcher
+        .get_document(&fetcher.message_url("b"))
+        .await
+        .unwrap();
+
+    let arrivals = arrivals.lock().unwrap();
+    assert_eq!(arrivals.len(), 2);
+    // nothing to wait for before the first fetch
+    assert!(arrivals[0] - start < Duration::from_millis(200));
+    assert!(arrivals[1] - arrivals[0] >= Duration::from_millis(200));
+}
+
+#[tokio::test]
+async fn slow_fetch_holds_back_no_other() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        if id == "slow" {
+            std::thread::sleep(Duration::from_secs(2));
+        }
+        MockResponse::html(MessagePage::new(id).render())
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        min_delay_between_requests: Some(Duration::from_millis(100)),
+        ..FetchConfig::default()
+    });
+
+    let start = Instant::now();
+    let slow = {
+        let fetcher = fetcher.clone();
+        tokio::spawn(async move {
+            fetcher
+                .get_document(&fetcher.message_url("slow"))
+                .await
+                .is_ok()
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(20)).await;
+    fetcher
+        .get_document(&fetcher.message_url("quick"))
+        .await
+        .unwrap();
+    let quick = start.elapsed();
+    assert!(slow.await.unwrap());
+
+    // spaced from the start of the slow fetch, not from its end
+    assert!(quick >= Duration::from_millis(100));
+    assert!(quick < Duration::from_secs(1), "{quick:?}");
+}
+
+#[tokio::test]
+async fn message_without_content_div() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let mut page = MessagePage::new("notice");
+        page.content = None;
+        match req.path.as_str() {
+            "/message-id/raw/notice" => MockResponse::new(
+                200,
+                "text/plain",
+                "From: moderator\r\nSubject: notice\r\n\r\nThread moved to <pgsql-general>.\r\n",
+            ),
+            "/message-id/raw/lost" => MockResponse::not_found(),
+            _ => MockResponse::html(page.render()),
+        }
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let detail = get_thread_by_id(&fetcher, &MessageId("notice".to_string()))
+        .await
+        .unwrap();
+    assert!(!detail.content_missing);
+    assert_eq!(
+        detail.content,
+        "<pre>Thread moved to &lt;pgsql-general&gt;.</pre>"
+    );
+    let raw = server.requests().pop().unwrap();
+    assert_eq!(raw.path, "/message-id/raw/notice");
+    // archives:antispam
+    assert_eq!(
+        raw.header("authorization"),
+        Some("Basic YXJjaGl2ZXM6YW50aXNwYW0=")
+    );
+
+    let detail = get_thread_by_id(&fetcher, &MessageId("lost".to_string()))
+        .await
+        .unwrap();
+    assert!(detail.content_missing);
+    assert_eq!(detail.content, "");
+    assert_eq!(detail.subject, "Subject of notice");
+}
+
+#[tokio::test]
+async fn traverse_newest_first() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/before/") {
+            "202501190000" => &[
+                ("b", "Second topic", "Bob", "12:30"),
+                ("c", "Third topic", "Carol", "15:45"),
+                ("d", "Fourth topic", "Dave", "23:59"),
+            ],
+            "202501181231" => &[
+                ("z", "Older topic", "Zoe", "08:10"),
+                ("a", "First topic", "Alice", "09:00"),
+                ("b", "Second topic", "Bob", "12:30"),
+            ],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let mut handled = Vec::new();
+    let threads = get_threads_between_newest_first(
+        &fetcher,
+        day.and_hms_opt(9, 0, 0).unwrap(),
+        day.and_hms_opt(23, 59, 59).unwrap(),
+        |thread| {
+            handled.push(thread.id.clone());
+            Ok(Some(thread))
+        },
+    )
+    .await
+    .unwrap();
+
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["d", "c", "b", "a"]);
+    assert_eq!(handled, ids);
+    assert_eq!(
+        server.paths(),
+        [
+            "/list/pgsql-hackers/before/202501190000",
+            "/list/pgsql-hackers/before/202501181231"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn latest_new_threads_stops_at_limit() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a", "First topic", "Alice", "09:00"),
+                ("b", "Second topic", "Bob", "12:30"),
+                ("c", "Re: First topic", "Carol", "15:45"),
+                ("d", "Third topic", "Dave", "18:00"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let threads = latest_new_threads(&fetcher, 2).await.unwrap();
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["d", "b"]);
+    assert_eq!(server.requests().len(), 1);
+}
+
+#[tokio::test]
+async fn entities_are_decoded_in_text_fields() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path.starts_with("/list/") {
+            return MockResponse::html(listing_page(&[(
+                "Jan. 18, 2025",
+                &[("a", "Fix &amp;amp; in pg&#x2F;dump", "O&#39;Brien", "09:00")],
+            )]));
+        }
+        let mut page = MessagePage::new("a");
+        // escaped once more by render()
+        page.subject = "Fix &amp; in pg&#x2F;dump".to_string();
+        page.from = "O&#39;Brien <ob(at)example(dot)org>".to_string();
+        page.attachments = vec![(
+            "a&amp;b.patch".to_string(),
+            "/x".to_string(),
+            "1 KB".to_string(),
+        )];
+        MockResponse::html(page.render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let mut threads = Vec::new();
+    for_each_thread(&fetcher, &fetcher.since_url(NaiveDateTime::MIN), |thread| {
+        threads.push(thread);
+        true
+    })
+    .await
+    .unwrap();
+    assert_eq!(threads[0].subject, "Fix & in pg/dump");
+    assert_eq!(threads[0].author, "O'Brien");
+
+    let detail = get_thread_by_id(&fetcher, &MessageId("a".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(detail.subject, "Fix & in pg/dump");
+    assert_eq!(detail.author_name, "O'Brien");
+    assert_eq!(detail.attachments[0].name, "a&b.patch");
+}
+
+#[tokio::test]
+async fn thread_reply_graph_as_dot() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    // (id, author, in-reply-to)
+    const MESSAGES: [(&str, &str, &str); 5] = [
+        ("s@x", "Alice", ""),
+        ("r1@x", "Bob", "<s@x>"),
+        ("r2@x", "Carol", "<r1@x>"),
+        ("r3@x", "Dave", "<s@x>"),
+        ("r4@x", "Eve", "<elsewhere@y>"),
+    ];
+    let server = MockServer::start(|req| {
+        let ids: Vec<_> = MESSAGES.iter().map(|(id, ..)| *id).collect();
+        if let Some(id) = req.path.strip_prefix("/message-id/raw/") {
+            let Some((_, author, parent)) = MESSAGES.iter().find(|(m, ..)| *m == id) else {
+                return MockResponse::not_found();
+            };
+            let raw = format!(
+                "From: {author} <{author}@example.org>\r\nDate: Wed, 22 Jan 2025 13:59:09 +0000\r\n\
+                 Message-ID: <{id}>\r\nIn-Reply-To: {parent}\r\n\r\nbody\r\n"
+            );
+            return MockResponse::new(200, "text/plain", raw);
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).thread(&ids).render())
+    });
+
+    let dot = thread_to_dot(
+        &Fetcher::new(&server.url()),
+        &MessageId::parse("s@x").unwrap(),
+        None,
+    )
+    .await
+    .unwrap();
+    assert!(dot.starts_with("digraph thread {"));
+    assert_eq!(dot.matches("[label=").count(), 5);
+    assert!(dot.contains("\"s@x\" [label=\"Alice\\n2025-01-22 13:59\"];"));
+    assert!(dot.contains("\"s@x\" -> \"r1@x\";"));
+    assert!(dot.contains("\"r1@x\" -> \"r2@x\";"));
+    assert!(dot.contains("\"s@x\" -> \"r3@x\";"));
+    assert!(dot.contains("\"s@x\" -> \"r4@x\" [style=dashed];"));
+    assert_eq!(dot.matches("->").count(), 4);
+}
+
+#[tokio::test]
+async fn new_subjects_by_thread_start() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            // "renamed" replies to a thread started last year under a new subject
+            let thread: &[&str] = match id {
+                "renamed" => &["old-starter", "renamed"],
+                _ => &[id],
+            };
+            return MockResponse::html(MessagePage::new(id).thread(thread).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("fresh", "Fresh topic", "Alice", "09:00"),
+                ("renamed", "Old topic, narrowed down", "Bob", "12:30"),
+                ("reply", "Re: Fresh topic", "Carol", "15:45"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let new_subjects = async |range_by: RangeBy| {
+        let threads = get_new_subjects_between_by(
+            &fetcher,
+            day.into(),
+            day.and_hms_opt(23, 59, 59).unwrap(),
+            range_by,
+        )
+        .await
+        .unwrap();
+        threads
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(new_subjects(RangeBy::ThreadStart).await, ["fresh"]);
+    assert_eq!(new_subjects(RangeBy::Message).await, ["fresh", "renamed"]);
+}
+
+#[tokio::test]
+async fn detail_carries_raw_headers() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path == "/message-id/raw/known" {
+            return MockResponse::new(
+                200,
+                "text/plain",
+                "Received: from a\r\nReceived: from b\r\nFrom: Jane Doe <jane@example.org>\r\n\
+                 To: pgsql-hackers@postgresql.org\r\nCc: joe@example.org,\r\n \
+                 ann@example.org\r\nList-Id: <pgsql-hackers.lists.postgresql.org>\r\n\
+                 User-Agent: Mutt/2.2\r\nContent-Type: text/plain; charset=utf-8\r\n\r\nHi\r\n",
+            );
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).render())
+    });
+
+    let detail = get_thread_by_id(
+        &Fetcher::new(&server.url()),
+        &MessageId("known".to_string()),
+    )
+    .await
+    .unwrap();
+    let keys: Vec<_> = detail.headers.keys().map(String::as_str).collect();
+    assert_eq!(
+        keys,
+        [
+            "Cc",
+            "Content-Type",
+            "From",
+            "List-Id",
+            "Received",
+            "To",
+            "User-Agent"
+        ]
+    );
+    assert_eq!(detail.headers["Cc"], "joe@example.org, ann@example.org");
+    assert_eq!(detail.headers["User-Agent"], "Mutt/2.2");
+    assert_eq!(detail.headers["Received"], "from a\nfrom b");
+}
+
+#[tokio::test]
+async fn flat_view_has_the_whole_thread() {
+    use mock_server::{flat_page, message_not_found_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path != "/message-id/flat/s%40x" {
+            return MockResponse::new(404, "text/html", message_not_found_page());
+        }
+        let starter = MessagePage::new("s%40x");
+        let mut reply = MessagePage::new("r%40x");
+        reply.from = "Bob <bob(at)example(dot)org>".to_string();
+        reply.date = "2025-01-22 15:00:00".to_string();
+        reply.content = Some("<p>See commit 1234567abc</p>".to_string());
+        reply.attachments = vec![(
+            "v2.patch".to_string(),
+            "/message-id/attachment/7/v2.patch".to_string(),
+            "1.5 kB".to_string(),
+        )];
+        let mut notice = MessagePage::new("n%40x");
+        notice.content = None;
+        MockResponse::html(flat_page(&[
+            ("s@x", &starter),
+            ("r@x", &reply),
+            ("n@x", &notice),
+        ]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let thread = get_thread_flat(&fetcher, &MessageId::parse("s@x").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(server.paths().len(), 1);
+    let ids: Vec<_> = thread.iter().map(|detail| detail.id.as_str()).collect();
+    assert_eq!(ids, ["s%40x", "r%40x", "n%40x"]);
+    assert!(thread.iter().all(|detail| detail.replies == ids));
+    assert_eq!(thread[0].subject, "Subject of s%40x");
+    assert_eq!(thread[0].author_email, "jane.doe@example.org");
+    assert!(thread[0].attachments.is_empty());
+    let reply = &thread[1];
+    assert_eq!(reply.author_name, "Bob");
+    assert_eq!(reply.datetime.hour(), 15);
+    assert_eq!(reply.references.commits, ["1234567abc"]);
+    assert_eq!(reply.attachments.len(), 1);
+    assert_eq!(reply.attachments[0].name, "v2.patch");
+    assert!(reply.headers.is_empty());
+    assert!(thread[2].content_missing);
+
+    let err = get_thread_flat(&fetcher, &MessageId::parse("gone@x").unwrap())
+        .await
+        .unwrap_err();
+    assert!(is_not_found(&err));
+}
+
+#[tokio::test]
+async fn raw_message_is_decoded() {
+    use mock_server::{MockResponse, MockServer};
+
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/message-id/raw/r%40x" => MockResponse::new(
+            200,
+            "text/plain",
+            "From: =?ISO-8859-1?Q?J=F6rg_M=FCller?= <joerg@example.org>\r\n\
+             Subject: =?UTF-8?Q?Re=3A_pg=5Fdump_crash?=\r\n\
+             Date: Wed, 22 Jan 2025 13:59:09 +0100\r\nMessage-ID: <r@x>\r\n\
+             In-Reply-To: <q@x>\r\nReferences: <s@x>\r\n <q@x>\r\nMIME-Version: 1.0\r\n\
+             Content-Type: text/plain; charset=iso-8859-1\r\n\
+             Content-Transfer-Encoding: quoted-printable\r\n\r\n\
+             Gr=FC=DFe, the fix looks r=\r\night.\r\n",
+        ),
+        _ => MockResponse::not_found(),
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let message = get_raw_message(&fetcher, &MessageId::parse("r@x").unwrap())
+        .await
+        .unwrap();
+    assert_eq!(message.message_id.as_deref(), Some("r@x"));
+    assert_eq!(message.in_reply_to.as_deref(), Some("q@x"));
+    assert_eq!(message.references, ["s@x", "q@x"]);
+    assert_eq!(message.subject, "Re: pg_dump crash");
+    assert_eq!(message.author_name, "Jörg Müller");
+    assert_eq!(message.author_email, "joerg@example.org");
+    let date = message.date.unwrap();
+    assert_eq!(date.offset().local_minus_utc(), 3600);
+    assert_eq!(
+        date.naive_utc(),
+        NaiveDate::from_ymd_opt(2025, 1, 22)
+            .unwrap()
+            .and_hms_opt(12, 59, 9)
+            .unwrap()
+    );
+    assert_eq!(message.text.trim_end(), "Grüße, the fix looks right.");
+    assert_eq!(message.headers["Message-ID"], "<r@x>");
+
+    let err = get_raw_message(&fetcher, &MessageId::parse("gone@x").unwrap())
+        .await
+        .unwrap_err();
+    assert!(is_not_found(&err));
+}
+
+#[tokio::test]
+async fn request_budget_trips_mid_traversal() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    // every page moves on by one thread, so the traversal would go on for a while
+    let server = MockServer::start(|req| {
+        let token = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let minute: u32 = token[10..].parse().unwrap();
+        let ids = [format!("m{minute}"), format!("m{}", minute + 1)];
+        let times = [format!("09:{minute:02}"), format!("09:{:02}", minute + 1)];
+        let rows = [
+            (ids[0].as_str(), "Topic", "Alice", times[0].as_str()),
+            (ids[1].as_str(), "Topic", "Bob", times[1].as_str()),
+        ];
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", &rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        max_total_requests: Some(3),
+        ..FetchConfig::default()
+    });
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let err = get_threads_between(
+        &fetcher,
+        day.and_hms_opt(9, 0, 0).unwrap(),
+        day.and_hms_opt(9, 30, 0).unwrap(),
+        |thread| Ok(Some(thread)),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::BudgetExceeded {
+            resource: "requests",
+            limit: 3,
+            ..
+        })
+    ));
+    assert_eq!(server.requests().len(), 3);
+
+    // a new budget allows new requests
+    let fetcher = fetcher.with_new_budget();
+    assert!(fetcher
+        .get_document(&fetcher.since_url(day.into()))
+        .await
+        .is_ok());
+}
+
+#[tokio::test]
+async fn reply_counts_match_thread_sizes() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let thread: &[&str] = match id {
+            "busy" => &["busy", "r1", "r2", "r3"],
+            "quiet" => &["quiet"],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(MessagePage::new(id).thread(thread).render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let thread = |id: &str| EmailThread {
+        id: id.to_string(),
+        list: MailingList::default(),
+        subject: format!("Topic {id}"),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+
+    let mut threads = vec![thread("busy"), thread("quiet"), thread("gone")];
+    add_reply_counts(&fetcher, &mut threads).await;
+
+    for thread in &threads[..2] {
+        let ids = thread_message_ids(&fetcher, &thread.id).await.unwrap();
+        assert_eq!(thread.reply_count, Some(ids.len() - 1));
+    }
+    assert_eq!(threads[0].reply_count, Some(3));
+    assert_eq!(threads[2].reply_count, None);
+}
+
+#[tokio::test]
+async fn traversal_handles_rows_out_of_time_order() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180900" => &[
+                ("b", "Topic b", "Bob", "09:20"),
+                ("a", "Topic a", "Alice", "09:05"),
+                ("c", "Topic c", "Carol", "09:40"),
+                ("d", "Topic d", "Dave", "09:10"),
+            ],
+            "202501180940" => &[
+                ("e", "Topic e", "Eve", "10:00"),
+                ("c", "Topic c", "Carol", "09:40"),
+                ("f", "Topic f", "Frank", "09:50"),
+            ],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let threads = get_threads_between(
+        &fetcher,
+        day.and_hms_opt(9, 0, 0).unwrap(),
+        day.and_hms_opt(9, 55, 0).unwrap(),
+        |thread| Ok(Some(thread)),
+    )
+    .await
+    .unwrap();
+
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["b", "a", "c", "d", "f"]);
+    assert_eq!(
+        server.paths(),
+        [
+            "/list/pgsql-hackers/since/202501180900",
+            "/list/pgsql-hackers/since/202501180940"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn merge_new_subjects_of_several_lists() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        // the whole day fits on one page, whatever page is asked for
+        let list = req.path.split('/').nth(2).unwrap_or("");
+        let rows: &[_] = match list {
+            "pgsql-hackers" => &[
+                ("h1", "Hackers topic 1", "Alice", "09:00"),
+                ("h2", "Hackers topic 2", "Bob", "15:00"),
+            ],
+            "pgsql-bugs" => &[
+                ("b1", "BUG #1: crash", "Carol", "08:00"),
+                ("b2", "BUG #2: hang", "Dave", "12:00"),
+            ],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let threads = new_subjects_multi(
+        &Fetcher::new(&server.url()),
+        &[MailingList::Hackers, MailingList::Bugs],
+        day.into(),
+        day.and_hms_opt(23, 59, 59).unwrap(),
+    )
+    .await
+    .unwrap();
+
+    let merged: Vec<_> = threads
+        .iter()
+        .map(|thread| (thread.id.as_str(), thread.list.name()))
+        .collect();
+    assert_eq!(
+        merged,
+        [
+            ("b1", "pgsql-bugs"),
+            ("h1", "pgsql-hackers"),
+            ("b2", "pgsql-bugs"),
+            ("h2", "pgsql-hackers"),
+        ]
+    );
+    assert!("pgsql-general".parse::<MailingList>().is_ok());
+    assert!("pgsql-nope".parse::<MailingList>().is_err());
+}
+
+#[tokio::test]
+async fn unparsable_day_header_is_reported() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        MockResponse::html(listing_page(&[
+            ("Sept. 17, 2025", &[("a", "Topic a", "Alice", "09:00")]),
+            ("Spt. 18, 2025", &[("b", "Topic b", "Bob", "10:00")]),
+            ("September 19, 2025", &[("c", "Topic c", "Carol", "11:00")]),
+            ("sep 20, 2025", &[("d", "Topic d", "Dave", "12:00")]),
+        ]))
+    });
+    let fetcher = Fetcher::new(&server.url()).record_warnings();
+
+    let mut threads = Vec::new();
+    for_each_thread(&fetcher, &fetcher.since_url(NaiveDateTime::MIN), |thread| {
+        threads.push((thread.id, thread.datetime.date().to_string()));
+        true
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        threads,
+        [
+            ("a".to_string(), "2025-09-17".to_string()),
+            ("c".to_string(), "2025-09-19".to_string()),
+            ("d".to_string(), "2025-09-20".to_string()),
+        ]
+    );
+    let warnings = fetcher.warnings();
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("unknown month 'Spt.' in date header 'Spt. 18, 2025'"));
+    assert!(transform_date("Nov 31, 2025").is_err());
+}
+
+#[tokio::test]
+async fn compact_detail_skips_content_and_attachments() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let mut page = MessagePage::new(id).thread(&["s@x", "r@x"]);
+        page.attachments = vec![("v1.patch".to_string(), "/x".to_string(), "1 KB".to_string())];
+        MockResponse::html(page.render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let id = MessageId::parse("s@x").unwrap();
+
+    let full = get_thread_by_id(&fetcher, &id).await.unwrap();
+    let compact = get_thread_detail(&fetcher, &id, ThreadDetailOptions::COMPACT)
+        .await
+        .unwrap();
+
+    assert!(!full.content.is_empty());
+    assert_eq!(full.attachments.len(), 1);
+    assert_eq!(compact.content, "");
+    assert!(!compact.content_missing);
+    assert!(compact.attachments.is_empty());
+    assert!(compact.headers.is_empty());
+    assert_eq!(compact.subject, full.subject);
+    assert_eq!(compact.author_name, full.author_name);
+    assert_eq!(compact.datetime, full.datetime);
+    assert_eq!(compact.replies, ["s@x", "r@x"]);
+    // the page of the full detail and its raw view, then the page alone
+    assert_eq!(
+        server.paths(),
+        [
+            "/message-id/s%40x",
+            "/message-id/raw/s%40x",
+            "/message-id/s%40x"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn prefetching_overlaps_fetching_with_processing() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    const DELAY: Duration = Duration::from_millis(150);
+    // one thread a page, each page starting at the thread of the previous one
+    let server = MockServer::start(|req| {
+        std::thread::sleep(DELAY);
+        let token = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let hour: u32 = token[8..10].parse().unwrap();
+        let threads = [hour, hour + 1].map(|hour| (format!("h{hour}"), format!("{hour:02}:00")));
+        let rows: Vec<_> = threads
+            .iter()
+            .map(|(id, time)| (id.as_str(), "Topic", "Alice", time.as_str()))
+            .collect();
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", &rows)]))
+    });
+    async fn run(url: &str, prefetch_pages: usize) -> (Vec<String>, Duration) {
+        let fetcher = Fetcher::new(url).with_config(FetchConfig {
+            prefetch_pages,
+            ..FetchConfig::default()
+        });
+        let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+        let started = Instant::now();
+        let mut pages = SincePages::new(
+            &fetcher,
+            day.and_hms_opt(1, 0, 0).unwrap(),
+            day.and_hms_opt(5, 0, 0).unwrap(),
+        );
+        let mut ids = Vec::new();
+        while let Some(page) = pages.next().await.unwrap() {
+            for thread in page {
+                // a starter check or the like
+                tokio::time::sleep(DELAY).await;
+                ids.push(thread.id);
+            }
+        }
+        (ids, started.elapsed())
+    }
+
+    let (sequential_ids, sequential) = run(&server.url(), 0).await;
+    let (prefetched_ids, prefetched) = run(&server.url(), 2).await;
+    assert_eq!(sequential_ids, ["h1", "h2", "h3", "h4", "h5"]);
+    assert_eq!(prefetched_ids, sequential_ids);
+    // five pages and five threads cost ten delays one after the other, prefetching hides most
+    // of the page fetches behind the processing
+    assert!(
+        prefetched + 2 * DELAY < sequential,
+        "prefetched in {prefetched:?}, sequentially in {sequential:?}"
+    );
+}
+
+#[tokio::test]
+async fn unknown_message_is_a_typed_not_found() {
+    use mock_server::{message_not_found_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/message-id/gone%40x" => MockResponse::html(message_not_found_page()),
+        _ => MockResponse::new(404, "text/html", message_not_found_page()),
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let err = get_thread_by_id(&fetcher, &MessageId::parse("gone@x").unwrap())
+        .await
+        .unwrap_err();
+    assert!(is_not_found(&err));
+    assert!(matches!(
+        err.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::NotFound(id)) if id == "gone%40x"
+    ));
+    let err = get_thread_starter_id(&fetcher, "never%40x")
+        .await
+        .unwrap_err();
+    assert!(is_not_found(&err));
+    assert_eq!(
+        err.root_cause().to_string(),
+        "no message never%40x in the archive"
+    );
+    // a listed message that is gone since is no starter, not the end of the run
+    assert!(!is_thread_starter_by_id(&fetcher, "gone%40x").await.unwrap());
+}
+
+#[tokio::test]
+async fn thread_tree_stops_at_max_messages() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let ids: Vec<String> = (0..120).map(|n| format!("m{n}@x")).collect();
+    let server = MockServer::start(move |req| {
+        if let Some(slug) = req.path.strip_prefix("/message-id/raw/") {
+            let n: usize = slug[1..].split('@').next().unwrap().parse().unwrap();
+            let parent = if n == 0 {
+                String::new()
+            } else {
+                format!("<m{}@x>", n - 1)
+            };
+            let raw = format!("From: Alice <a@x>\r\nIn-Reply-To: {parent}\r\n\r\nbody\r\n");
+            return MockResponse::new(200, "text/plain", raw);
+        }
+        let ids: Vec<_> = ids.iter().map(String::as_str).collect();
+        MockResponse::html(MessagePage::new("m0@x").thread(&ids).render())
+    });
+
+    let fetcher = Fetcher::new(&server.url());
+    let tree = build_thread_tree(&fetcher, "m0%40x", Some(25))
+        .await
+        .unwrap();
+    assert!(tree.truncated);
+    assert_eq!(tree.total, 120);
+    assert_eq!(tree.nodes.len(), 25);
+    assert_eq!(tree.nodes[24].parent.as_deref(), Some("m23@x"));
+    // the thread page and one raw view per message kept
+    assert_eq!(server.requests().len(), 1 + 25);
+
+    let tree = build_thread_tree(&fetcher, "m0%40x", Some(120))
+        .await
+        .unwrap();
+    assert!(!tree.truncated);
+    assert_eq!(tree.nodes.len(), 120);
+}
+
+#[test]
+fn content_stats_count_prose_only() {
+    let stats = content_stats("<p>Hello hackers,<br>here is a patch.</p>");
+    assert_eq!(
+        stats,
+        ContentStats {
+            words: 6,
+            chars: 26,
+            est_read_secs: 2,
+        }
+    );
+
+    // quotes, indented code, fences and diffs are skipped
+    let content = "<p>On Mon, Alice wrote:<br>\
+                   &gt; why not use a hash table?<br>\
+                   &gt; it would be faster<br>\
+                   <br>\
+                   Because of this loop:<br>\
+                   \u{20}   for (i = 0; i &lt; n; i++)<br>\
+                   \tfoo(i);<br>\
+                   ```<br>\
+                   SELECT 1;<br>\
+                   ```<br>\
+                   diff --git a/x.c b/x.c<br>\
+                   +int x;<br>\
+                   <br>\
+                   Thoughts?</p>\
+                   <blockquote>quoted in a block</blockquote>";
+    let stats = content_stats(content);
+    assert_eq!(stats.words, 9);
+
+    let long = format!("<p>{}</p>", "word ".repeat(WORDS_PER_MINUTE * 3));
+    assert_eq!(content_stats(&long).est_read_secs, 180);
+    assert_eq!(content_stats(""), ContentStats::default());
+}
+
+#[test]
+fn new_text_leaves_quotes_out() {
+    let content = "<p>Hi,</p>\
+                   <p>On Mon, 20 Jan 2025 at 10:00, Alice &lt;alice@example.org&gt; wrote:<br>\
+                   &gt; why not use a hash table?<br>\
+                   &gt;&gt; it would be faster<br>\
+                   <br>\
+                   Because of the ordering.<br>\
+                   <br>\
+                   Am 21.01.2025 um 08:00 schrieb Bob:</p>\
+                   <blockquote>the patch<br>looks fine</blockquote>\
+                   <p>Agreed, v2 attached.</p>";
+    assert_eq!(
+        content_new_text(content),
+        "Hi,\n\nBecause of the ordering.\n\nAgreed, v2 attached."
+    );
+    assert_eq!(content_new_text("<pre>&gt; only a quote</pre>"), "");
+
+    // the author's own lines that merely look like one stay
+    let content = "<p>Here is what I rewrote:<br>\
+                   the loop, twice.<br>\
+                   The function now writes:<br>\
+                   \tSELECT 1;<br>\
+                   Someone wrote:</p>\
+                   <blockquote>no</blockquote>";
+    assert_eq!(
+        content_new_text(content),
+        "Here is what I rewrote:\nthe loop, twice.\nThe function now writes:\n\tSELECT 1;"
+    );
+}
+
+#[tokio::test]
+async fn configured_headers_and_cookies_are_sent() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        MockResponse::html(MessagePage::new("a").render())
+            .with_header("Set-Cookie", "session=s3cret; Path=/")
+    });
+    let mut config = FetchConfig {
+        cookie_store: true,
+        ..FetchConfig::default()
+    };
+    config.extra_headers.insert(
+        reqwest::header::HeaderName::from_static("x-archive-token"),
+        reqwest::header::HeaderValue::from_static("t0ken"),
+    );
+    let fetcher = Fetcher::new(&server.url()).with_config(config);
+
+    fetcher
+        .get_document(&fetcher.message_url("a"))
+        .await
+        .unwrap();
+    fetcher
+        .get_document(&fetcher.message_url("b"))
+        .await
+        .unwrap();
+    let requests = server.requests();
+    assert!(requests
+        .iter()
+        .all(|req| req.header("x-archive-token") == Some("t0ken")));
+    assert_eq!(requests[0].header("cookie"), None);
+    assert_eq!(requests[1].header("cookie"), Some("session=s3cret"));
+
+    // nothing extra by default
+    let fetcher = Fetcher::new(&server.url());
+    fetcher
+        .get_document(&fetcher.message_url("a"))
+        .await
+        .unwrap();
+    fetcher
+        .get_document(&fetcher.message_url("b"))
+        .await
+        .unwrap();
+    let last = server.requests().pop().unwrap();
+    assert_eq!(last.header("x-archive-token"), None);
+    assert_eq!(last.header("cookie"), None);
+}
+
+#[tokio::test]
+async fn broken_header_table_is_reported_with_its_markup() {
+    use mock_server::{MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        MockResponse::html(
+            "<html><body><div id=\"pgContentWrap\"><table class=\"message-header\">\
+             <tr><th>Sender:</th><td>Jane Doe</td></tr>\
+             <tr><th>Subject:</th><td>Renamed rows</td></tr>\
+             <tr><th>Thread:</th><td><select id=\"thread_select\">\
+             <option value=\"a@x\">a@x</option></select></td></tr>\
+             </table></div></body></html>",
+        )
+    });
+
+    let err = get_thread_by_id(
+        &Fetcher::new(&server.url()),
+        &MessageId::parse("a@x").unwrap(),
+    )
+    .await
+    .unwrap_err();
+    let Some(ScrapeError::UnexpectedLayout {
+        id,
+        rows,
+        html_snippet,
+    }) = err.downcast_ref::<ScrapeError>()
+    else {
+        panic!("unexpected error: {err:#}");
+    };
+    assert_eq!(id, "a%40x");
+    assert_eq!(*rows, 3);
+    assert!(html_snippet.contains("<th>Sender:</th>"));
+    assert!(err.to_string().contains("<td>Renamed rows</td>"));
+
+    let long = format!("<table>{}</table>", "<tr></tr>".repeat(200));
+    let ScrapeError::UnexpectedLayout { html_snippet, .. } =
+        ScrapeError::unexpected_layout("a", 200, &long)
+    else {
+        unreachable!()
+    };
+    assert_eq!(html_snippet.chars().count(), LAYOUT_SNIPPET_CHARS + 1);
+}
+
+#[tokio::test]
+async fn warm_starter_cache_skips_starter_pages() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            let thread: &[&str] = match id {
+                "reply" => &["fresh", "reply"],
+                _ => &[id],
+            };
+            return MockResponse::html(MessagePage::new(id).thread(thread).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("fresh", "Fresh topic", "Alice", "09:00"),
+                ("reply", "Fresh topic, part two", "Bob", "12:30"),
+            ],
+        )]))
+    });
+    let path = std::env::temp_dir().join(format!("pgdevhub-starters-{}.tsv", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let new_subjects = async || {
+        // a fresh fetcher each time, like a restarted process
+        let fetcher = Fetcher::new(&server.url())
+            .with_starter_cache(&path)
+            .unwrap();
+        get_new_subjects_between(&fetcher, day.into(), day.and_hms_opt(23, 59, 59).unwrap())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect::<Vec<_>>()
+    };
+    let message_pages = || {
+        server
+            .paths()
+            .iter()
+            .filter(|path| path.starts_with("/message-id/"))
+            .count()
+    };
+
+    assert_eq!(new_subjects().await, ["fresh"]);
+    assert_eq!(message_pages(), 2);
+    assert_eq!(new_subjects().await, ["fresh"]);
+    assert_eq!(message_pages(), 2);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn parse_archive_byte_sizes() {
+    assert_eq!(parse_byte_size("0 bytes"), Some(0));
+    assert_eq!(parse_byte_size("1 byte"), Some(1));
+    assert_eq!(parse_byte_size("1,023 bytes"), Some(1023));
+    assert_eq!(parse_byte_size("12 kB"), Some(12 * 1024));
+    assert_eq!(parse_byte_size("12KB"), Some(12 * 1024));
+    assert_eq!(parse_byte_size("3.4\u{a0}MB"), Some(3_565_158));
+    assert_eq!(parse_byte_size(" 2 MiB "), Some(2 * 1024 * 1024));
+    assert_eq!(parse_byte_size("1.0 GB"), Some(1 << 30));
+    assert_eq!(parse_byte_size("512"), Some(512));
+    assert_eq!(parse_byte_size(""), None);
+    assert_eq!(parse_byte_size("MB"), None);
+    assert_eq!(parse_byte_size("12 parsecs"), None);
+}
+
+#[tokio::test]
+async fn attachment_sizes_add_up() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let mut page = MessagePage::new(id);
+        page.attachments = [
+            ("v2-0001-Refactor.patch", "12.5 KB"),
+            ("v2-0002-Add-tests.patch", "3.4\u{a0}MB"),
+            ("v2-0003-Docs.patch", "856 bytes"),
+            ("notes.txt", "?"),
+        ]
+        .iter()
+        .map(|(name, size)| {
+            (
+                name.to_string(),
+                format!("/message-id/attachment/1/{name}"),
+                size.to_string(),
+            )
+        })
+        .collect();
+        MockResponse::html(page.render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+
+    let detail = get_thread_by_id(&fetcher, &MessageId::parse("s@x").unwrap())
+        .await
+        .unwrap();
+    let sizes: Vec<_> = detail
+        .attachments
+        .iter()
+        .map(|attachment| attachment.size)
+        .collect();
+    assert_eq!(sizes, [Some(12_800), Some(3_565_158), Some(856), None]);
+    assert_eq!(detail.total_attachment_bytes(), 12_800 + 3_565_158 + 856);
+}
+
+#[test]
+fn default_ranges_end_at_the_clock() {
+    let at = |month, day, hour| {
+        NaiveDate::from_ymd_opt(2025, month, day)
+            .unwrap()
+            .and_hms_opt(hour, 30, 0)
+            .unwrap()
+    };
+    let fetcher = Fetcher::new("http://unused").with_clock(|| {
+        NaiveDate::from_ymd_opt(2025, 3, 1)
+            .unwrap()
+            .and_hms_opt(8, 30, 0)
+            .unwrap()
+    });
+
+    assert_eq!(fetcher.now(), at(3, 1, 8));
+    assert_eq!(
+        fetcher.last(TimeDelta::days(1)),
+        (at(2, 28, 8), at(3, 1, 8))
+    );
+    assert_eq!(fetcher.last(TimeDelta::days(7)).0, at(2, 22, 8));
+    assert_eq!(fetcher.last(TimeDelta::days(30)).0, at(1, 30, 8));
+    assert_eq!(fetcher.last(TimeDelta::hours(6)).0, at(3, 1, 2));
+}
+
+#[tokio::test]
+async fn reply_ids_match_thread_dropdown() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        let thread: &[&str] = match id {
+            "busy" => &["busy", "r1", "r2", "r3"],
+            "quiet" => &["quiet"],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(MessagePage::new(id).thread(thread).render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let thread = |id: &str| EmailThread {
+        id: id.to_string(),
+        list: MailingList::default(),
+        subject: format!("Topic {id}"),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+
+    let mut threads = vec![thread("busy"), thread("quiet"), thread("gone")];
+    add_replies(&fetcher, &mut threads).await;
+
+    let dropdown = thread_message_ids(&fetcher, "busy").await.unwrap();
+    assert_eq!(threads[0].replies.as_deref(), Some(&dropdown[1..]));
+    assert_eq!(threads[0].reply_count, Some(3));
+    assert_eq!(threads[1].replies, Some(Vec::new()));
+    assert_eq!(threads[2].replies, None);
+    assert!(threads[0]
+        .to_string()
+        .ends_with("\nReply: https://www.postgresql.org/message-id/r3"));
+}
+
+#[tokio::test]
+async fn row_without_time_is_listed_at_midnight() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Topic b", "", ""),
+                ("c", "Topic c", "Carol", "noon"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url()).record_warnings();
+
+    let mut threads = Vec::new();
+    for_each_thread(&fetcher, &fetcher.since_url(NaiveDateTime::MIN), |thread| {
+        threads.push((thread.id, thread.datetime.to_string()));
+        true
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(
+        threads,
+        [
+            ("a".to_string(), "2025-01-18 09:00:00".to_string()),
+            ("b".to_string(), "2025-01-18 00:00:00".to_string()),
+            ("c".to_string(), "2025-01-18 00:00:00".to_string()),
+        ]
+    );
+    let warnings = fetcher.warnings();
+    assert_eq!(warnings.len(), 2);
+    assert!(warnings[1].contains("no time in 'noon' for c on 2025-01-18"));
+}
+
+#[tokio::test]
+async fn attachments_of_a_range_are_flattened() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            let mut page = MessagePage::new(id);
+            let attachment = |number: u32, name: &str| {
+                (
+                    name.to_string(),
+                    format!("/message-id/attachment/{number}/{name}"),
+                    "2 kB".to_string(),
+                )
+            };
+            page.attachments = match id {
+                "v1%40x" => vec![
+                    attachment(1, "v1-0001-a.patch"),
+                    attachment(2, "v1-0002-b.patch"),
+                ],
+                // the first patch again, as forwarded by a reviewer
+                "review%40x" => vec![attachment(1, "v1-0001-a.patch")],
+                "v2%40x" => vec![attachment(3, "v1-0001-a.patch")],
+                "gone%40x" => return MockResponse::html(mock_server::message_not_found_page()),
+                _ => Vec::new(),
+            };
+            return MockResponse::html(page.render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("v1%40x", "Patch", "Alice", "09:00"),
+                ("question%40x", "Re: Patch", "Bob", "10:00"),
+                ("review%40x", "Re: Patch", "Carol", "11:00"),
+                ("gone%40x", "Re: Patch", "Dave", "11:30"),
+                ("v2%40x", "Re: Patch", "Alice", "12:00"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url()).record_warnings();
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let attachments =
+        attachments_between(&fetcher, day.into(), day.and_hms_opt(23, 59, 0).unwrap())
+            .await
+            .unwrap();
+    let listed: Vec<_> = attachments
+        .iter()
+        .map(|(id, attachment)| (id.to_string(), attachment.href.as_str()))
+        .collect();
+    assert_eq!(
+        listed,
+        [
+            (
+                "v1%40x".to_string(),
+                "/message-id/attachment/1/v1-0001-a.patch"
+            ),
+            (
+                "v1%40x".to_string(),
+                "/message-id/attachment/2/v1-0002-b.patch"
+            ),
+            (
+                "v2%40x".to_string(),
+                "/message-id/attachment/3/v1-0001-a.patch"
+            ),
+        ]
+    );
+    assert_eq!(attachments[0].1.size, Some(2048));
+    assert_eq!(fetcher.warnings().len(), 1);
+    assert_eq!(attachment_file_name(&attachments[2].1), "3-v1-0001-a.patch");
+    assert_eq!(
+        attachment_file_name(&ThreadAttachment {
+            name: "../../etc/passwd".to_string(),
+            href: "/message-id/attachment/4/passwd".to_string(),
+            size: None,
+        }),
+        "4-passwd"
+    );
+}
+
+#[test]
+fn from_headers_with_odd_layouts() {
+    let jane = ("Jane Doe".to_string(), "jane.doe@example.org".to_string());
+    assert_eq!(
+        parse_from_header("Jane Doe <jane(dot)doe(at)example(dot)org>"),
+        jane
+    );
+    assert_eq!(
+        parse_from_header("  Jane\n   Doe\n\t<jane(dot)doe(at)example(dot)org>\n"),
+        jane
+    );
+    assert_eq!(
+        parse_from_header("\"Jane Doe\" <jane(dot)doe(at)example(dot)org>"),
+        jane
+    );
+    assert_eq!(
+        parse_from_header("Bob <The Builder> Smith <bob(at)example(dot)org>"),
+        (
+            "Bob <The Builder> Smith".to_string(),
+            "bob@example.org".to_string()
+        )
+    );
+    assert_eq!(
+        parse_from_header("Carol"),
+        ("Carol".to_string(), String::new())
+    );
+    assert_eq!(
+        parse_from_header("a < b"),
+        ("a < b".to_string(), String::new())
+    );
+
+    let doc = Html::parse_fragment(
+        "<table><tr><td>Jane Doe<br>Example Corp<span> &lt;jane(dot)doe(at)example(dot)org&gt;\
+         </span></td></tr></table>",
+    );
+    let td = doc.select(&Selector::parse("td").unwrap()).next().unwrap();
+    assert_eq!(
+        from_cell_author(&td),
+        (
+            "Jane Doe Example Corp".to_string(),
+            "jane.doe@example.org".to_string()
+        )
+    );
+}
+
+#[tokio::test]
+async fn only_allowlisted_attachment_extensions_are_saved() {
+    use mock_server::{MockResponse, MockServer};
+
+    let server = MockServer::start(|req| MockResponse::new(200, "text/plain", req.path.clone()));
+    let fetcher = Fetcher::new(&server.url());
+    let attachments: Vec<_> = [
+        "v3-0001-Fix.patch",
+        "v3-0002-Tests.DIFF",
+        "v3-0003-Docs.patch.gz",
+        "screenshot.png",
+        "dump.sql",
+        "patch",
+    ]
+    .iter()
+    .enumerate()
+    .map(|(i, name)| ThreadAttachment {
+        name: name.to_string(),
+        href: format!("/message-id/attachment/{i}/{name}"),
+        size: None,
+    })
+    .collect();
+    let dir = std::env::temp_dir().join(format!("pgdevhub-attachments-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+
+    let extensions = ["patch".to_string(), ".diff".to_string()];
+    let saved = save_attachments(&fetcher, &attachments, &dir, &extensions)
+        .await
+        .unwrap();
+    let mut files: Vec<_> = std::fs::read_dir(&dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    files.sort();
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    assert_eq!(saved, 2);
+    assert_eq!(files, ["0-v3-0001-Fix.patch", "1-v3-0002-Tests.DIFF"]);
+    assert_eq!(server.paths().len(), 2);
+    assert!(attachments[2].has_extension(&PATCH_EXTENSIONS));
+}
+
+#[tokio::test]
+async fn listing_rows_that_go_uncounted_are_reported() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|_| {
+        let page = listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Topic b", "Bob", "10:00"),
+            ],
+        )]);
+        // a collapsed group and a row missing its time cell
+        MockResponse::html(page.replace(
+            "</table>",
+            "<tr><td colspan=\"3\"><a href=\"/list/pgsql-hackers/2025-01-18/\">\
+             12 more messages</a></td></tr>\
+             <tr><th><a href=\"/message-id/c\">Topic c</a></th><td>Carol</td></tr></table>",
+        ))
+    });
+    let fetcher = Fetcher::new(&server.url()).record_warnings();
+
+    let mut ids = Vec::new();
+    for_each_thread(&fetcher, &fetcher.since_url(NaiveDateTime::MIN), |thread| {
+        ids.push(thread.id);
+        true
+    })
+    .await
+    .unwrap();
+
+    assert_eq!(ids, ["a", "b"]);
+    assert_eq!(
+        fetcher.warnings(),
+        [
+            "the listing of 2025-01-18 links to '12 more messages' \
+             (/list/pgsql-hackers/2025-01-18/), threads behind it are missing",
+            "the listing of 2025-01-18 has 2 rows with a link that could not be read",
+        ]
+    );
+}
+
+#[tokio::test]
+async fn starters_of_a_busy_thread_are_learned_from_one_page() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let busy: Vec<String> = (0..10).map(|i| format!("busy{i}")).collect();
+    let thread_of = move |id: &str| -> Option<Vec<String>> {
+        match canonical_message_id(id).as_str() {
+            "quiet0" | "quiet1" => Some(vec!["quiet0".to_string(), "quiet1".to_string()]),
+            // the archive links these encoded
+            "enc@x" | "enc-reply@x" => {
+                Some(vec!["enc%40x".to_string(), "enc-reply%40x".to_string()])
+            }
+            id if id.starts_with("busy") => Some(busy.clone()),
+            _ => None,
+        }
+    };
+    let server = MockServer::start(move |req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            let thread = thread_of(id).unwrap();
+            let thread: Vec<_> = thread.iter().map(String::as_str).collect();
+            return MockResponse::html(MessagePage::new(id).thread(&thread).render());
+        }
+        let mut rows = vec![("quiet0", "Quiet topic", "Bob", "08:00")];
+        let busy_rows: Vec<_> = (0..10).map(|i| format!("busy{i}")).collect();
+        for id in &busy_rows {
+            rows.push((id.as_str(), "Busy topic", "Alice", "09:30"));
+        }
+        rows.push(("quiet1", "Re: Quiet topic", "Carol", "11:00"));
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", &rows)]))
+    });
+    let message_pages = || {
+        server
+            .paths()
+            .iter()
+            .filter(|path| path.starts_with("/message-id/"))
+            .count()
+    };
+    let fetcher = Fetcher::new(&server.url());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let end = day.and_hms_opt(23, 59, 0).unwrap();
+
+    let starters: Vec<_> = get_new_subjects_between(&fetcher, day.into(), end)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|thread| thread.id)
+        .collect();
+    assert_eq!(starters, ["quiet0", "busy0"]);
+    // one page per thread, instead of one per listed message
+    assert_eq!(message_pages(), 2);
+
+    // what the run learned agrees with looking each message up on its own, whichever way
+    // its slug is encoded
+    for id in [
+        "quiet0",
+        "quiet1",
+        "busy0",
+        "busy5",
+        "enc-reply%40x",
+        "enc@x",
+    ] {
+        let cached = is_thread_starter_by_id(&fetcher, id).await.unwrap();
+        let fresh = is_thread_starter_by_id(&Fetcher::new(&server.url()), id)
+            .await
+            .unwrap();
+        assert_eq!(cached, fresh, "{id}");
+    }
+    assert!(is_thread_starter_by_id(&fetcher, "enc@x").await.unwrap());
+    assert_eq!(message_pages(), 9);
+
+    // a new run starts without what the previous one learned
+    let fetcher = fetcher.with_new_budget();
+    assert!(!is_thread_starter_by_id(&fetcher, "busy3").await.unwrap());
+    assert_eq!(message_pages(), 10);
+}
+
+#[test]
+fn inline_patches_in_message_bodies() {
+    let content = "<p>Hi,<br>\n\
+        <br>\n\
+        I think this should be:<br>\n\
+        <br>\n\
+        --- a/src/backend/utils/adt/int.c<br>\n\
+        +++ b/src/backend/utils/adt/int.c<br>\n\
+        @@ -10,4 +10,4 @@ int4in(PG_FUNCTION_ARGS)<br>\n\
+        \u{a0}{<br>\n\
+        -\treturn x;<br>\n\
+        +\treturn y;<br>\n\
+        <br>\n\
+        \u{a0}}<br>\n\
+        <br>\n\
+        Thoughts?</p>\n\
+        <blockquote>--- a/quoted.c<br>\n+++ b/quoted.c<br>\n@@ -1 +1 @@<br>\n-a<br>\n+b</blockquote>";
+    let patches = extract_inline_patches(content);
+    assert_eq!(
+        patches,
+        ["--- a/src/backend/utils/adt/int.c\n\
+          +++ b/src/backend/utils/adt/int.c\n\
+          @@ -10,4 +10,4 @@ int4in(PG_FUNCTION_ARGS)\n\
+          \u{a0}{\n\
+          -\treturn x;\n\
+          +\treturn y;\n\
+          \n\
+          \u{a0}}"]
+    );
+
+    let content = "<p>--- a/b.c is where it goes wrong,<br>\n\
+        +++ fixes it, @@ marks nothing here.<br>\n\
+        <br>\n\
+        diff --git a/x b/x<br>\n\
+        index 1234567..89abcde 100644<br>\n\
+        --- a/x<br>\n\
+        +++ b/x<br>\n\
+        @@ -1 +1 @@<br>\n\
+        -old<br>\n\
+        +new<br>\n\
+        \\ No newline at end of file<br>\n\
+        diff --git a/y b/y<br>\n\
+        --- a/y<br>\n\
+        +++ b/y<br>\n\
+        @@ -0,0 +1,2 @@<br>\n\
+        +one<br>\n\
+        +two<br>\n\
+        Regards</p>";
+    let patches = extract_inline_patches(content);
+    assert_eq!(patches.len(), 1);
+    assert!(patches[0].starts_with("diff --git a/x b/x\n"));
+    assert!(patches[0].ends_with("+one\n+two"));
+
+    assert!(extract_inline_patches("<p>Hello hackers,<br>here is a patch.</p>").is_empty());
+}
+
+#[tokio::test]
+async fn multi_list_merge_respects_list_concurrency() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+    use std::sync::atomic::AtomicUsize;
+
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let (in_flight_, max_in_flight_) = (in_flight.clone(), max_in_flight.clone());
+    let server = MockServer::start(move |_req| {
+        // a list's pages are fetched one after the other, so listing pages in flight are lists
+        // in flight
+        let current = in_flight_.fetch_add(1, Ordering::SeqCst) + 1;
+        max_in_flight_.fetch_max(current, Ordering::SeqCst);
+        std::thread::sleep(Duration::from_millis(50));
+        in_flight_.fetch_sub(1, Ordering::SeqCst);
+        MockResponse::html(listing_page(&[]))
+    });
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let run = async |list_concurrency: usize| {
+        max_in_flight.store(0, Ordering::SeqCst);
+        let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+            list_concurrency,
+            ..FetchConfig::default()
+        });
+        new_subjects_multi(
+            &fetcher,
+            &MailingList::ALL,
+            day.into(),
+            day.and_hms_opt(23, 59, 59).unwrap(),
+        )
+        .await
+        .unwrap();
+        max_in_flight.load(Ordering::SeqCst)
+    };
+
+    assert_eq!(run(2).await, 2);
+    assert_eq!(run(1).await, 1);
+    assert_eq!(run(FetchConfig::default().list_concurrency).await, 3);
+}
+
+#[tokio::test]
+async fn thread_archived_as_mbox() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    // in thread order, which is not the order they were sent in
+    const MESSAGES: [(&str, &str, &str); 3] = [
+        ("s@x", "Alice", "Wed, 22 Jan 2025 13:59:09 +0000"),
+        ("r2@x", "Carol", "Thu, 23 Jan 2025 08:00:00 +0100"),
+        ("r1@x", "Bob", "Wed, 22 Jan 2025 15:00:00 -0800"),
+    ];
+    let server = MockServer::start(|req| {
+        let ids: Vec<_> = MESSAGES.iter().map(|(id, ..)| *id).collect();
+        if let Some(id) = req.path.strip_prefix("/message-id/raw/") {
+            let Some((_, author, date)) = MESSAGES.iter().find(|(m, ..)| *m == id) else {
+                return MockResponse::not_found();
+            };
+            let raw = format!(
+                "From: \"{author}\" <{}@example.org>\r\nDate: {date}\r\nMessage-ID: <{id}>\r\n\r\n\
+                 From the docs:\r\n>From here on\r\n\r\nRegards\r\n",
+                author.to_lowercase()
+            );
+            return MockResponse::new(200, "text/plain", raw);
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).thread(&ids).render())
+    });
+    let out = std::env::temp_dir().join(format!("pgdevhub-mbox-{}", std::process::id()));
+
+    archive_thread_mbox(&Fetcher::new(&server.url()), "s@x", &out)
+        .await
+        .unwrap();
+    let mbox = std::fs::read_to_string(&out).unwrap();
+    std::fs::remove_file(&out).unwrap();
+
+    let separators: Vec<_> = mbox
+        .lines()
+        .filter(|line| line.starts_with("From "))
+        .collect();
+    assert_eq!(
+        separators,
+        [
+            "From alice@example.org Wed Jan 22 13:59:09 2025",
+            "From bob@example.org Wed Jan 22 23:00:00 2025",
+            "From carol@example.org Thu Jan 23 07:00:00 2025",
+        ]
+    );
+    // one entry per message, each ending in a blank line before the next separator
+    assert_eq!(mbox.matches("\n\nFrom ").count(), 2);
+    assert!(mbox.ends_with("Regards\n\n"));
+    assert_eq!(
+        mbox.matches("\n>From the docs:\n>>From here on\n").count(),
+        3
+    );
+    assert!(!mbox.contains('\r'));
+}
+
+#[tokio::test]
+async fn threads_exported_with_synthesized_headers() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path.starts_with("/message-id/raw/") {
+            return MockResponse::not_found();
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        let mut page = MessagePage::new(id).thread(&["s@x", "r1@x"]);
+        if id == "r1%40x" {
+            page.subject = "Re: Résumé of the patch".to_string();
+            page.from = "Bob Smith, Jr. <bob(at)example(dot)org>".to_string();
+            page.date = "2025-01-22 15:00:00".to_string();
+            page.content = Some(
+                "<p>On Wed, Alice wrote:</p><blockquote>From the docs<br>\nsee <b>this</b>\
+                 <blockquote>older</blockquote></blockquote><p>Agreed.</p>"
+                    .to_string(),
+            );
+        }
+        MockResponse::html(page.render())
+    });
+    let store: Arc<dyn storage::Storage> = Arc::new(storage::Sqlite::open_in_memory().unwrap());
+    let fetcher = Fetcher::new(&server.url()).with_store(store);
+    // the starter is kept from an earlier run
+    let id = MessageId::parse("s@x").unwrap();
+    get_thread_by_id(&fetcher, &id).await.unwrap();
+    let fetched = server.paths().len();
+
+    let mut mbox = Vec::new();
+    let written = export_threads_mbox(&fetcher, &[id], &mut mbox)
+        .await
+        .unwrap();
+    assert_eq!(written, 2);
+    assert!(!server.paths()[fetched..]
+        .iter()
+        .any(|path| path.ends_with("/s@x") || path.ends_with("/s%40x")));
+    let mbox = String::from_utf8(mbox).unwrap();
+    let (starter, reply) = mbox.split_once("\n\nFrom ").unwrap();
+
+    assert!(starter.starts_with("From jane.doe@example.org Wed Jan 22 13:59:09 2025\n"));
+    assert!(starter.contains("\nMessage-ID: <s@x>\n"));
+    assert!(starter.contains("\nDate: Wed, 22 Jan 2025 13:59:09 +0000\n"));
+    assert!(starter.contains("\nFrom: Jane Doe <jane.doe@example.org>\n"));
+    assert!(starter.contains("\nSubject: Subject of s%40x\n"));
+    assert!(!starter.contains("In-Reply-To"));
+    assert!(starter.ends_with("\n\nHello hackers,\nhere is a patch."));
+
+    assert!(reply.starts_with("bob@example.org Wed Jan 22 15:00:00 2025\n"));
+    assert!(reply.contains("\nFrom: \"Bob Smith, Jr.\" <bob@example.org>\n"));
+    assert!(reply.contains("\nSubject: =?UTF-8?B?UmU6IFLDqXN1bcOpIG9mIHRoZSBwYXRjaA==?=\n"));
+    assert!(reply.contains("\nIn-Reply-To: <s@x>\nReferences: <s@x>\n"));
+    assert!(reply
+        .ends_with("\n\nOn Wed, Alice wrote:\n> From the docs\n> see this\n>> older\nAgreed.\n\n"));
+}
+
+#[tokio::test]
+async fn threads_exported_to_maildirs_are_added_to() {
+    use mock_server::{flat_page, MessagePage, MockResponse, MockServer};
+    use std::sync::atomic::AtomicBool;
+
+    let replied = Arc::new(AtomicBool::new(false));
+    let server = MockServer::start({
+        let replied = replied.clone();
+        move |req| {
+            if req.path.starts_with("/message-id/raw/") {
+                return MockResponse::not_found();
+            }
+            let thread: &[&str] = match replied.load(Ordering::SeqCst) {
+                false => &["s@x", "r1@x"],
+                true => &["s@x", "r1@x", "r2@x"],
+            };
+            let page = |id: &str| {
+                let mut page = MessagePage::new(id).thread(thread);
+                if id == "r2%40x" {
+                    page.date = "2025-01-23 08:00:00".to_string();
+                }
+                page
+            };
+            if req.path.starts_with("/message-id/flat/") {
+                let pages: Vec<_> = thread
+                    .iter()
+                    .map(|id| (*id, page(&id.replace('@', "%40"))))
+                    .collect();
+                let pages: Vec<_> = pages.iter().map(|(id, page)| (*id, page)).collect();
+                return MockResponse::html(flat_page(&pages));
+            }
+            MockResponse::html(page(req.path.trim_start_matches("/message-id/")).render())
+        }
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let dir = std::env::temp_dir().join(format!("pgdevhub-maildir-{}", std::process::id()));
+    let ids = [MessageId::parse("r1@x").unwrap()];
+    let files = || -> Vec<String> {
+        let mut names: Vec<_> = std::fs::read_dir(dir.join("s%40x/cur"))
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    };
+
+    assert_eq!(
+        export_threads_maildir(&fetcher, &ids, &dir).await.unwrap(),
+        2
+    );
+    assert_eq!(files(), ["1737554349.r1%40x:2,", "1737554349.s%40x:2,"]);
+    assert!(dir.join("s%40x/new").is_dir());
+    assert_eq!(std::fs::read_dir(dir.join("s%40x/tmp")).unwrap().count(), 0);
+    let reply = std::fs::read_to_string(dir.join("s%40x/cur/1737554349.r1%40x:2,")).unwrap();
+    assert!(reply.starts_with("Message-ID: <r1@x>\n"));
+    assert!(reply.contains("\nIn-Reply-To: <s@x>\n"));
+
+    replied.store(true, Ordering::SeqCst);
+    let fetched = server.paths().len();
+    assert_eq!(
+        export_threads_maildir(&fetcher, &ids, &dir).await.unwrap(),
+        1
+    );
+    // the starter and the new reply came from the flat view, not their own pages
+    assert_eq!(
+        server.paths()[fetched..],
+        [
+            "/message-id/r1%40x",
+            "/message-id/raw/r1%40x",
+            "/message-id/flat/s%40x"
+        ]
+    );
+    assert_eq!(files().len(), 3);
+    assert!(files().contains(&"1737619200.r2%40x:2,".to_string()));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[tokio::test]
+async fn walk_stops_cleanly_when_caught_up() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" => MockResponse::html(listing_page(&[(
+                "Jan. 18, 2025",
+                &[("a", "Topic a", "Alice", "09:00")],
+            )])),
+            // beyond the newest message
+            "202501180900" => MockResponse::html(""),
+            // where the archive redirects a token it cannot list
+            _ => {
+                MockResponse::html("<html><body><h2>Archives</h2><p>Nothing here</p></body></html>")
+            }
+        }
+    });
+    let fetcher = Fetcher::new(&server.url())
+        .with_clock(|| {
+            NaiveDate::from_ymd_opt(2025, 1, 18)
+                .unwrap()
+                .and_hms_opt(12, 0, 0)
+                .unwrap()
+        })
+        .record_warnings();
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let threads = get_threads_between(
+        &fetcher,
+        day.into(),
+        day.and_hms_opt(23, 59, 59).unwrap(),
+        |thread| Ok(Some(thread)),
+    )
+    .await
+    .unwrap();
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["a"]);
+
+    let later = day.and_hms_opt(10, 0, 0).unwrap();
+    assert!(
+        get_threads_between(&fetcher, later, later + TimeDelta::hours(1), |thread| {
+            Ok(Some(thread))
+        })
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert!(fetcher.warnings().is_empty());
+
+    // a range starting in the future is not fetched at all
+    let requests = server.requests().len();
+    let future = day.succ_opt().unwrap().succ_opt().unwrap().into();
+    assert!(
+        get_threads_between(&fetcher, future, future + TimeDelta::days(1), |thread| {
+            Ok(Some(thread))
+        })
+        .await
+        .unwrap()
+        .is_empty()
+    );
+    assert_eq!(server.requests().len(), requests);
+}
+
+#[tokio::test]
+async fn new_subjects_stream_into_a_sink() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            // c replies to a
+            let thread: &[_] = if id == "c" { &["a", "c"] } else { &[id] };
+            return MockResponse::html(MessagePage::new(id).thread(thread).render());
+        }
+        let rows: &[_] = match req.path.trim_start_matches("/list/pgsql-hackers/since/") {
+            "202501180000" => &[
+                ("a", "Topic a", "Alice", "09:00"),
+                ("b", "Topic b", "Bob", "10:00"),
+            ],
+            "202501181000" => &[
+                ("b", "Topic b", "Bob", "10:00"),
+                ("c", "Topic c, split off", "Carol", "11:00"),
+                ("d", "Topic d", "Dave", "12:00"),
+            ],
+            _ => &[("d", "Topic d", "Dave", "12:00")],
+        };
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let end = day.and_hms_opt(23, 59, 59).unwrap();
+
+    let mut sink: Vec<EmailThread> = Vec::new();
+    let written =
+        write_new_subjects_between(&fetcher, day.into(), end, RangeBy::ThreadStart, &mut sink)
+            .await
+            .unwrap();
+    assert_eq!(written, 3);
+    let ids: Vec<_> = sink.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["a", "b", "d"]);
+
+    // several sinks get the same threads
+    let (mut first, mut second): (Vec<EmailThread>, Vec<EmailThread>) = (Vec::new(), Vec::new());
+    let mut sinks: Vec<Box<dyn ThreadSink>> = vec![Box::new(&mut first), Box::new(&mut second)];
+    write_new_subjects_between(&fetcher, day.into(), end, RangeBy::Message, &mut sinks)
+        .await
+        .unwrap();
+    drop(sinks);
+    assert_eq!(first.len(), 4);
+    assert_eq!(second.len(), 4);
+}
+
+#[test]
+fn references_to_commits_and_bugs() {
+    let content = "<p>This was broken by commit 3c1a2b9f0e and reported as BUG #18234,<br>\n\
+        see https://git.postgresql.org/gitweb/?p=postgresql.git;a=commitdiff;\
+        h=9e2d4c2a77b1f0f5a0c1d8e3b6a4f7c2d1e0b9a8.<br>\n\
+        <blockquote>The fix for bug #18234 in commit 3c1a2b9f0e is wrong.</blockquote></p>";
+    assert_eq!(
+        extract_references(content),
+        References {
+            commits: vec![
+                "3c1a2b9f0e".to_string(),
+                "9e2d4c2a77b1f0f5a0c1d8e3b6a4f7c2d1e0b9a8".to_string(),
+            ],
+            bugs: vec!["18234".to_string()],
+        }
+    );
+
+    // hex-looking words and numbers alone are not references
+    let content = "<p>Set 0xdeadbeef in 1234567 of 2025 rows, commit the change.<br>\n\
+        Message-ID: CAFj8pRDn=abc1234@mail.gmail.com, bug # was 12.</p>";
+    assert_eq!(extract_references(content), References::default());
+}
+
+#[tokio::test]
+async fn since_overlap_misses_nothing_in_a_dense_range() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+    use std::sync::atomic::AtomicBool;
+
+    // three threads a minute from 09:00 to 09:09, seven to a page from the token's minute on
+    let threads: Vec<_> = (0..30)
+        .map(|i| (format!("t{i}"), format!("09:{:02}", i / 3)))
+        .collect();
+    let drop_t6 = Arc::new(AtomicBool::new(false));
+    let drop_t6_ = drop_t6.clone();
+    let server = MockServer::start(move |req| {
+        let token = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let from = format!("{}:{}", &token[8..10], &token[10..12]);
+        let rows: Vec<_> = threads
+            .iter()
+            .filter(|(id, time)| {
+                *time >= from
+                    && !(id == "t6" && drop_t6_.load(Ordering::SeqCst) && from.as_str() > "09:00")
+            })
+            .take(7)
+            .map(|(id, time)| (id.as_str(), "Topic", "Alice", time.as_str()))
+            .collect();
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", &rows)]))
+    });
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let run = async |minutes: u64| {
+        let fetcher = Fetcher::new(&server.url())
+            .with_config(FetchConfig {
+                since_overlap: Duration::from_secs(minutes * 60),
+                ..FetchConfig::default()
+            })
+            .record_warnings();
+        let before = server.requests().len();
+        let threads = get_threads_between(
+            &fetcher,
+            day.into(),
+            day.and_hms_opt(23, 59, 59).unwrap(),
+            |thread| Ok(Some(thread)),
+        )
+        .await
+        .unwrap();
+        let ids: HashSet<_> = threads.into_iter().map(|thread| thread.id).collect();
+        (
+            ids.len(),
+            server.requests().len() - before,
+            fetcher.warnings(),
+        )
+    };
+
+    let (found, pages, warnings) = run(0).await;
+    assert_eq!(found, 30);
+    assert!(warnings.is_empty(), "{warnings:?}");
+    let (found, wider_pages, warnings) = run(1).await;
+    assert_eq!(found, 30);
+    assert!(warnings.is_empty(), "{warnings:?}");
+    assert!(pages < wider_pages);
+
+    // a thread of the boundary minute goes missing from the next page
+    drop_t6.store(true, Ordering::SeqCst);
+    let (_, _, warnings) = run(0).await;
+    assert_eq!(warnings.len(), 1);
+    assert!(warnings[0].contains("does not list 1 threads"));
+}
+
+#[test]
+fn moderation_notices_are_told_from_posts() {
+    assert!(is_list_notice(
+        "pgsql-hackers-owner@lists.postgresql.org",
+        "Your message to pgsql-hackers",
+        ""
+    ));
+    assert!(is_list_notice(
+        "PostgreSQL Lists",
+        "Moderation notice",
+        "<p>Your message is being held until the list moderator can review it.</p>"
+    ));
+    assert!(is_list_notice(
+        "Mail Delivery System",
+        "Undelivered Mail Returned to Sender",
+        ""
+    ));
+    assert!(is_list_notice(
+        "MAILER-DAEMON@example.org",
+        "failure notice",
+        ""
+    ));
+
+    // posts about moderation or delivery are still posts
+    assert!(!is_list_notice(
+        "Alice <alice@example.org>",
+        "Re: Moderation of the pgsql-hackers list",
+        "<p>Should first posts be held? I think the owner should decide.</p>"
+    ));
+    assert!(!is_list_notice(
+        "Bob",
+        "[PATCH] Fix walsender delivery of status messages",
+        ""
+    ));
+}
+
+#[tokio::test]
+async fn position_of_a_message_in_its_thread() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(
+            MessagePage::new(id)
+                .thread(&["start%40x", "reply1%40x", "reply2%40x", "reply3%40x"])
+                .render(),
+        )
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let ids = thread_message_ids(&fetcher, "reply2%40x").await.unwrap();
+
+    assert_eq!(message_position(&ids, "start%40x").unwrap(), (1, 4));
+    assert_eq!(message_position(&ids, "reply2@x").unwrap(), (3, 4));
+    assert!(message_position(&ids, "elsewhere%40x").is_err());
+
+    let detail = get_thread_detail(
+        &fetcher,
+        &MessageId::parse("reply3%40x").unwrap(),
+        ThreadDetailOptions::COMPACT,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        message_position(&detail.replies, &detail.id).unwrap(),
+        (4, 4)
+    );
+}
+
+#[tokio::test]
+async fn client_timeout_and_user_agent_are_configured() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path.contains("slow") {
+            std::thread::sleep(Duration::from_millis(500));
+        }
+        MockResponse::html(MessagePage::new("a").render())
+    });
+    let once = RetryPolicy {
+        attempts: 1,
+        backoff: Duration::from_millis(1),
+    };
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        timeout_retry: once,
+        request_timeout: Some(Duration::from_millis(100)),
+        user_agent: "digest-bot/2.0".to_string(),
+        ..FetchConfig::default()
+    });
+
+    fetcher
+        .get_document(&fetcher.message_url("a"))
+        .await
+        .unwrap();
+    let err = fetcher
+        .get_document(&fetcher.message_url("slow"))
+        .await
+        .unwrap_err();
+    assert!(matches!(
+        err.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::Network {
+            class: ErrorClass::Timeout,
+            ..
+        })
+    ));
+    assert_eq!(
+        server.requests()[0].header("user-agent"),
+        Some("digest-bot/2.0")
+    );
+
+    let fetcher = Fetcher::new(&server.url());
+    fetcher
+        .get_document(&fetcher.message_url("a"))
+        .await
+        .unwrap();
+    let last = server.requests().pop().unwrap();
+    assert!(last.header("user-agent").unwrap().starts_with("pgdevhub/"));
+}
+
+#[tokio::test]
+async fn broken_message_pages_are_errors_not_panics() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        let page = match req.path.as_str() {
+            "/message-id/baddate%40x" => MessagePage {
+                date: "yesterday".to_string(),
+                ..MessagePage::new("baddate%40x")
+            }
+            .render(),
+            "/message-id/noselect%40x" => MessagePage::new("noselect%40x")
+                .render()
+                .replace("thread_select", "other_select"),
+            _ => listing_page(&[(
+                "Jan. 18, 2025",
+                &[("noselect%40x", "Topic", "Alice", "09:00")],
+            )]),
+        };
+        MockResponse::html(page)
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let error = async |id: &str| {
+        get_thread_by_id(&fetcher, &MessageId::parse(id).unwrap())
+            .await
+            .unwrap_err()
+    };
+
+    assert!(matches!(
+        error("baddate@x").await.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::InvalidDate { text, .. }) if text == "yesterday"
+    ));
+    assert!(matches!(
+        error("noselect@x").await.downcast_ref::<ScrapeError>(),
+        Some(ScrapeError::MissingElement { selector, .. }) if *selector == THREAD_SELECT
+    ));
+
+    // a traversal checking the starter of such a message fails rather than panicking
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let err = get_new_subjects_between(&fetcher, day.into(), day.and_hms_opt(23, 59, 59).unwrap())
+        .await
+        .unwrap_err();
+    assert!(err.chain().any(|cause| matches!(
+        cause.downcast_ref(),
+        Some(ScrapeError::MissingElement { .. })
+    )));
+}
+
+#[tokio::test]
+async fn request_rate_allows_a_burst_then_paces() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let arrivals = Arc::new(Mutex::new(Vec::new()));
+    let server_arrivals = arrivals.clone();
+    let server = MockServer::start(move |req| {
+        server_arrivals.lock().unwrap().push(Instant::now());
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).render())
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        request_rate: Some(2.0),
+        request_burst: 3,
+        ..FetchConfig::default()
+    });
+
+    let start = Instant::now();
+    for id in ["a", "b", "c", "d"] {
+        fetcher
+            .get_document(&fetcher.message_url(id))
+            .await
+            .unwrap();
+    }
+
+    // the burst goes out without waiting, the next request only once a token is back
+    let arrivals = arrivals.lock().unwrap();
+    assert!(arrivals[2] - start < Duration::from_millis(500));
+    assert!(arrivals[3] - start >= Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn busy_site_is_retried_honoring_retry_after() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+    use std::sync::atomic::AtomicUsize;
+
+    let hits = Arc::new(AtomicUsize::new(0));
+    let server_hits = hits.clone();
+    let server = MockServer::start(move |req| match req.path.as_str() {
+        "/message-id/flaky" => match server_hits.fetch_add(1, Ordering::SeqCst) {
+            0 => MockResponse::new(503, "text/html", "down for maintenance"),
+            1 => MockResponse::new(429, "text/html", "slow down").with_header("Retry-After", "0"),
+            _ => MockResponse::html(MessagePage::new("flaky").render()),
+        },
+        "/message-id/patient" => {
+            MockResponse::new(429, "text/html", "slow down").with_header("Retry-After", "3600")
+        }
+        _ => MockResponse::new(500, "text/html", "oops"),
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        unavailable_retry: RetryPolicy {
+            attempts: 3,
+            backoff: Duration::from_millis(1),
+        },
+        ..FetchConfig::default()
+    });
+
+    fetcher
+        .get_document(&fetcher.message_url("flaky"))
+        .await
+        .unwrap();
+    assert_eq!(hits.load(Ordering::SeqCst), 3);
+
+    let unavailable = async |id: &str| {
+        let err = fetcher
+            .get_document(&fetcher.message_url(id))
+            .await
+            .unwrap_err();
+        match err.downcast::<ScrapeError>().unwrap() {
+            ScrapeError::Unavailable {
+                status, attempts, ..
+            } => (status.as_u16(), attempts),
+            err => panic!("unexpected error {err:?}"),
+        }
+    };
+    assert_eq!(unavailable("broken").await, (500, 3));
+    // waiting an hour is not worth it, the fetch fails at once
+    assert_eq!(unavailable("patient").await, (429, 1));
+}
+
+#[tokio::test]
+async fn active_subjects_fetch_details_concurrently_in_listing_order() {
+    use mock_server::{listing_page, MessagePage, MockResponse, MockServer};
+    use std::sync::atomic::AtomicUsize;
+
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let (in_flight_, max_in_flight_) = (in_flight.clone(), max_in_flight.clone());
+    let server = MockServer::start(move |req| {
+        // only the details fetch the raw view, the starter checks before them do not
+        if let Some(id) = req.path.strip_prefix("/message-id/raw/") {
+            let current = in_flight_.fetch_add(1, Ordering::SeqCst) + 1;
+            max_in_flight_.fetch_max(current, Ordering::SeqCst);
+            // the first ones take the longest, so they would finish last if order were lost
+            let delay = if id.starts_with('a') { 150 } else { 50 };
+            std::thread::sleep(Duration::from_millis(delay));
+            in_flight_.fetch_sub(1, Ordering::SeqCst);
+            return MockResponse::new(200, "text/plain", "Subject: topic\r\n\r\nbody\r\n");
+        }
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a%40x", "Topic a", "Alice", "09:00"),
+                ("b%40x", "Topic b", "Bob", "10:00"),
+                ("c%40x", "Topic c", "Carol", "11:00"),
+                ("d%40x", "Topic d", "Dave", "12:00"),
+            ],
+        )]))
+    });
+    let fetcher = Fetcher::new(&server.url()).with_config(FetchConfig {
+        page_concurrency: 2,
+        ..FetchConfig::default()
+    });
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    let threads =
+        get_active_subjects_between(&fetcher, day.into(), day.and_hms_opt(23, 59, 59).unwrap())
+            .await
+            .unwrap();
+
+    let ids: Vec<_> = threads.iter().map(|thread| thread.id.as_str()).collect();
+    assert_eq!(ids, ["a%40x", "b%40x", "c%40x", "d%40x"]);
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn progress_is_reported_to_the_observer() {
+    use mock_server::{
+        listing_page, message_not_found_page, MessagePage, MockResponse, MockServer,
+    };
+    use progress::ProgressObserver;
+
+    #[derive(Debug, Default)]
+    struct Counts(Mutex<(usize, usize, usize, Vec<String>)>);
+
+    impl ProgressObserver for Arc<Counts> {
+        fn page_fetched(&self, _: &str, _: Duration) {
+            self.0.lock().unwrap().0 += 1;
+        }
+
+        fn thread_found(&self, _: &EmailThread) {
+            self.0.lock().unwrap().1 += 1;
+        }
+
+        fn detail_fetched(&self, _: &EmailThreadDetail) {
+            self.0.lock().unwrap().2 += 1;
+        }
+
+        fn error(&self, message: &str) {
+            self.0.lock().unwrap().3.push(message.to_string());
+        }
+    }
+
+    let server = MockServer::start(|req| {
+        if req.path == "/message-id/gone%40x" {
+            return MockResponse::new(404, "text/html", message_not_found_page());
+        }
+        if req.path.starts_with("/message-id/raw/") {
+            return MockResponse::new(200, "text/plain", "Subject: Topic a\r\n\r\nbody\r\n");
+        }
+        if let Some(id) = req.path.strip_prefix("/message-id/") {
+            return MockResponse::html(MessagePage::new(id).render());
+        }
+        MockResponse::html(listing_page(&[(
+            "Jan. 18, 2025",
+            &[
+                ("a%40x", "Topic a", "Alice", "09:00"),
+                ("gone%40x", "Topic gone", "Bob", "10:00"),
+            ],
+        )]))
+    });
+    let counts = Arc::new(Counts::default());
+    let fetcher = Fetcher::new(&server.url()).with_progress(counts.clone());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+
+    get_active_subjects_between(&fetcher, day.into(), day.and_hms_opt(23, 59, 59).unwrap())
+        .await
+        .unwrap();
+
+    let (pages, threads, details, errors) = &*counts.0.lock().unwrap();
+    assert_eq!(*pages, server.requests().len());
+    // the last since-page is fetched again to see that nothing follows, its threads with it
+    assert_eq!(*threads, 4);
+    assert_eq!(*details, 1);
+    assert_eq!(errors.len(), 1, "{errors:?}");
+    assert!(errors[0].contains("gone%40x"));
+}
+
+#[tokio::test]
+async fn sync_starts_where_the_last_one_stopped() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+    use std::sync::atomic::AtomicBool;
+    use storage::Storage;
+
+    let third_posted = Arc::new(AtomicBool::new(false));
+    let posted = third_posted.clone();
+    let server = MockServer::start(move |req| {
+        let third = posted.load(Ordering::SeqCst);
+        let token = req.path.trim_start_matches("/list/pgsql-hackers/since/");
+        let mut rows = match token {
+            "202501180000" => vec![
+                ("first", "First topic", "Alice", "09:00"),
+                ("second", "Second topic", "Bob", "12:30"),
+            ],
+            "202501181230" => vec![("second", "Second topic", "Bob", "12:30")],
+            "202501181545" if third => vec![],
+            _ => return MockResponse::not_found(),
+        };
+        if third {
+            rows.push(("third", "Third topic", "Carol", "15:45"));
+        }
+        MockResponse::html(listing_page(&[("Jan. 18, 2025", &rows)]))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let store = storage::Sqlite::open_in_memory().unwrap();
+    let first_since = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap().into();
+    let ids = |threads: Vec<EmailThread>| -> Vec<String> {
+        threads.into_iter().map(|thread| thread.id).collect()
+    };
+
+    let threads = sync(&fetcher, &store, first_since).await.unwrap();
+    assert_eq!(ids(threads), ["first", "second"]);
+    let fetched = server.paths().len();
+
+    // nothing new, only the page of the cursor is fetched
+    assert!(sync(&fetcher, &store, first_since)
+        .await
+        .unwrap()
+        .is_empty());
+    let paths = server.paths();
+    assert!(paths[fetched..]
+        .iter()
+        .all(|path| path.ends_with("/since/202501181230")));
+
+    third_posted.store(true, Ordering::SeqCst);
+    let threads = sync(&fetcher, &store, first_since).await.unwrap();
+    assert_eq!(ids(threads), ["third"]);
+    assert_eq!(
+        store.cursor(MailingList::Hackers).unwrap(),
+        NaiveDate::from_ymd_opt(2025, 1, 18)
+            .unwrap()
+            .and_hms_opt(15, 45, 0)
+    );
+    let day_end = NaiveDate::from_ymd_opt(2025, 1, 18)
+        .unwrap()
+        .and_hms_opt(23, 59, 59)
+        .unwrap();
+    assert_eq!(
+        store
+            .threads_between(MailingList::Hackers, first_since, day_end)
+            .unwrap()
+            .len(),
+        3
+    );
+}
+
+#[tokio::test]
+async fn backfill_resumes_with_the_month_it_stopped_in() {
+    use mock_server::{listing_page, MockResponse, MockServer};
+    use std::sync::atomic::AtomicBool;
+    use storage::Storage;
+
+    let february_up = Arc::new(AtomicBool::new(false));
+    let up = february_up.clone();
+    let server = MockServer::start(move |req| {
+        let path = req.path.trim_start_matches("/list/pgsql-hackers/");
+        let days: &[(&str, &[_])] = match path {
+            "2025-01/" => &[
+                (
+                    "Jan. 3, 2025",
+                    &[("first", "First topic", "Alice", "09:00")],
+                ),
+                (
+                    "Jan. 20, 2025",
+                    &[("second", "Second topic", "Bob", "10:00")],
+                ),
+            ],
+            "since/202501201000" => &[
+                (
+                    "Jan. 20, 2025",
+                    &[("second", "Second topic", "Bob", "10:00")],
+                ),
+                (
+                    "Feb. 2, 2025",
+                    &[("third", "Third topic", "Carol", "08:00")],
+                ),
+            ],
+            "2025-02/" | "since/202502020800" if up.load(Ordering::SeqCst) => &[(
+                "Feb. 2, 2025",
+                &[("third", "Third topic", "Carol", "08:00")],
+            )],
+            _ => return MockResponse::not_found(),
+        };
+        MockResponse::html(listing_page(days))
+    });
+    let fetcher = Fetcher::new(&server.url());
+    let store = storage::Sqlite::open_in_memory().unwrap();
+    let day = |month, day| NaiveDate::from_ymd_opt(2025, month, day).unwrap();
+    let (since, until) = (day(1, 1).into(), day(3, 1).into());
+
+    // February fails, January is kept and checkpointed
+    assert!(backfill(&fetcher, &store, since, until).await.is_err());
+    let kept = |store: &storage::Sqlite| {
+        store
+            .threads_between(MailingList::Hackers, since, until)
+            .unwrap()
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(kept(&store), ["first", "second"]);
+    assert_eq!(
+        store.backfilled(MailingList::Hackers, since).unwrap(),
+        Some(day(2, 1).into())
+    );
+
+    february_up.store(true, Ordering::SeqCst);
+    let fetched = server.paths().len();
+    assert_eq!(backfill(&fetcher, &store, since, until).await.unwrap(), 1);
+    let paths = server.paths();
+    assert!(paths[fetched..]
+        .iter()
+        .all(|path| !path.contains("2025-01")));
+    assert_eq!(kept(&store), ["first", "second", "third"]);
+    assert_eq!(
+        store.backfilled(MailingList::Hackers, since).unwrap(),
+        Some(until)
+    );
+    // done, nothing left to fetch
+    assert_eq!(backfill(&fetcher, &store, since, until).await.unwrap(), 0);
+    assert_eq!(server.paths().len(), paths.len());
+}
+
+#[tokio::test]
+async fn detail_pages_teach_the_starters_of_their_thread() {
+    use mock_server::{MessagePage, MockResponse, MockServer};
+
+    let server = MockServer::start(|req| {
+        if req.path.starts_with("/message-id/raw/") {
+            return MockResponse::new(200, "text/plain", "Subject: Topic\r\n\r\nbody\r\n");
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        let page = MessagePage::new(id).thread(&["s%40x", "r1%40x", "r2%40x"]);
+        MockResponse::html(page.render())
+    });
+    let fetcher = Fetcher::new(&server.url());
+    get_thread_by_id(&fetcher, &MessageId::parse("r1%40x").unwrap())
+        .await
+        .unwrap();
+    let fetched = server.paths().len();
+
+    assert!(!is_thread_starter_by_id(&fetcher, "r2%40x").await.unwrap());
+    assert!(is_thread_starter_by_id(&fetcher, "s%40x").await.unwrap());
+    assert_eq!(
+        get_thread_starter_id(&fetcher, "r1%40x").await.unwrap(),
+        "s%40x"
+    );
+    assert_eq!(server.paths().len(), fetched);
+}
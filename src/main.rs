@@ -1,656 +1,1047 @@
-use anyhow::{Context, Ok, Result};
-use chrono::{NaiveDate, NaiveDateTime, TimeDelta};
-use const_format::concatcp;
-use phf::phf_map;
-use reqwest::blocking::Client;
-use scraper::{Html, Selector};
-
-const PG_SITE: &str = "https://www.postgresql.org";
-const MESSAGE_URL_PREFIX: &str = concatcp!(PG_SITE, "/message-id");
-const NEXT_THREADS_URL_PREFIX: &str = concatcp!(PG_SITE, "/list/pgsql-hackers/since");
-
-// compile-time lookup table
-static MONTHS_MAP: phf::Map<&'static str, &'static str> = phf_map! {
-    "Jan." => "January",
-    "Feb." => "February",
-    "March" => "March",
-    "April" => "April",
-    "May" => "May",
-    "June" => "June",
-    "July" => "July",
-    "Aug." => "August",
-    "Sept." => "September",
-    "Oct." => "October",
-    "Nov." => "November",
-    "Dec." => "December",
+use anyhow::{bail, Context, Ok, Result};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, TimeDelta};
+use clap::{Parser, Subcommand};
+use pgdevhub::config::Config;
+use pgdevhub::import;
+use pgdevhub::progress::ProgressObserver;
+use pgdevhub::records::{self, RecordWriter};
+use pgdevhub::sink::ThreadSink;
+use pgdevhub::storage::{self, Storage, Unseen};
+use pgdevhub::{
+    add_replies, add_reply_counts, api, archive_thread_mbox, attachments_between, backfill, browse,
+    digest, export_threads_maildir, export_threads_mbox, get_active_subjects_between,
+    get_new_subjects_between, get_new_subjects_between_by, get_threads_between_newest_first, http,
+    is_new_subject, replay_since_pages, save_attachments, scheduler, sync, template, thread_to_dot,
+    watch_thread, write_new_subjects_between, EmailThread, Fetcher, HttpVersion, MessageId,
+    RangeBy, PG_SITE,
 };
-
-fn transform_date(date_text: &str) -> Option<NaiveDate> {
-    let date_text: String = date_text
-        .split(' ')
-        .map(|s| {
-            MONTHS_MAP
-                .get(s)
-                .map(|s| s.to_string())
-                .unwrap_or(s.to_string())
-        })
-        .collect();
-    NaiveDate::parse_from_str(&date_text, "%B %d, %Y").ok()
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    about = "Follow discussions on the PostgreSQL mailing lists",
+    after_help = "Log messages go to stderr, filtered by RUST_LOG like RUST_LOG=warn to keep \
+                  quiet or RUST_LOG=pgdevhub=debug to trace every fetch and parse. The default \
+                  is info."
+)]
+struct Cli {
+    /// print the since-page urls visited, so the run can be reproduced exactly
+    #[arg(long, global = true)]
+    print_since_urls: bool,
+
+    /// how many attachments may be downloaded at the same time
+    #[arg(long, global = true)]
+    attachment_concurrency: Option<usize>,
+
+    /// how many attachment downloads may start per second, unlimited by default
+    #[arg(long, global = true, value_parser = parse_rate)]
+    attachment_rate: Option<f64>,
+
+    /// wait at least this many milliseconds between the end of a page fetch and the start of
+    /// the next one
+    #[arg(long, global = true)]
+    min_delay_between_requests: Option<u64>,
+
+    /// start at most this many requests per second on average, unlimited by default
+    #[arg(long, global = true, value_parser = parse_rate)]
+    request_rate: Option<f64>,
+
+    /// how many requests may start back to back under --request-rate, 1 by default
+    #[arg(long, global = true, requires = "request_rate")]
+    request_burst: Option<u32>,
+
+    /// print each thread like this instead, with placeholders {subject}, {author}, {datetime},
+    /// {url} and {id}, e.g. "{datetime}\t{author}\t{subject}"
+    #[arg(long, global = true, value_parser = str::parse::<template::Template>)]
+    template: Option<template::Template>,
+
+    /// stop with an error after this many requests
+    #[arg(long, global = true)]
+    max_total_requests: Option<u64>,
+
+    /// stop with an error after receiving this many bytes
+    #[arg(long, global = true)]
+    max_total_bytes: Option<u64>,
+
+    /// fetch up to this many since-pages ahead while the threads of the current one are checked
+    #[arg(long, global = true, default_value_t = 0)]
+    prefetch_pages: usize,
+
+    /// start each since-page this many minutes before the newest thread of the previous one,
+    /// to catch threads listed out of order
+    #[arg(long, global = true, value_name = "MINUTES", default_value_t = 0)]
+    since_overlap: u64,
+
+    /// the mailing list to follow, like pgsql-bugs; pgsql-hackers by default
+    #[arg(long, global = true)]
+    list: Option<pgdevhub::MailingList>,
+
+    /// how many mailing lists may be traversed at the same time when merging several
+    #[arg(long, global = true)]
+    list_concurrency: Option<usize>,
+
+    /// send this header with every request, like "Authorization: Bearer ..."; may be repeated
+    #[arg(long = "header", global = true, value_parser = parse_header)]
+    headers: Vec<(reqwest::header::HeaderName, reqwest::header::HeaderValue)>,
+
+    /// keep cookies the site sets and send them back on later requests
+    #[arg(long, global = true)]
+    cookies: bool,
+
+    /// also list moderation and bounce notices among new and active subjects
+    #[arg(long, global = true)]
+    include_notices: bool,
+
+    /// tell new subjects by their In-Reply-To and References headers rather than their
+    /// subjects, fetching the raw view of each listed message
+    #[arg(long, global = true)]
+    thread_by_headers: bool,
+
+    /// file remembering the thread starter of each message looked up, so later runs and API
+    /// requests can skip those page fetches
+    #[arg(long, global = true)]
+    starter_cache: Option<std::path::PathBuf>,
+
+    /// HTTP version to talk to the archive with
+    #[arg(long, global = true, value_enum, default_value = "auto")]
+    http_version: HttpVersion,
+
+    /// give up on a request that takes longer than this many seconds, 30 by default
+    #[arg(long, global = true, value_name = "SECONDS")]
+    timeout: Option<u64>,
+
+    /// the User-Agent sent to the archive, pgdevhub/<version> by default
+    #[arg(long, global = true)]
+    user_agent: Option<String>,
+
+    /// show a progress line with the pages, threads and errors so far instead of printing every
+    /// page fetched
+    #[arg(long, global = true)]
+    progress: bool,
+
+    /// read settings from this TOML file, overridden by PGDEVHUB_* environment variables and
+    /// then by the options given here
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<std::path::PathBuf>,
+
+    /// keep the threads and messages scraped in this SQLite database, or in the PostgreSQL
+    /// database of a postgres:// url
+    #[arg(long, global = true, value_name = "FILE|URL")]
+    store: Option<String>,
+
+    /// also index the messages kept in the --store database in this directory, which then
+    /// answers search
+    #[cfg(feature = "tantivy")]
+    #[arg(long, global = true, value_name = "DIR")]
+    search_index: Option<std::path::PathBuf>,
+
+    /// keep pages in this directory and only download them again when they changed
+    #[arg(long, global = true, value_name = "DIR")]
+    http_cache: Option<std::path::PathBuf>,
+
+    /// save every page fetched into this directory, for replaying the run with --replay
+    #[arg(long, global = true, value_name = "DIR")]
+    record: Option<std::path::PathBuf>,
+
+    /// serve pages from the ones saved by --record instead of fetching them
+    #[arg(long, global = true, value_name = "DIR", conflicts_with = "record")]
+    replay: Option<std::path::PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-trait PgMessage {
-    fn id(&self) -> &str;
+#[derive(Subcommand)]
+enum Command {
+    /// new subjects of the last week (the default)
+    New {
+        /// look back this many days instead of a week
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+        /// list the newest subjects first
+        #[arg(long)]
+        newest_first: bool,
+        /// also count the replies of each subject, one page fetch per subject
+        #[arg(long)]
+        reply_counts: bool,
+        /// also list the ids of the replies to each subject, one page fetch per subject
+        #[arg(long)]
+        output_replies: bool,
+        /// count a subject as new by when its thread started, or more cheaply by when the
+        /// listed message was posted
+        #[arg(long, value_enum, default_value = "thread-start")]
+        range_by: RangeBy,
+        /// skip the subjects an earlier run with --unseen already listed, remembered in the
+        /// --store database, so overlapping ranges list each subject once
+        #[arg(long)]
+        unseen: bool,
+        /// write the subjects for reading, or with all their fields as JSON Lines or CSV
+        #[arg(long, value_enum, default_value = "text")]
+        output: Output,
+        /// also write the subjects to this Parquet file
+        #[cfg(feature = "parquet")]
+        #[arg(long)]
+        out_parquet: Option<std::path::PathBuf>,
+    },
+    /// subjects under discussion in the last day
+    Active {
+        /// look back this many hours instead of a whole day
+        #[arg(long, default_value_t = 24, value_parser = clap::value_parser!(u32).range(1..))]
+        hours: u32,
+        /// warn when a listing and a message page disagree on the message time
+        #[arg(long)]
+        verify_datetimes: bool,
+        /// write the messages for reading, or with all their fields as JSON Lines or CSV
+        #[arg(long, value_enum, default_value = "text")]
+        output: Output,
+    },
+    /// list the subjects posted since the last sync and keep them in the --store database,
+    /// which remembers where each sync stopped, to run from cron
+    Sync {
+        /// on the first sync of a list, look back this many days
+        #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+    },
+    /// keep the whole history of the list in the --store database, a month at a time. an
+    /// interrupted backfill resumes with the month it stopped in when run again with the same
+    /// --from
+    Backfill {
+        /// the first day to keep, like 1997-01-01
+        #[arg(long)]
+        from: NaiveDate,
+        /// the day to stop before, like 2025-01-01, or now
+        #[arg(long, default_value = "now")]
+        to: String,
+    },
+    /// keep the messages of mbox files, like the monthly ones postgresql.org offers for download,
+    /// in the --store database as messages of the --list, without crawling the archive
+    Import {
+        #[arg(required = true)]
+        files: Vec<std::path::PathBuf>,
+    },
+    /// find the messages kept in the --store database whose subject, author or content match
+    /// QUERY, of the --list if given
+    Search {
+        /// words to find like io_uring, in the full-text query syntax of the database
+        query: String,
+        /// only messages of the last this many days (default: all of them)
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        days: Option<u32>,
+    },
+    /// bring the tables of the --store database up to this version, which opening it for any
+    /// other command also does
+    Migrate {
+        /// only list the migrations the database has not been through
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// serve the JSON API
+    Serve {
+        /// the address to listen on, 127.0.0.1:3000 by default
+        #[arg(long)]
+        bind: Option<String>,
+        /// also print a digest of the new subjects on this cron schedule, like "0 9 * * 1" for
+        /// Mondays at 09:00
+        #[arg(long)]
+        schedule: Option<String>,
+        /// serve the API under this path, like /pgdev when a reverse proxy forwards /pgdev/
+        #[arg(long)]
+        base_path: Option<String>,
+        /// also serve a prebuilt frontend from this directory, with its index.html for any path
+        /// that is neither an API route nor a file
+        #[arg(long, value_name = "DIR")]
+        static_dir: Option<std::path::PathBuf>,
+        /// remember in this file how far the scheduled digest got, so that after a restart it
+        /// picks up where it stopped instead of at the time of the restart
+        #[arg(long, value_name = "FILE")]
+        digest_state: Option<std::path::PathBuf>,
+        /// keep the answers for a while and serve them again to whoever asks the same, in this
+        /// Redis server (redis://host) or in memory
+        #[arg(long, value_name = "URL|memory")]
+        response_cache: Option<String>,
+        /// seconds a listing is kept by the --response-cache, 0 for none
+        #[arg(long, value_name = "SECS")]
+        listing_ttl: Option<u64>,
+        /// seconds a thread is kept by the --response-cache, 0 for none
+        #[arg(long, value_name = "SECS")]
+        thread_ttl: Option<u64>,
+        /// keep the last day's active subjects and the last week's new subjects ready, listed
+        /// again every this many seconds (600 by default), 0 to list them on each request
+        #[arg(long, value_name = "SECS")]
+        warm_every: Option<u64>,
+    },
+    /// page through threads interactively, one since-page at a time
+    Browse {
+        /// where to start, like 2025-01-18T09:00:00 (default: a day ago)
+        #[arg(long)]
+        since: Option<NaiveDateTime>,
+    },
+    /// list the threads of exactly these since-pages, like the ones printed by
+    /// --print-since-urls, to debug parsing against the pages of an earlier run
+    Replay {
+        /// since-page tokens like 202501180930, or whole since-page urls
+        #[arg(required = true)]
+        tokens: Vec<String>,
+    },
+    /// follow a thread and print new replies as they arrive
+    Watch {
+        #[arg(value_parser = MessageId::parse)]
+        starter_id: MessageId,
+        /// poll interval in seconds
+        #[arg(default_value_t = 300)]
+        interval: u64,
+    },
+    /// list the attachments of every message posted in the last week
+    Attachments {
+        /// look back this many days instead of a week
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+        /// also save the attachments into this directory
+        #[arg(long, value_name = "DIR")]
+        download_attachments: Option<std::path::PathBuf>,
+        /// save only attachments with these extensions, like patch,diff (default: all)
+        #[arg(long, value_delimiter = ',')]
+        attachment_ext: Vec<String>,
+    },
+    /// print the reply tree of a thread as Graphviz DOT, for `dot -Tsvg`
+    Graph {
+        #[arg(value_parser = MessageId::parse)]
+        starter_id: MessageId,
+        /// draw only the first this many messages, each of which costs a fetch
+        #[arg(long)]
+        max_messages: Option<usize>,
+    },
+    /// save the raw source of a whole thread as an mbox file, for archiving or a mail client
+    Mbox {
+        #[arg(value_parser = MessageId::parse)]
+        starter_id: MessageId,
+        /// the file to write
+        out: std::path::PathBuf,
+    },
+    /// write whole threads with headers made up from what was scraped of their messages, to open
+    /// them in mutt or import them in notmuch. messages kept in the --store database are not
+    /// fetched again
+    Export {
+        /// the threads, by the id of any of their messages (default: the new subjects of the last
+        /// --days)
+        #[arg(value_parser = MessageId::parse)]
+        ids: Vec<MessageId>,
+        #[arg(long, default_value_t = 7, value_parser = clap::value_parser!(u32).range(1..))]
+        days: u32,
+        #[arg(long, value_enum, default_value = "mbox")]
+        format: ExportFormat,
+        /// the file to write (default: standard output), or the directory of the maildirs
+        #[arg(long, value_name = "FILE|DIR", required_if_eq("format", "maildir"))]
+        out: Option<std::path::PathBuf>,
+    },
 }
 
-#[derive(Debug)]
-struct EmailThread {
-    id: String,
-    subject: String,
-    datetime: NaiveDateTime,
-    author: String,
-}
-
-impl PgMessage for EmailThread {
-    fn id(&self) -> &str {
-        &self.id
-    }
+/// how `new` and `active` write what they list to standard output
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Output {
+    /// a block per subject, or the --template
+    Text,
+    /// a JSON object per line, for loading into DuckDB or pandas
+    Jsonl,
+    /// a header row and a row per subject
+    Csv,
 }
 
-impl std::fmt::Display for EmailThread {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Thread: {}\nAuthor: {}\nTime: {}\nURL: {PG_SITE}/message-id/{}",
-            self.subject,
-            self.author,
-            self.datetime.format("%Y-%m-%d %H:%M:%S"),
-            self.id
-        )
+impl Output {
+    /// the records to write, none for text
+    fn records(self) -> Option<records::Format> {
+        match self {
+            Output::Text => None,
+            Output::Jsonl => Some(records::Format::Jsonl),
+            Output::Csv => Some(records::Format::Csv),
+        }
     }
 }
 
-#[derive(Debug)]
-struct ThreadAttachment {
-    name: String,
-    // url without domain name
-    href: String,
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ExportFormat {
+    /// an mboxrd file of RFC 2822 messages
+    Mbox,
+    /// a maildir per thread, a file per message, added to when exporting again
+    Maildir,
 }
 
-#[derive(Debug)]
-struct EmailThreadDetail {
-    id: String,
-    subject: String,
-    datetime: NaiveDateTime,
-    author_name: String,
-    author_email: String,
-    // a html fragment
-    content: String,
-    // name and url
-    attachments: Vec<ThreadAttachment>,
-    // list of other messages' id
-    replies: Vec<String>,
+/// a `Name: value` header given on the command line
+fn parse_header(
+    header: &str,
+) -> Result<(reqwest::header::HeaderName, reqwest::header::HeaderValue)> {
+    let (name, value) = header
+        .split_once(':')
+        .with_context(|| format!("'{header}' is not a header, expected 'Name: value'"))?;
+    Ok((name.trim().parse()?, value.trim().parse()?))
 }
 
-impl PgMessage for EmailThreadDetail {
-    fn id(&self) -> &str {
-        &self.id
+/// a rate per second given on the command line, which must let something start
+fn parse_rate(rate: &str) -> Result<f64> {
+    let rate: f64 = rate.trim().parse()?;
+    if !(rate.is_finite() && rate > 0.0) {
+        bail!("{rate} is not a positive rate per second");
     }
+    Ok(rate)
 }
 
-impl std::fmt::Display for EmailThreadDetail {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(
-            f,
-            "Thread: {}\n\
-            Author Name: {}\n\
-            Author Email: {}\n\
-            Time: {}\n\
-            URL: {PG_SITE}/message-id/{}\n\
-            Content Size: {}\n\
-            Total Attachments: {}\n\
-            Total replies: {}",
-            self.subject,
-            self.author_name,
-            self.author_email,
-            self.datetime.format("%Y-%m-%d %H:%M:%S"),
-            self.id,
-            self.content.len(),
-            self.attachments.len(),
-            self.replies.len(),
-        )
-    }
+/// counts of a run on a spinner on stderr, with the errors printed above it
+#[derive(Debug)]
+struct ProgressLine {
+    bar: indicatif::ProgressBar,
+    pages: AtomicU64,
+    threads: AtomicU64,
+    details: AtomicU64,
+    errors: AtomicU64,
 }
 
-fn clean_subject_title(title: &str) -> String {
-    let title = title.trim();
-    // remove unicode emoji
-    let title = title.split('📎').next().unwrap_or(title).trim().to_string();
-    // replace multiple spaces with single one
-    let mut new_title = String::new();
-    let mut prev_char = ' ';
-    for char in title.chars() {
-        if char.is_whitespace() && !prev_char.is_whitespace() {
-            new_title.push(' ');
-        } else if !char.is_whitespace() {
-            new_title.push(char);
+impl ProgressLine {
+    fn new(bar: indicatif::ProgressBar) -> Self {
+        bar.enable_steady_tick(Duration::from_millis(100));
+        ProgressLine {
+            bar,
+            pages: AtomicU64::new(0),
+            threads: AtomicU64::new(0),
+            details: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
         }
-        prev_char = char;
     }
-    new_title
-}
 
-fn handle_table(
-    table: &scraper::ElementRef,
-    date: NaiveDate,
-    mut handle_email_thread: impl FnMut(EmailThread) -> bool,
-) -> bool {
-    let tr_selector = Selector::parse("tr").unwrap();
-    let th_selector = Selector::parse("th").unwrap();
-    let td_selector = Selector::parse("td").unwrap();
-    let a_selector = Selector::parse("a").unwrap();
-    let mut handle_ok = true;
-
-    for tr in table.select(&tr_selector) {
-        // Get the thread subject from th
-        let subject_th = tr.select(&th_selector).next();
-        // Get author and time from td
-        let tds: Vec<_> = tr.select(&td_selector).collect();
-
-        // Skip table header rows
-        if tds.is_empty() {
-            continue;
-        }
-
-        if let (Some(subject_td), true) = (subject_th, tds.len() >= 2) {
-            let author_td = &tds[0];
-            let time_td = &tds[1];
-
-            // Get subject and URL
-            if let Some(a) = subject_td.select(&a_selector).next() {
-                let text = a.text().collect::<String>().trim().to_string();
-                let clean_subject = clean_subject_title(&text);
-
-                let href = a.value().attr("href").unwrap_or("");
-                let author = author_td.text().collect::<String>().trim().to_string();
-                let time_str = time_td.text().collect::<String>().trim().to_string();
-                let datetime_str = format!("{} {}", date.format("%Y-%m-%d"), time_str);
-                let datetime = NaiveDateTime::parse_from_str(&datetime_str, "%Y-%m-%d %H:%M")
-                    .unwrap_or_default();
-
-                if !handle_email_thread(EmailThread {
-                    id: href.trim_start_matches("/message-id/").to_string(),
-                    subject: clean_subject,
-                    datetime,
-                    author,
-                }) {
-                    handle_ok = false;
-                    break;
-                }
-            }
-        }
+    fn count(&self, counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.bar.set_message(format!(
+            "{} pages, {} threads listed, {} details, {} errors",
+            self.pages.load(Ordering::Relaxed),
+            self.threads.load(Ordering::Relaxed),
+            self.details.load(Ordering::Relaxed),
+            self.errors.load(Ordering::Relaxed),
+        ));
     }
-    handle_ok
-}
-
-fn get_document(url: &str) -> Result<Html> {
-    println!("get document from {url}");
-    let client = Client::new();
-    let start_time = std::time::Instant::now();
-    let response = client.get(url).send().context("Failed to fetch the page")?;
-    println!(
-        "get document from {url}, done, elapsed: {} ms",
-        start_time.elapsed().as_millis()
-    );
-    let body = response.text().context("Failed to get response text")?;
-
-    let document = Html::parse_document(&body);
-    Ok(document)
 }
 
-/// handle threads of each day found in the page.
-/// when `handle` returns `false`, the processing is stopped.
-fn for_each_thread(url: &str, mut handle: impl FnMut(EmailThread) -> bool) -> Result<()> {
-    let document = get_document(url)?;
-
-    // Find all elements
-    let h2_selector = Selector::parse("h2").unwrap();
-    // Next to h2, find table
-    let table_selector = Selector::parse("h2 + table").unwrap();
-    let mut table_iter = document.select(&table_selector);
-
-    // First find the date
-    for h2 in document.select(&h2_selector) {
-        let date_text = h2.text().collect::<String>();
-        if let Some(date) = transform_date(&date_text) {
-            if let Some(false) = table_iter
-                .next()
-                .map(|table| handle_table(&table, date, &mut handle))
-            {
-                break;
-            }
-        }
+impl ProgressObserver for ProgressLine {
+    fn page_fetched(&self, _: &str, _: Duration) {
+        self.count(&self.pages);
     }
-    Ok(())
-}
 
-// NaiveDateTime is copyable
-fn get_threads_between<T: PgMessage>(
-    start_date: NaiveDateTime,
-    end_date: NaiveDateTime,
-    mut handle: impl FnMut(EmailThread) -> Option<T>,
-) -> Result<Vec<T>> {
-    let mut start_date = start_date;
-    let mut threads: Vec<T> = Vec::new();
-
-    // we use following two variables to ensure we process each date fully and exactly once
-    let mut current_size = 0;
-    let mut prev_date = start_date
-        .checked_sub_signed(TimeDelta::seconds(1))
-        .unwrap();
-
-    // process all threads between, like 20250101-00:00:00 and 20250101-23:59:59
-    while start_date <= end_date {
-        println!("start_date={start_date:#?} end_date={end_date:#?}");
-
-        // if the start_date was processed already, we are done with all dates
-        if prev_date == start_date {
-            break;
-        }
-        prev_date = start_date;
+    fn thread_found(&self, _: &EmailThread) {
+        self.count(&self.threads);
+    }
 
-        let current_url = format!(
-            "{NEXT_THREADS_URL_PREFIX}/{}",
-            start_date.format("%Y%m%d%H%M")
-        );
+    fn detail_fetched(&self, _: &pgdevhub::EmailThreadDetail) {
+        self.count(&self.details);
+    }
 
-        // It is possbile that we get part of data in the last day in the current page and get the same
-        // part of data in the next page of the same day. For example, we get some threads published parallelly
-        // at 20250212-13:58, and get next page from '/list/pgsql-hackers/since/202502121358', then we will get
-        // the same threads again of time 20250212-13:58. We need to remove the duplicates.
-        let mut has_dups = true;
-        for_each_thread(&current_url, |thread| {
-            if has_dups {
-                for thr in threads.iter().rev() {
-                    if thr.id() == thread.id {
-                        has_dups = true;
-                        return true; // return early for next thread
-                    }
-                }
-                has_dups = false;
-            }
+    fn error(&self, message: &str) {
+        self.bar.println(format!("warning: {message}"));
+        self.count(&self.errors);
+    }
+}
 
-            start_date = thread.datetime;
+/// prints each thread written to it like [`print_thread`]
+struct PrintSink<'a> {
+    fetcher: &'a Fetcher,
+    template: Option<&'a template::Template>,
+}
 
-            // we only handle threads between start_date and end_date
-            let in_range = start_date <= end_date;
-            if in_range {
-                if let Some(thread) = handle(thread) {
-                    threads.push(thread);
-                }
-            }
-            in_range
-        })
-        .context("Failed to process email threads")?;
+impl ThreadSink for PrintSink<'_> {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        print_thread(self.fetcher, self.template, thread, thread);
+        Ok(())
+    }
 
-        // not get any new thread
-        if current_size == threads.len() {
-            break;
-        }
-        current_size += threads.len();
+    fn flush(&mut self) -> Result<()> {
+        use std::io::Write;
+        std::io::stdout().flush()?;
+        Ok(())
     }
-    Ok(threads)
 }
 
-// Get new subjects between start_day and end_day (inclusive)
-fn get_new_subjects_between(
-    start_date: NaiveDateTime,
-    end_date: NaiveDateTime,
-) -> Result<Vec<EmailThread>> {
-    get_threads_between(start_date, end_date, |thread| {
-        if is_thread_starter(&thread) {
-            Some(thread)
-        } else {
-            None
+/// print `thread` with the user's `template`, or else in its full `layout` followed by a blank
+/// line
+fn print_thread(
+    fetcher: &Fetcher,
+    template: Option<&template::Template>,
+    thread: &EmailThread,
+    layout: &dyn std::fmt::Display,
+) {
+    match template {
+        Some(template) => println!(
+            "{}",
+            template.render(thread, &fetcher.message_url(&thread.id))
+        ),
+        None => {
+            println!("{}", layout);
+            println!();
         }
-    })
+    }
 }
 
-/// active subject is the subject under discussion, including reply thread and new thread
-fn get_active_subjects_between(
-    start_date: NaiveDateTime,
-    end_date: NaiveDateTime,
-) -> Result<Vec<EmailThreadDetail>> {
-    let mut seen_ids = std::collections::HashSet::new();
-    get_threads_between(start_date, end_date, |thread| {
-        let id = get_thread_starter_id(&thread.id);
-        if seen_ids.contains(&id) {
-            None
-        } else {
-            let t = get_thread_by_id(&id);
-            seen_ids.insert(id);
-            Some(t)
-        }
-    })
+/// list the migrations the database at `url` has not been through, and apply them unless
+/// `dry_run`
+fn migrate(url: &str, dry_run: bool) -> Result<()> {
+    let pending = storage::pending_migrations(url)?;
+    if pending.is_empty() {
+        println!("The database is up to date");
+        return Ok(());
+    }
+    println!("Migrations to apply:");
+    for migration in &pending {
+        println!("{:>4} {}", migration.version, migration.description);
+    }
+    if !dry_run {
+        storage::open(url)?;
+        println!("Applied {} migrations", pending.len());
+    }
+    Ok(())
 }
 
-fn get_thread_by_id(id: &str) -> EmailThreadDetail {
-    let message_url = format!("{MESSAGE_URL_PREFIX}/{id}");
-    let doc = get_document(&message_url)
-        .context("failed to get the email")
-        .unwrap();
-
-    let table_tag_name = "#pgContentWrap table";
-    let table_tag = Selector::parse(table_tag_name).unwrap();
-    let select_tag = Selector::parse("select#thread_select").unwrap();
-    let option_tag = Selector::parse("option").unwrap();
-    let tr_tag = Selector::parse("tr").unwrap();
-    let td_tag = Selector::parse("td").unwrap();
-    let content_tag_name = "#pgContentWrap div.message-content";
-    let content_tag = Selector::parse(content_tag_name).unwrap();
-    let attchm_tag_name = "#pgContentWrap table.message-attachments";
-    let attchm_tag = Selector::parse(attchm_tag_name).unwrap();
-    let th_tag = Selector::parse("th").unwrap();
-    let a_tag = Selector::parse("a").unwrap();
-
-    let tr_elems: Vec<_> = doc
-        .select(&table_tag)
-        .next()
-        .context(format!("no tag '{table_tag_name}' found in the page"))
-        .unwrap()
-        .select(&tr_tag)
-        .collect();
-
-    let replies: Vec<_> = doc
-        .select(&select_tag)
-        .next()
-        .context("no 'select' tag in the page")
-        .unwrap()
-        .select(&option_tag)
-        .map(|opt_elem| opt_elem.value().attr("value").unwrap_or("").to_string())
-        .collect();
-
-    let content_elem = doc
-        .select(&content_tag)
-        .next()
-        .context(format!("no tag '{content_tag_name}' found"))
-        .unwrap();
-    let content = content_elem.inner_html();
-
-    let mut attachments = Vec::new();
-    if let Some(attchm_elem) = doc.select(&attchm_tag).next() {
-        for att in attchm_elem.select(&th_tag) {
-            if let Some(link) = att.select(&a_tag).next() {
-                attachments.push(ThreadAttachment {
-                    name: link.text().collect::<String>().trim().to_string(),
-                    href: link.value().attr("href").unwrap_or("").to_string(),
-                });
-            }
-        }
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let filter = tracing_subscriber::EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .init();
+    let mut settings = Config::load(cli.config.as_deref())?;
+    if cli.list.is_some() {
+        settings.list = cli.list;
     }
-
-    let (from_elem, subject_elem, datetime_elem) = if tr_elems.len() == 8 {
-        (tr_elems[0], tr_elems[2], tr_elems[3])
-    } else if tr_elems.len() == 9 {
-        (tr_elems[0], tr_elems[3], tr_elems[4])
-    } else {
-        panic!("the table has neither 8 or 9 rows");
+    let mut config = settings.fetch_config();
+    if let Some(concurrency) = cli.attachment_concurrency {
+        config.attachment_concurrency = concurrency;
+    }
+    if let Some(rate) = cli.attachment_rate {
+        config.attachment_rate = Some(rate);
+    }
+    if let Some(concurrency) = cli.list_concurrency {
+        config.list_concurrency = concurrency;
+    }
+    config.http_version = cli.http_version;
+    if let Some(timeout) = cli.timeout {
+        config.request_timeout = Some(Duration::from_secs(timeout));
+    }
+    if let Some(user_agent) = cli.user_agent {
+        config.user_agent = user_agent;
+    }
+    if let Some(delay) = cli.min_delay_between_requests {
+        config.min_delay_between_requests = Some(Duration::from_millis(delay));
+    }
+    if let Some(rate) = cli.request_rate {
+        config.request_rate = Some(rate);
+    }
+    if let Some(burst) = cli.request_burst {
+        config.request_burst = burst;
+    }
+    config.max_total_requests = cli.max_total_requests;
+    config.max_total_bytes = cli.max_total_bytes;
+    config.prefetch_pages = cli.prefetch_pages;
+    config.since_overlap = Duration::from_secs(cli.since_overlap * 60);
+    config.extra_headers.extend(cli.headers);
+    config.cookie_store = cli.cookies;
+    let mut fetcher = settings.fetcher(config);
+    if cli.print_since_urls {
+        fetcher = fetcher.record_since_urls();
+    }
+    if cli.include_notices {
+        fetcher = fetcher.include_notices();
+    }
+    if cli.thread_by_headers {
+        fetcher = fetcher.thread_by_headers();
+    }
+    let network: Box<dyn http::HttpFetcher> = match cli.http_cache.or(settings.http_cache()?) {
+        Some(dir) => Box::new(http::Cached::new(&dir)),
+        None => Box::new(http::Network),
+    };
+    fetcher = match &cli.record {
+        Some(dir) => fetcher.with_http(http::Record::new(network, dir)),
+        None => fetcher.with_http(network),
     };
-    let td_elem = from_elem.select(&td_tag).next().unwrap();
-    let author_details = td_elem.text().collect::<String>().trim().to_string();
-    let mut author_details = author_details.split('<');
-    let author_name = author_details.next().unwrap_or("").trim().to_string();
-    let author_email = author_details
-        .next()
-        .unwrap_or("")
-        .trim_end_matches(">")
-        .replace("(dot)", ".")
-        .replace("(at)", "@");
-
-    let td_elem = subject_elem.select(&td_tag).next().unwrap();
-    let subject = clean_subject_title(td_elem.text().collect::<String>().trim());
-
-    let td_elem = datetime_elem.select(&td_tag).next().unwrap();
-    let datetime_str = td_elem.text().collect::<String>().trim().to_string();
-    let datetime = NaiveDateTime::parse_from_str(&datetime_str, "%Y-%m-%d %H:%M:%S")
-        .context("invalid datetime format")
-        .unwrap();
-
-    EmailThreadDetail {
-        id: id.to_string(),
-        subject,
-        datetime,
-        author_name,
-        author_email,
-        content,
-        attachments,
-        replies,
+    if let Some(dir) = &cli.replay {
+        fetcher = fetcher.with_http(http::Replay::new(dir));
     }
-}
-
-fn is_thread_starter(thread: &EmailThread) -> bool {
-    if thread.subject.starts_with("Re:")
-        || thread.subject.starts_with("re:")
-        || thread.subject.starts_with("RE:")
-        || thread.subject.starts_with("rE:")
-    {
-        return false;
+    if let Some(path) = cli.starter_cache.or(settings.starter_cache()?) {
+        fetcher = fetcher.with_starter_cache(&path)?;
     }
-
-    if thread.subject.starts_with("Re：")
-        || thread.subject.starts_with("re：")
-        || thread.subject.starts_with("RE：")
-        || thread.subject.starts_with("rE：")
-    {
-        return false;
+    let store_url = cli.store.or(settings.database.clone());
+    // before the database is opened, which migrates it
+    if let Some(Command::Migrate { dry_run }) = cli.command {
+        let url = store_url
+            .as_deref()
+            .context("migrate needs a database, give --store or set database in the config")?;
+        return migrate(url, dry_run);
     }
-
-    if !thread.subject.to_lowercase().contains("re:") {
-        return true;
+    let store = store_url.as_deref().map(storage::open).transpose()?;
+    #[cfg(feature = "tantivy")]
+    let store = match (store, &cli.search_index) {
+        (Some(store), Some(dir)) => {
+            Some(std::sync::Arc::new(storage::Tantivy::open(dir, store)?) as _)
+        }
+        (None, Some(_)) => anyhow::bail!("--search-index needs a database to index, give --store"),
+        (store, None) => store,
+    };
+    if let Some(store) = &store {
+        fetcher = fetcher.with_store(store.clone());
+    }
+    let progress = cli.progress.then(indicatif::ProgressBar::new_spinner);
+    if let Some(bar) = &progress {
+        fetcher = fetcher.with_progress(ProgressLine::new(bar.clone()));
     }
 
-    is_thread_starter_by_id(&thread.id)
-}
-
-#[allow(unused)]
-fn get_subject_thread_id_list(id: &str) -> Result<Vec<String>> {
-    let message_url = format!("{MESSAGE_URL_PREFIX}/{id}");
-    let select_tag = Selector::parse("select#thread_select").unwrap();
-    let option_tag = Selector::parse("option").unwrap();
-
-    get_document(&message_url)
-        .context("failed to get document")
-        .unwrap()
-        .select(&select_tag)
-        .next()
-        .context("no 'select' tag in the page")
-        .and_then(|select| {
-            Ok(select
-                .select(&option_tag)
-                .map(|opt_elem| opt_elem.value().attr("value").unwrap_or("").to_string())
-                .collect::<Vec<_>>())
-        })
-}
-
-fn get_thread_starter_id(id: &str) -> String {
-    let message_url = format!("{MESSAGE_URL_PREFIX}/{id}");
-    let select_tag = Selector::parse("select#thread_select").unwrap();
-    let option_tag = Selector::parse("option").unwrap();
-
-    get_document(&message_url)
-        .context("failed to get document")
-        .unwrap()
-        .select(&select_tag)
-        .next()
-        .context("no 'select' tag in the page")
-        .unwrap()
-        .select(&option_tag)
-        .next()
-        .context("no 'option' tag in 'select' tag")
-        .unwrap()
-        .value()
-        .attr("value")
-        .map(|value| value.to_string())
-        .context("no 'value' tag in the 'option' tag")
-        .unwrap()
-}
-
-fn is_thread_starter_by_id(id: &str) -> bool {
-    get_thread_starter_id(id) == id
-}
-
-fn main() -> Result<()> {
-    use chrono::Local;
-
-    let args: Vec<_> = std::env::args().collect();
-    let get_active = args.len() == 2 && args[1] == "active";
-
-    if get_active {
-        let end_date = Local::now().naive_local();
-        let start_date = end_date - TimeDelta::days(1);
-
-        println!(
-            "Fetching all subjects under discussion from {} to {}",
-            start_date, end_date
-        );
-        let thread_emails = get_active_subjects_between(start_date, end_date)?;
-        println!("----------------------------");
-        for thread in thread_emails {
-            println!("{}", thread);
-            println!();
+    let command = cli.command.unwrap_or(Command::New {
+        days: 7,
+        newest_first: false,
+        reply_counts: false,
+        output_replies: false,
+        range_by: RangeBy::ThreadStart,
+        unseen: false,
+        output: Output::Text,
+        #[cfg(feature = "parquet")]
+        out_parquet: None,
+    });
+    match command {
+        Command::Browse { since } => {
+            let since = since.unwrap_or_else(|| fetcher.last(TimeDelta::days(1)).0);
+            browse(&fetcher, since, std::io::stdin().lock(), std::io::stdout()).await?;
+        }
+        Command::Sync { days } => {
+            let store = store
+                .as_deref()
+                .context("sync needs a database, give --store or set database in the config")?;
+            let (first_since, _) = fetcher.last(TimeDelta::days(days.into()));
+            for thread in sync(&fetcher, store, first_since).await? {
+                print_thread(&fetcher, cli.template.as_ref(), &thread, &thread);
+            }
+        }
+        Command::Backfill { from, to } => {
+            let store = store
+                .as_deref()
+                .context("backfill needs a database, give --store or set database in the config")?;
+            let until = match to.as_str() {
+                "now" => fetcher.now(),
+                day => day
+                    .parse::<NaiveDate>()
+                    .with_context(|| format!("bad --to '{day}', give a day like 2025-01-01"))?
+                    .into(),
+            };
+            let kept = backfill(&fetcher, store, from.into(), until).await?;
+            println!("Kept {kept} threads from {from} to {until}");
+        }
+        Command::Import { files } => {
+            let store = store
+                .as_deref()
+                .context("import needs a database, give --store or set database in the config")?;
+            for path in files {
+                let imported =
+                    import::import_mbox(&path, settings.list.unwrap_or_default(), store)?;
+                println!(
+                    "Imported {} messages in {} threads from {}",
+                    imported.messages,
+                    imported.threads,
+                    path.display()
+                );
+                if imported.skipped > 0 {
+                    println!(
+                        "Skipped {} messages without a Message-ID or unreadable",
+                        imported.skipped
+                    );
+                }
+            }
+        }
+        Command::Search { query, days } => {
+            let store = store
+                .as_deref()
+                .context("search needs a database, give --store or set database in the config")?;
+            let (start_date, end_date) = match days {
+                Some(days) => fetcher.last(TimeDelta::days(days.into())),
+                None => (DateTime::UNIX_EPOCH.naive_utc(), fetcher.now()),
+            };
+            for thread in store.search(&query, settings.list, start_date, end_date)? {
+                print_thread(&fetcher, cli.template.as_ref(), &thread, &thread);
+            }
         }
-    } else {
-        let end_date = Local::now().naive_local();
-        let start_date = end_date - TimeDelta::days(7);
+        Command::Migrate { .. } => unreachable!("migrate runs before the database is opened"),
+        Command::Serve {
+            bind,
+            schedule,
+            base_path,
+            static_dir,
+            digest_state,
+            response_cache,
+            listing_ttl,
+            thread_ttl,
+            warm_every,
+        } => {
+            if let Some(schedule) = schedule {
+                let schedule = scheduler::parse_schedule(&schedule)?;
+                let fetcher = fetcher.clone();
+                let mut state = match &digest_state {
+                    Some(path) => digest::DigestState::load(path, fetcher.now())?,
+                    None => digest::DigestState::new(fetcher.now()),
+                };
+                // the scheduler runs the digests on a thread of its own
+                let runtime = tokio::runtime::Handle::current();
+                scheduler::spawn(schedule, move |time| {
+                    let time = time.naive_local();
+                    println!("New subjects between {} and {time}:", state.watermark());
+                    let delivered =
+                        runtime.block_on(digest::run(&fetcher, &mut state, time, |thread| {
+                            println!("{thread}");
+                            Ok(())
+                        }));
+                    if let Err(err) = delivered {
+                        tracing::error!("scheduled digest failed: {err:#}");
+                    }
+                });
+            }
+            let bind = bind.or(settings.bind.clone());
+            let caching = response_cache
+                .or(settings.response_cache.clone())
+                .map(|url| -> Result<_> {
+                    let mut caching = api::cache::ResponseCaching::new(api::cache::open(&url)?);
+                    if let Some(secs) = listing_ttl.or(settings.listing_ttl_secs) {
+                        caching.listing_ttl = Duration::from_secs(secs);
+                    }
+                    if let Some(secs) = thread_ttl.or(settings.thread_ttl_secs) {
+                        caching.thread_ttl = Duration::from_secs(secs);
+                    }
+                    Ok(caching)
+                })
+                .transpose()?;
+            let warm_every = match warm_every.or(settings.warm_every_secs) {
+                Some(0) => None,
+                Some(secs) => Some(Duration::from_secs(secs)),
+                None => Some(api::warm::REFRESH_EVERY),
+            };
+            api::serve(
+                bind.as_deref().unwrap_or("127.0.0.1:3000"),
+                fetcher.clone(),
+                base_path.as_deref(),
+                static_dir.as_deref(),
+                caching,
+                warm_every,
+            )
+            .await?;
+        }
+        Command::Replay { tokens } => {
+            let threads = replay_since_pages(&fetcher, &tokens, Some).await?;
+            println!("----------------------------");
+            for thread in threads {
+                print_thread(&fetcher, cli.template.as_ref(), &thread, &thread);
+            }
+        }
+        Command::Attachments {
+            days,
+            download_attachments: download_dir,
+            attachment_ext,
+        } => {
+            let (start_date, end_date) = fetcher.last(TimeDelta::days(days.into()));
+            println!("Fetching the attachments posted from {start_date} to {end_date}");
+            let (ids, attachments): (Vec<_>, Vec<_>) =
+                attachments_between(&fetcher, start_date, end_date)
+                    .await?
+                    .into_iter()
+                    .unzip();
+            println!("----------------------------");
+            for (id, attachment) in ids.iter().zip(&attachments) {
+                println!(
+                    "{}\t{}\t{}",
+                    fetcher.message_url(&id.to_string()),
+                    attachment.name,
+                    fetcher.attachment_url(attachment)
+                );
+            }
+            if let Some(dir) = download_dir {
+                let saved = save_attachments(&fetcher, &attachments, &dir, &attachment_ext).await?;
+                println!("Saved {saved} attachments to {}", dir.display());
+            }
+        }
+        Command::Graph {
+            starter_id,
+            max_messages,
+        } => {
+            print!(
+                "{}",
+                thread_to_dot(&fetcher, &starter_id, max_messages).await?
+            );
+        }
+        Command::Mbox { starter_id, out } => {
+            archive_thread_mbox(&fetcher, &starter_id.to_string(), &out).await?;
+            println!("Saved the thread of {starter_id} to {}", out.display());
+        }
+        Command::Export {
+            ids,
+            days,
+            format,
+            out,
+        } => {
+            let ids = match ids.is_empty() {
+                false => ids,
+                true => {
+                    let (start_date, end_date) = fetcher.last(TimeDelta::days(days.into()));
+                    get_new_subjects_between(&fetcher, start_date, end_date)
+                        .await?
+                        .iter()
+                        .map(|thread| MessageId::parse(&thread.id))
+                        .collect::<Result<_>>()?
+                }
+            };
+            let written = match (format, &out) {
+                (ExportFormat::Maildir, Some(dir)) => {
+                    export_threads_maildir(&fetcher, &ids, dir).await?
+                }
+                (ExportFormat::Maildir, None) => unreachable!("clap requires --out for maildir"),
+                (ExportFormat::Mbox, Some(path)) => {
+                    let mut file = std::io::BufWriter::new(
+                        std::fs::File::create(path)
+                            .with_context(|| format!("failed to create {}", path.display()))?,
+                    );
+                    let written = export_threads_mbox(&fetcher, &ids, &mut file).await?;
+                    std::io::Write::flush(&mut file)?;
+                    written
+                }
+                (ExportFormat::Mbox, None) => {
+                    export_threads_mbox(&fetcher, &ids, &mut std::io::stdout().lock()).await?
+                }
+            };
+            tracing::info!("exported {written} messages of {} threads", ids.len());
+        }
+        Command::Watch {
+            starter_id,
+            interval,
+        } => {
+            println!(
+                "Watching thread {starter_id} for new replies every {interval} seconds, \
+                press Enter to stop"
+            );
+            let handle = watch_thread(
+                &fetcher,
+                &starter_id,
+                Duration::from_secs(interval),
+                |reply| {
+                    println!("----------------------------");
+                    println!("{}", reply);
+                    for attachment in &reply.attachments {
+                        println!(
+                            "Attachment: {} ({PG_SITE}{})",
+                            attachment.name, attachment.href
+                        );
+                    }
+                    println!();
+                },
+            )
+            .await?;
+            std::io::stdin().read_line(&mut String::new())?;
+            handle.stop().await;
+        }
+        Command::Active {
+            hours,
+            verify_datetimes,
+            output,
+        } => {
+            if verify_datetimes {
+                fetcher = fetcher.verify_datetimes();
+            }
+            let (start_date, end_date) = fetcher.last(TimeDelta::hours(hours.into()));
+
+            match output.records() {
+                None => {
+                    println!(
+                        "Fetching all subjects under discussion from {} to {}",
+                        start_date, end_date
+                    );
+                    let thread_emails =
+                        get_active_subjects_between(&fetcher, start_date, end_date).await?;
+                    println!("----------------------------");
+                    for thread in thread_emails {
+                        print_thread(&fetcher, cli.template.as_ref(), &thread.summary(), &thread);
+                    }
+                }
+                Some(format) => {
+                    let stdout = std::io::BufWriter::new(std::io::stdout());
+                    let mut writer = RecordWriter::new(format, stdout);
+                    for thread in
+                        get_active_subjects_between(&fetcher, start_date, end_date).await?
+                    {
+                        writer.write_message(&thread)?;
+                    }
+                    writer.flush()?;
+                }
+            }
+        }
+        Command::New {
+            days,
+            newest_first,
+            reply_counts,
+            output_replies,
+            range_by,
+            unseen,
+            output,
+            #[cfg(feature = "parquet")]
+            out_parquet,
+        } => {
+            let (start_date, end_date) = fetcher.last(TimeDelta::days(days.into()));
+
+            // only the records go to standard output when it is read by another program
+            let text = output == Output::Text;
+            if text {
+                println!(
+                    "Fetching new topics of the last {days} days from {} to {}",
+                    start_date, end_date
+                );
+            }
+            let mut sinks: Vec<Box<dyn ThreadSink>> = match output.records() {
+                None => vec![Box::new(PrintSink {
+                    fetcher: &fetcher,
+                    template: cli.template.as_ref(),
+                })],
+                Some(format) => vec![Box::new(RecordWriter::new(
+                    format,
+                    std::io::BufWriter::new(std::io::stdout()),
+                ))],
+            };
+            #[cfg(feature = "parquet")]
+            if let Some(path) = &out_parquet {
+                sinks.push(Box::new(pgdevhub::parquet_sink::ParquetSink::create(path)?));
+            }
+            if let Some(store) = &store {
+                sinks.push(Box::new(&**store as &dyn Storage));
+            }
+            let mut sinks: Box<dyn ThreadSink> = if unseen {
+                let store = store.as_deref().context(
+                    "--unseen needs a database, give --store or set database in the config",
+                )?;
+                Box::new(Unseen::new(store, sinks))
+            } else {
+                Box::new(sinks)
+            };
+            // the subjects can be written as they are found, unless they all have to be there
+            // first to be ordered or to get their replies fetched in a batch
+            if newest_first || output_replies || reply_counts {
+                let mut thread_emails = if newest_first {
+                    let listed = get_threads_between_newest_first(
+                        &fetcher,
+                        start_date,
+                        end_date,
+                        |thread| Ok(Some(thread)),
+                    )
+                    .await?;
+                    let mut new = Vec::new();
+                    for thread in listed {
+                        if is_new_subject(&fetcher, &thread, range_by).await? {
+                            new.push(thread);
+                        }
+                    }
+                    new
+                } else {
+                    get_new_subjects_between_by(&fetcher, start_date, end_date, range_by).await?
+                };
+                if output_replies {
+                    add_replies(&fetcher, &mut thread_emails).await;
+                } else if reply_counts {
+                    add_reply_counts(&fetcher, &mut thread_emails).await;
+                }
+                if text {
+                    println!("----------------------------");
+                }
+                for thread in &thread_emails {
+                    sinks.write(thread)?;
+                }
+                sinks.flush()?;
+            } else {
+                if text {
+                    println!("----------------------------");
+                }
+                write_new_subjects_between(&fetcher, start_date, end_date, range_by, &mut *sinks)
+                    .await?;
+            }
+            #[cfg(feature = "parquet")]
+            if let Some(path) = out_parquet.filter(|_| text) {
+                println!("Wrote the subjects to {}", path.display());
+            }
+        }
+    }
 
-        println!(
-            "Fetching new topics for last week from {} to {}",
-            start_date, end_date
-        );
-        let thread_emails = get_new_subjects_between(start_date, end_date)?;
-        println!("----------------------------");
-        for thread in thread_emails {
-            println!("{}", thread);
-            println!();
+    if let Some(bar) = progress {
+        bar.finish_and_clear();
+    }
+    if cli.print_since_urls {
+        println!("Visited since pages:");
+        for url in fetcher.since_urls() {
+            println!("{url}");
         }
     }
     Ok(())
 }
 
 #[test]
-fn test1() {
-    // has Chinese ':' in the subject title, like this: 'Re：Limit length of queryies in pg_stat_statement extension'
-    let start_day = "20250118";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    println!("Fetching emails from: {} ~ {}", start_date, end_date);
-    let thread_emails = get_new_subjects_between(start_date.into(), end_date).unwrap();
-    assert!(thread_emails.len() == 1);
-
-    println!("\nFirst emails in each thread:");
-    println!("----------------------------");
-    for thread in thread_emails {
-        println!("{}", thread);
-        println!();
-    }
+fn parse_command_line_headers() {
+    let (name, value) = parse_header("X-Archive-Token: t0ken").unwrap();
+    assert_eq!(name, "x-archive-token");
+    assert_eq!(value, "t0ken");
+    assert!(parse_header("no colon").is_err());
 }
 
 #[test]
-fn test2() {
-    // has Re: in subject title, like this: 'Fwd: Re: A new look at old NFS readdir() problems?'
-    let start_day = "20250102";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    println!("Fetching emails from: {} ~ {}", start_date, end_date);
-    let thread_emails = get_new_subjects_between(start_date.into(), end_date).unwrap();
-    assert!(thread_emails
-        .iter()
-        .any(|thread| thread.subject.contains("Re:")));
-
-    println!("\nFirst emails in each thread:");
-    println!("----------------------------");
-    for thread in thread_emails {
-        println!("{}", thread);
-        println!();
-    }
+fn active_hours_are_a_positive_span() {
+    let hours = |value: &str| match Cli::try_parse_from(["pgdevhub", "active", "--hours", value]) {
+        Result::Ok(Cli {
+            command: Some(Command::Active { hours, .. }),
+            ..
+        }) => Some(hours),
+        _ => None,
+    };
+    assert_eq!(hours("48"), Some(48));
+    assert_eq!(hours("0"), None);
+    assert_eq!(hours("-3"), None);
+    assert_eq!(hours("99999999999999"), None);
+
+    let fetcher = pgdevhub::Fetcher::default();
+    let (start, _) = fetcher.last(TimeDelta::hours(u32::MAX.into()));
+    assert_eq!(start, chrono::NaiveDateTime::MIN);
 }
 
 #[test]
-fn test3() {
-    // has unicode emoji and '\n' in the subject title
-    let start_day = "20250106";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    println!("Fetching emails from: {} ~ {}", start_date, end_date);
-    let thread_emails = get_new_subjects_between(start_date.into(), end_date).unwrap();
-    assert!(thread_emails
-        .iter()
-        .any(|thread| !thread.subject.contains('\n')));
-
-    println!("\nFirst emails in each thread:");
-    println!("----------------------------");
-    for thread in thread_emails {
-        println!("{}", thread);
-        println!();
+fn days_are_a_positive_span() {
+    let days = |args: &[&str]| {
+        let command = Cli::try_parse_from(["pgdevhub"].iter().chain(args))
+            .ok()?
+            .command;
+        match command? {
+            Command::New { days, .. }
+            | Command::Sync { days }
+            | Command::Attachments { days, .. }
+            | Command::Export { days, .. } => Some(days),
+            Command::Search { days, .. } => days,
+            _ => None,
+        }
+    };
+    for command in ["new", "sync", "attachments", "export"] {
+        assert_eq!(days(&[command, "--days", "3"]), Some(3), "{command}");
+        assert_eq!(days(&[command, "--days", "0"]), None, "{command}");
+        assert_eq!(days(&[command, "--days", "-3"]), None, "{command}");
+        assert_eq!(
+            days(&[command, "--days", "999999999999999"]),
+            None,
+            "{command}"
+        );
     }
-}
+    assert_eq!(days(&["search", "io_uring", "--days", "3"]), Some(3));
+    assert_eq!(days(&["search", "io_uring", "--days", "0"]), None);
+    assert_eq!(days(&["search", "io_uring", "--days", "-3"]), None);
 
-#[test]
-fn test4() {
-    let start_day = "20240104";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    let thread_emails_20240104 = get_new_subjects_between(start_date.into(), end_date).unwrap();
-    let start_day = "20240105";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    let thread_emails_20240105 = get_new_subjects_between(start_date.into(), end_date).unwrap();
-    let start_day = "20240106";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_date = start_date.and_hms_opt(23, 59, 59).unwrap();
-    let thread_emails_20240106 = get_new_subjects_between(start_date.into(), end_date).unwrap();
-
-    let start_day = "20240104";
-    let start_date = NaiveDate::parse_from_str(&start_day, "%Y%m%d").unwrap();
-    let end_day = "20240106";
-    let end_date = NaiveDate::parse_from_str(&end_day, "%Y%m%d").unwrap();
-    let end_date = end_date.and_hms_opt(23, 59, 59).unwrap();
-    let thread_emails = get_new_subjects_between(start_date.into(), end_date).unwrap();
-
-    assert!(
-        thread_emails_20240104.len() + thread_emails_20240105.len() + thread_emails_20240106.len()
-            == thread_emails.len()
-    );
-    assert!(thread_emails.iter().all(|thread| {
-        thread_emails_20240104.iter().any(|t| t.id == thread.id)
-            || thread_emails_20240105.iter().any(|t| t.id == thread.id)
-            || thread_emails_20240106.iter().any(|t| t.id == thread.id)
-    }));
+    let fetcher = pgdevhub::Fetcher::default();
+    let (start, _) = fetcher.last(TimeDelta::days(u32::MAX.into()));
+    assert_eq!(start, chrono::NaiveDateTime::MIN);
 }
 
 #[test]
-fn get_email_thread_detail() {
-    let detail = get_thread_by_id(
-        "CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com",
-    );
-    println!("{detail:#?}");
-    assert_eq!(
-        detail.id,
-        "CAHv8RjKhA%3D_h5vAbozzJ1Opnv%3DKXYQHQ-fJyaMfqfRqPpnC2bA%40mail.gmail.com"
-    );
-    assert_eq!(detail.subject, "Enhance 'pg_createsubscriber' to retrieve databases automatically when no database is provided.");
-
-    assert_eq!(
-        detail.datetime.format("%Y-%m-%d %H:%M:%S").to_string(),
-        "2025-01-22 13:59:09"
-    );
-    assert_eq!(detail.author_name, "Shubham Khanna");
-    assert_eq!(detail.author_email, "khannashubham1197@gmail.com");
-    assert!(detail.content.contains("<br>"));
-    assert_eq!(detail.attachments.len(), 1);
-    assert_eq!(
-        detail.attachments[0].name,
-        "v1-0001-Enhance-pg_createsubscriber-to-fetch-and-append-a.patch"
-    );
-    assert_eq!(detail.attachments[0].href, "/message-id/attachment/170920/v1-0001-Enhance-pg_createsubscriber-to-fetch-and-append-a.patch");
-    assert_eq!(detail.replies.len(), 34);
+fn parse_command_line_rates() {
+    assert_eq!(parse_rate("2.5").unwrap(), 2.5);
+    for rate in ["0", "-1", "NaN", "inf", "fast"] {
+        assert!(parse_rate(rate).is_err(), "{rate}");
+    }
 }
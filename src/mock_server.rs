@@ -0,0 +1,349 @@
+//! A tiny HTTP/1.1 server plus page builders mimicking the postgresql.org archive markup, so the
+//! scraper can be exercised without touching the real site.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+#[derive(Debug, Clone)]
+pub struct MockRequest {
+    pub path: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockRequest {
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl MockResponse {
+    pub fn new(status: u16, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        MockResponse {
+            status,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.into(),
+        }
+    }
+
+    pub fn html(body: impl Into<Vec<u8>>) -> Self {
+        MockResponse::new(200, "text/html; charset=utf-8", body)
+    }
+
+    pub fn not_found() -> Self {
+        MockResponse::new(404, "text/plain", "not found")
+    }
+
+    /// no response at all, the connection is closed once the request is read, like a server
+    /// that went down
+    pub fn disconnect() -> Self {
+        MockResponse::new(0, "", "")
+    }
+
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        self.headers.push((name.to_string(), value.to_string()));
+        self
+    }
+}
+
+type Handler = dyn Fn(&MockRequest) -> MockResponse + Send + Sync;
+
+/// serves every request with `handler`, one thread per connection, honoring keep-alive.
+/// all requests and accepted connections are recorded for assertions.
+pub struct MockServer {
+    port: u16,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+    connections: Arc<AtomicUsize>,
+    shutdown: Arc<AtomicBool>,
+}
+
+impl MockServer {
+    pub fn start(handler: impl Fn(&MockRequest) -> MockResponse + Send + Sync + 'static) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handler: Arc<Handler> = Arc::new(handler);
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let connections = Arc::new(AtomicUsize::new(0));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let (requests_, connections_, shutdown_) =
+            (requests.clone(), connections.clone(), shutdown.clone());
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                if shutdown_.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else { continue };
+                connections_.fetch_add(1, Ordering::SeqCst);
+                let (handler, requests) = (handler.clone(), requests_.clone());
+                thread::spawn(move || serve_connection(stream, handler, requests));
+            }
+        });
+
+        MockServer {
+            port,
+            requests,
+            connections,
+            shutdown,
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        self.requests().into_iter().map(|req| req.path).collect()
+    }
+
+    pub fn hits(&self, path: &str) -> usize {
+        self.paths().iter().filter(|p| *p == path).count()
+    }
+
+    pub fn connections(&self) -> usize {
+        self.connections.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // wake up the accept loop so it can notice the shutdown flag
+        let _ = TcpStream::connect(("127.0.0.1", self.port));
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    handler: Arc<Handler>,
+    requests: Arc<Mutex<Vec<MockRequest>>>,
+) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut writer = stream;
+    loop {
+        let mut request_line = String::new();
+        if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+            return;
+        }
+        // every request the scraper makes is a GET, only its target matters
+        let mut parts = request_line.split_whitespace().skip(1);
+        let path = parts.next().unwrap_or("").to_string();
+
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            if reader.read_line(&mut line).unwrap_or(0) == 0 {
+                return;
+            }
+            let line = line.trim_end();
+            if line.is_empty() {
+                break;
+            }
+            if let Some((name, value)) = line.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+
+        let mut request = MockRequest {
+            path,
+            headers,
+            body: Vec::new(),
+        };
+        let body_len = request
+            .header("content-length")
+            .and_then(|len| len.parse().ok())
+            .unwrap_or(0);
+        request.body = vec![0; body_len];
+        if reader.read_exact(&mut request.body).is_err() {
+            return;
+        }
+        let close = request
+            .header("connection")
+            .is_some_and(|value| value.eq_ignore_ascii_case("close"));
+        requests.lock().unwrap().push(request.clone());
+
+        let response = handler(&request);
+        if response.status == 0 {
+            return;
+        }
+        let mut head = format!("HTTP/1.1 {} Mock\r\n", response.status);
+        for (name, value) in &response.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str(&format!("Content-Length: {}\r\n\r\n", response.body.len()));
+        if writer.write_all(head.as_bytes()).is_err()
+            || writer.write_all(&response.body).is_err()
+            || close
+        {
+            return;
+        }
+    }
+}
+
+/// a message page as rendered under `/message-id/<id>`
+pub struct MessagePage {
+    pub subject: String,
+    pub from: String,
+    pub date: String,
+    pub cc: Option<String>,
+    // rendered without a div.message-content when `None`, like some moderation notices
+    pub content: Option<String>,
+    // name, href and size as shown in the size column
+    pub attachments: Vec<(String, String, String)>,
+    pub thread: Vec<String>,
+}
+
+impl MessagePage {
+    pub fn new(id: &str) -> Self {
+        MessagePage {
+            subject: format!("Subject of {id}"),
+            from: "Jane Doe <jane(dot)doe(at)example(dot)org>".to_string(),
+            date: "2025-01-22 13:59:09".to_string(),
+            cc: None,
+            content: Some("<p>Hello hackers,<br>here is a patch.</p>".to_string()),
+            attachments: Vec::new(),
+            thread: vec![id.to_string()],
+        }
+    }
+
+    pub fn thread(mut self, ids: &[&str]) -> Self {
+        self.thread = ids.iter().map(|id| id.to_string()).collect();
+        self
+    }
+
+    pub fn render(&self) -> String {
+        let options: String = self
+            .thread
+            .iter()
+            .map(|id| format!("<option value=\"{id}\">{id}</option>"))
+            .collect();
+        let views = [
+            "<tr><th>Views:</th><td>Raw Message | Whole Thread</td></tr>".to_string(),
+            format!(
+                "<tr><th>Thread:</th><td><select id=\"thread_select\">{options}</select></td></tr>"
+            ),
+        ];
+        format!(
+            "<html><body><div id=\"pgContentWrap\">{}</div></body></html>",
+            self.message("message-id", &views)
+        )
+    }
+
+    /// the header table, content and attachments of the message, with `extra_rows` after its
+    /// Message-ID row
+    fn message(&self, message_id: &str, extra_rows: &[String]) -> String {
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+        let mut rows = vec![
+            format!("<tr><th>From:</th><td>{}</td></tr>", escape(&self.from)),
+            "<tr><th>To:</th><td>pgsql-hackers(at)postgresql(dot)org</td></tr>".to_string(),
+        ];
+        if let Some(cc) = &self.cc {
+            rows.push(format!("<tr><th>Cc:</th><td>{}</td></tr>", escape(cc)));
+        }
+        rows.extend([
+            format!(
+                "<tr><th>Subject:</th><td>{}</td></tr>",
+                escape(&self.subject)
+            ),
+            format!("<tr><th>Date:</th><td>{}</td></tr>", self.date),
+            format!("<tr><th>Message-ID:</th><td>{message_id}</td></tr>"),
+        ]);
+        rows.extend_from_slice(extra_rows);
+        rows.push("<tr><th>Lists:</th><td>pgsql-hackers</td></tr>".to_string());
+        let attachments: String = self
+            .attachments
+            .iter()
+            .map(|(name, href, size)| {
+                format!(
+                    "<tr><th><a href=\"{href}\">{name}</a></th>\
+                     <td>text/x-patch</td><td>{size}</td></tr>"
+                )
+            })
+            .collect();
+        let attachments = if attachments.is_empty() {
+            String::new()
+        } else {
+            format!(
+                "<table class=\"message-attachments\">\
+                 <thead><tr><th>Attachment</th><th>Content-Type</th><th>Size</th></tr></thead>\
+                 <tbody>{attachments}</tbody></table>"
+            )
+        };
+        let content = self
+            .content
+            .as_ref()
+            .map(|content| format!("<div class=\"message-content\">{content}</div>"))
+            .unwrap_or_default();
+        format!(
+            "<table class=\"message-header\">{}</table>{content}{attachments}",
+            rows.concat(),
+        )
+    }
+}
+
+/// the flat view of a thread, each of `messages` with its message-id linked in its header
+pub fn flat_page(messages: &[(&str, &MessagePage)]) -> String {
+    let messages: String = messages
+        .iter()
+        .map(|(id, page)| {
+            let link = format!(
+                "<a href=\"/message-id/{}\">{id}</a>",
+                id.replace('@', "%40")
+            );
+            format!("<a name=\"{id}\"></a>{}", page.message(&link, &[]))
+        })
+        .collect();
+    format!("<html><body><div id=\"pgContentWrap\">{messages}</div></body></html>")
+}
+
+/// what the archive shows under `/message-id/<id>` for an id it does not know, in the site's
+/// layout but without any of a message page's parts
+pub fn message_not_found_page() -> String {
+    "<html><head><title>PostgreSQL: Message not found</title></head><body>\
+     <div id=\"pgContentWrap\">\
+     <h1>Message not found</h1>\
+     <p>The specified message was not found.</p>\
+     </div></body></html>"
+        .to_string()
+}
+
+/// `(id, subject, author, HH:MM)` of a since-listing row
+pub type ListingRow<'a> = (&'a str, &'a str, &'a str, &'a str);
+
+/// a since-listing page with one `(date header, rows)` entry per day
+pub fn listing_page(days: &[(&str, &[ListingRow])]) -> String {
+    let mut html = String::from("<html><body><div id=\"pgContentWrap\">");
+    for (date, rows) in days {
+        html.push_str(&format!("<h2>{date}</h2><table>"));
+        html.push_str("<tr><th>Subject</th><th>Author</th><th>Time</th></tr>");
+        for (id, subject, author, time) in rows.iter() {
+            html.push_str(&format!(
+                "<tr><th><a href=\"/message-id/{id}\">{subject}</a></th><td>{author}</td><td>{time}</td></tr>"
+            ));
+        }
+        html.push_str("</table>");
+    }
+    html.push_str("</div></body></html>");
+    html
+}
@@ -0,0 +1,143 @@
+//! Write thread listings to Parquet files, for loading into DuckDB or pandas.
+
+use crate::sink::ThreadSink;
+use crate::EmailThread;
+use anyhow::Result;
+use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampMillisecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use parquet::arrow::ArrowWriter;
+use std::fs::File;
+use std::path::Path;
+use std::sync::Arc;
+
+/// rows buffered before they are written out as one record batch
+const BATCH_ROWS: usize = 1024;
+
+/// collects [`EmailThread`] rows and writes them to a Parquet file in batches of
+/// [`BATCH_ROWS`], so exporting a long range does not keep every row in memory
+pub struct ParquetSink {
+    schema: SchemaRef,
+    writer: ArrowWriter<File>,
+    rows: Vec<EmailThread>,
+}
+
+impl ParquetSink {
+    pub fn create(path: &Path) -> Result<Self> {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Utf8, false),
+            Field::new("subject", DataType::Utf8, false),
+            Field::new("author", DataType::Utf8, false),
+            Field::new(
+                "datetime",
+                DataType::Timestamp(TimeUnit::Millisecond, None),
+                false,
+            ),
+            Field::new("list", DataType::Utf8, false),
+        ]));
+        let writer = ArrowWriter::try_new(File::create(path)?, schema.clone(), None)?;
+        Ok(ParquetSink {
+            schema,
+            writer,
+            rows: Vec::with_capacity(BATCH_ROWS),
+        })
+    }
+
+    fn write_batch(&mut self) -> Result<()> {
+        if self.rows.is_empty() {
+            return Ok(());
+        }
+        let rows = std::mem::take(&mut self.rows);
+        let strings = |field: fn(&EmailThread) -> &str| -> ArrayRef {
+            Arc::new(StringArray::from_iter_values(rows.iter().map(field)))
+        };
+        let columns = vec![
+            strings(|thread| &thread.id),
+            strings(|thread| &thread.subject),
+            strings(|thread| &thread.author),
+            Arc::new(TimestampMillisecondArray::from_iter_values(
+                rows.iter()
+                    .map(|thread| thread.datetime.and_utc().timestamp_millis()),
+            )),
+            strings(|thread| thread.list.name()),
+        ];
+        self.writer
+            .write(&RecordBatch::try_new(self.schema.clone(), columns)?)?;
+        Ok(())
+    }
+}
+
+impl ThreadSink for ParquetSink {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        self.rows.push(thread.clone());
+        if self.rows.len() >= BATCH_ROWS {
+            self.write_batch()?;
+        }
+        Ok(())
+    }
+
+    /// write the rows still buffered and the file footer, after which nothing more can be
+    /// written
+    fn flush(&mut self) -> Result<()> {
+        self.write_batch()?;
+        self.writer.finish()?;
+        Ok(())
+    }
+}
+
+#[test]
+fn write_and_read_back_threads() {
+    use crate::MailingList;
+    use arrow_array::Array;
+    use chrono::NaiveDate;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let path = std::env::temp_dir().join(format!("pgdevhub-{}.parquet", std::process::id()));
+    let datetime = NaiveDate::from_ymd_opt(2025, 1, 18)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap();
+    let mut sink = ParquetSink::create(&path).unwrap();
+    for i in 0..BATCH_ROWS + 2 {
+        sink.write(&EmailThread {
+            id: format!("id-{i}"),
+            list: if i % 2 == 0 {
+                MailingList::Hackers
+            } else {
+                MailingList::Bugs
+            },
+            subject: format!("Subject {i}"),
+            datetime,
+            author: "Alice".to_string(),
+            reply_count: None,
+            replies: None,
+        })
+        .unwrap();
+    }
+    sink.flush().unwrap();
+
+    let reader = ParquetRecordBatchReaderBuilder::try_new(File::open(&path).unwrap())
+        .unwrap()
+        .build()
+        .unwrap();
+    let batches: Vec<RecordBatch> = reader.map(|batch| batch.unwrap()).collect();
+    std::fs::remove_file(&path).unwrap();
+
+    let rows: usize = batches.iter().map(RecordBatch::num_rows).sum();
+    assert_eq!(rows, BATCH_ROWS + 2);
+    let first = &batches[0];
+    let column = |name| first.column_by_name(name).unwrap();
+    let ids = column("id").as_any().downcast_ref::<StringArray>().unwrap();
+    assert_eq!(ids.value(1), "id-1");
+    let lists = column("list")
+        .as_any()
+        .downcast_ref::<StringArray>()
+        .unwrap();
+    assert_eq!(lists.value(0), "pgsql-hackers");
+    assert_eq!(lists.value(1), "pgsql-bugs");
+    let datetimes = column("datetime")
+        .as_any()
+        .downcast_ref::<TimestampMillisecondArray>()
+        .unwrap();
+    assert_eq!(datetimes.value_as_datetime(0), Some(datetime));
+    assert_eq!(datetimes.len(), first.num_rows());
+}
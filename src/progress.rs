@@ -0,0 +1,96 @@
+//! What a long scrape is up to, reported as it goes to whoever drives it: a progress bar on the
+//! command line, log lines in the API server.
+
+use crate::{EmailThread, EmailThreadDetail};
+use std::time::Duration;
+
+/// Notified by a [`Fetcher`](crate::Fetcher) and the traversals running on it. Every method
+/// does nothing unless implemented, and may be called from several threads at once.
+pub trait ProgressObserver: Send + Sync + std::fmt::Debug {
+    /// `url` was fetched, retries included, in `elapsed`
+    fn page_fetched(&self, _url: &str, _elapsed: Duration) {}
+
+    /// `thread` is listed on a page of the archive, once for every page it is on
+    fn thread_found(&self, _thread: &EmailThread) {}
+
+    /// the details of a message were fetched
+    fn detail_fetched(&self, _detail: &EmailThreadDetail) {}
+
+    /// something went wrong that does not stop the run, like a skipped message or a retry
+    fn error(&self, _message: &str) {}
+
+    /// where the run is at, like the since-page being traversed
+    fn info(&self, _message: &str) {}
+}
+
+/// Logs the fetches, errors and notes as [`tracing`] events, the default. fetches are at the
+/// debug level, errors are warnings.
+#[derive(Debug)]
+pub struct Log;
+
+impl ProgressObserver for Log {
+    // the url is already on the fetch span around it
+    fn page_fetched(&self, _: &str, elapsed: Duration) {
+        tracing::debug!(elapsed_ms = elapsed.as_millis() as u64, "page fetched");
+    }
+
+    fn error(&self, message: &str) {
+        tracing::warn!("{message}");
+    }
+
+    fn info(&self, message: &str) {
+        tracing::info!("{message}");
+    }
+}
+
+#[tokio::test]
+async fn fetches_are_logged_in_their_span() {
+    use crate::mock_server::{MessagePage, MockResponse, MockServer};
+    use crate::{get_thread_by_id, Fetcher, MessageId};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Buffer {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let server = MockServer::start(|req| {
+        if req.path.starts_with("/message-id/raw/") {
+            return MockResponse::new(200, "text/plain", "Subject: Topic\r\n\r\nbody\r\n");
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        MockResponse::html(MessagePage::new(id).render())
+    });
+    let buffer = Buffer::default();
+    let writer = buffer.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter("pgdevhub=debug")
+        .with_writer(move || writer.clone())
+        .with_ansi(false)
+        .finish();
+
+    // the test runtime polls everything on this thread
+    let default = tracing::subscriber::set_default(subscriber);
+    let fetcher = Fetcher::new(&server.url());
+    get_thread_by_id(&fetcher, &MessageId::parse("a%40x").unwrap())
+        .await
+        .unwrap();
+    drop(default);
+
+    let log = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let fetched = log
+        .lines()
+        .find(|line| line.contains("page fetched") && line.contains("/message-id/a%40x"))
+        .unwrap_or_else(|| panic!("no fetch of the message page in {log}"));
+    assert!(fetched.contains("DEBUG"));
+    assert!(fetched.contains("thread_detail{id=\"a%40x\"}:fetch{url="));
+}
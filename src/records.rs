@@ -0,0 +1,284 @@
+//! Write listed threads and scraped messages as JSON Lines or CSV, with all their fields, for
+//! loading into DuckDB or pandas.
+//!
+//! A JSON line keeps the lists and maps of a message as JSON. A CSV row has a column per field:
+//! lists of ids are joined by spaces, the counts of `stats` get a column each, and the other
+//! lists and maps are written as JSON text.
+
+use crate::sink::ThreadSink;
+use crate::{
+    permalink, ContentStats, EmailThread, EmailThreadDetail, References, ThreadAttachment,
+};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// a JSON object per line
+    Jsonl,
+    /// a header row, then a row per record
+    Csv,
+}
+
+/// ids, a list in JSON and joined by spaces in CSV, which has no lists within a column
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Ids<'a> {
+    List(&'a [String]),
+    Joined(String),
+}
+
+#[derive(Serialize)]
+struct ThreadRecord<'a> {
+    id: &'a str,
+    url: String,
+    list: &'static str,
+    subject: &'a str,
+    author: &'a str,
+    datetime: NaiveDateTime,
+    reply_count: Option<usize>,
+    replies: Option<Ids<'a>>,
+}
+
+#[derive(Serialize)]
+struct MessageRecord<'a> {
+    id: &'a str,
+    url: String,
+    subject: &'a str,
+    datetime: NaiveDateTime,
+    author_name: &'a str,
+    author_email: &'a str,
+    content: &'a str,
+    content_missing: bool,
+    content_new_text: &'a str,
+    stats: ContentStats,
+    inline_patches: &'a [String],
+    references: &'a References,
+    attachments: &'a [ThreadAttachment],
+    replies: &'a [String],
+    headers: &'a BTreeMap<String, String>,
+}
+
+#[derive(Serialize)]
+struct MessageRow<'a> {
+    id: &'a str,
+    url: String,
+    subject: &'a str,
+    datetime: NaiveDateTime,
+    author_name: &'a str,
+    author_email: &'a str,
+    content: &'a str,
+    content_missing: bool,
+    content_new_text: &'a str,
+    words: usize,
+    chars: usize,
+    est_read_secs: usize,
+    inline_patches: String,
+    references: String,
+    attachments: String,
+    replies: String,
+    headers: String,
+}
+
+enum Out<W: Write> {
+    Jsonl(W),
+    Csv(Box<csv::Writer<W>>),
+}
+
+/// Writes threads and messages to `W` as records of a [`Format`]. Threads come in as a
+/// [`ThreadSink`], messages through [`write_message`](RecordWriter::write_message). A CSV output
+/// takes one kind only, its header row is the fields of the first record.
+pub struct RecordWriter<W: Write> {
+    out: Out<W>,
+}
+
+impl<W: Write> RecordWriter<W> {
+    pub fn new(format: Format, out: W) -> Self {
+        RecordWriter {
+            out: match format {
+                Format::Jsonl => Out::Jsonl(out),
+                Format::Csv => Out::Csv(Box::new(csv::Writer::from_writer(out))),
+            },
+        }
+    }
+
+    fn write_record<T: Serialize>(&mut self, record: &T) -> Result<()> {
+        match &mut self.out {
+            Out::Jsonl(out) => {
+                serde_json::to_writer(&mut *out, record)?;
+                out.write_all(b"\n")?;
+            }
+            Out::Csv(writer) => writer.serialize(record)?,
+        }
+        Ok(())
+    }
+
+    pub fn write_message(&mut self, detail: &EmailThreadDetail) -> Result<()> {
+        let url = permalink(&detail.id);
+        if let Out::Jsonl(_) = self.out {
+            return self.write_record(&MessageRecord {
+                id: &detail.id,
+                url,
+                subject: &detail.subject,
+                datetime: detail.datetime,
+                author_name: &detail.author_name,
+                author_email: &detail.author_email,
+                content: &detail.content,
+                content_missing: detail.content_missing,
+                content_new_text: &detail.content_new_text,
+                stats: detail.stats,
+                inline_patches: &detail.inline_patches,
+                references: &detail.references,
+                attachments: &detail.attachments,
+                replies: &detail.replies,
+                headers: &detail.headers,
+            });
+        }
+        self.write_record(&MessageRow {
+            id: &detail.id,
+            url,
+            subject: &detail.subject,
+            datetime: detail.datetime,
+            author_name: &detail.author_name,
+            author_email: &detail.author_email,
+            content: &detail.content,
+            content_missing: detail.content_missing,
+            content_new_text: &detail.content_new_text,
+            words: detail.stats.words,
+            chars: detail.stats.chars,
+            est_read_secs: detail.stats.est_read_secs,
+            inline_patches: serde_json::to_string(&detail.inline_patches)?,
+            references: serde_json::to_string(&detail.references)?,
+            attachments: serde_json::to_string(&detail.attachments)?,
+            replies: detail.replies.join(" "),
+            headers: serde_json::to_string(&detail.headers)?,
+        })
+    }
+}
+
+impl<W: Write> ThreadSink for RecordWriter<W> {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        let replies = thread.replies.as_deref().map(|ids| match self.out {
+            Out::Jsonl(_) => Ids::List(ids),
+            Out::Csv(_) => Ids::Joined(ids.join(" ")),
+        });
+        self.write_record(&ThreadRecord {
+            id: &thread.id,
+            url: permalink(&thread.id),
+            list: thread.list.name(),
+            subject: &thread.subject,
+            author: &thread.author,
+            datetime: thread.datetime,
+            reply_count: thread.reply_count,
+            replies,
+        })
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.out {
+            Out::Jsonl(out) => out.flush()?,
+            Out::Csv(writer) => writer.flush()?,
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn records_have_every_field() {
+    use crate::MailingList;
+    use chrono::NaiveDate;
+
+    let datetime = NaiveDate::from_ymd_opt(2025, 1, 18)
+        .unwrap()
+        .and_hms_opt(9, 0, 0)
+        .unwrap();
+    let thread = EmailThread {
+        id: "a%40x".to_string(),
+        list: MailingList::Bugs,
+        subject: "Crash, again".to_string(),
+        datetime,
+        author: "Alice".to_string(),
+        reply_count: Some(2),
+        replies: Some(vec!["b%40x".to_string(), "c%40x".to_string()]),
+    };
+    let written = |format| {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(format, &mut out);
+        writer.write(&thread).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        String::from_utf8(out).unwrap()
+    };
+
+    let line: serde_json::Value = serde_json::from_str(&written(Format::Jsonl)).unwrap();
+    assert_eq!(line["list"], "pgsql-bugs");
+    assert_eq!(line["datetime"], "2025-01-18T09:00:00");
+    assert_eq!(line["reply_count"], 2);
+    assert_eq!(line["replies"], serde_json::json!(["b%40x", "c%40x"]));
+    assert_eq!(
+        written(Format::Csv),
+        "id,url,list,subject,author,datetime,reply_count,replies\n\
+         a%40x,https://www.postgresql.org/message-id/a%40x,pgsql-bugs,\"Crash, again\",Alice,\
+         2025-01-18T09:00:00,2,b%40x c%40x\n"
+    );
+
+    let mut headers = BTreeMap::new();
+    headers.insert("User-Agent".to_string(), "Mutt/2.2".to_string());
+    let detail = EmailThreadDetail {
+        id: "a%40x".to_string(),
+        subject: "Crash, again".to_string(),
+        datetime,
+        author_name: "Alice".to_string(),
+        author_email: "alice@example.org".to_string(),
+        content: "<p>See commit 1234567abc</p>".to_string(),
+        content_missing: false,
+        content_new_text: "See commit 1234567abc".to_string(),
+        stats: ContentStats {
+            words: 3,
+            chars: 16,
+            est_read_secs: 1,
+        },
+        inline_patches: Vec::new(),
+        references: References {
+            commits: vec!["1234567abc".to_string()],
+            bugs: Vec::new(),
+        },
+        attachments: vec![ThreadAttachment {
+            name: "fix.patch".to_string(),
+            href: "/message-id/attachment/1/fix.patch".to_string(),
+            size: Some(120),
+        }],
+        replies: vec!["a%40x".to_string(), "b%40x".to_string()],
+        headers,
+    };
+    let message = |format| {
+        let mut out = Vec::new();
+        let mut writer = RecordWriter::new(format, &mut out);
+        writer.write_message(&detail).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        String::from_utf8(out).unwrap()
+    };
+
+    let line: serde_json::Value = serde_json::from_str(&message(Format::Jsonl)).unwrap();
+    assert_eq!(line["stats"]["words"], 3);
+    assert_eq!(line["references"]["commits"][0], "1234567abc");
+    assert_eq!(line["attachments"][0]["size"], 120);
+    assert_eq!(line["headers"]["User-Agent"], "Mutt/2.2");
+
+    let csv = message(Format::Csv);
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let header: Vec<String> = reader.headers().unwrap().iter().map(String::from).collect();
+    let row = reader.records().next().unwrap().unwrap();
+    let column = |name: &str| row[header.iter().position(|h| h == name).unwrap()].to_string();
+    assert_eq!(column("words"), "3");
+    assert_eq!(column("replies"), "a%40x b%40x");
+    assert_eq!(column("headers"), r#"{"User-Agent":"Mutt/2.2"}"#);
+    assert_eq!(
+        column("attachments"),
+        r#"[{"name":"fix.patch","href":"/message-id/attachment/1/fix.patch","size":120}]"#
+    );
+}
@@ -0,0 +1,148 @@
+//! Run jobs on a cron schedule inside the process, so a self-hosted server needs no system cron.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Local};
+use cron::Schedule;
+use std::str::FromStr;
+use std::thread;
+
+const DAY_NAMES: [&str; 8] = ["Sun", "Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"];
+
+/// parse a crontab-style `minute hour day-of-month month day-of-week` expression, like
+/// `0 9 * * 1` for every Monday at 09:00.
+/// a sixth leading field is taken as the seconds.
+pub fn parse_schedule(expr: &str) -> Result<Schedule> {
+    let mut fields: Vec<String> = expr.split_whitespace().map(str::to_string).collect();
+    match fields.len() {
+        5 => fields.insert(0, "0".to_string()),
+        6 => {}
+        _ => bail!("'{expr}' is not a cron expression, expected 5 fields like '0 9 * * 1'"),
+    }
+    // the cron crate counts week days from 1 = Sunday, crontab from 0 = Sunday; names mean the
+    // same to both
+    fields[5] = day_names(&fields[5]).with_context(|| format!("bad day of week in '{expr}'"))?;
+    Schedule::from_str(&fields.join(" ")).with_context(|| format!("bad cron expression '{expr}'"))
+}
+
+fn day_names(field: &str) -> Result<String> {
+    let mut named = String::new();
+    let mut chars = field.chars().peekable();
+    let mut after_step = false;
+    while let Some(c) = chars.next() {
+        if !c.is_ascii_digit() {
+            after_step = c == '/';
+            named.push(c);
+            continue;
+        }
+        let mut number = c.to_string();
+        while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+            number.push(digit);
+        }
+        if after_step {
+            // a step like `*/2` counts days, it does not name one
+            named.push_str(&number);
+        } else {
+            let name = DAY_NAMES
+                .get(number.parse::<usize>()?)
+                .with_context(|| format!("no day {number}, days go from 0 to 7"))?;
+            named.push_str(name);
+        }
+        after_step = false;
+    }
+    Ok(named)
+}
+
+/// where the scheduler learns the time and waits for the next run
+pub trait Clock {
+    fn now(&self) -> DateTime<Local>;
+    /// block until `time`, or return false to stop the scheduler instead
+    fn sleep_until(&self, time: DateTime<Local>) -> bool;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<Local> {
+        Local::now()
+    }
+
+    fn sleep_until(&self, time: DateTime<Local>) -> bool {
+        if let Ok(delay) = (time - Local::now()).to_std() {
+            thread::sleep(delay);
+        }
+        true
+    }
+}
+
+/// call `job` with the scheduled time at every upcoming time of `schedule`, until the clock says
+/// stop or the schedule runs out
+pub fn run(schedule: &Schedule, clock: &impl Clock, mut job: impl FnMut(DateTime<Local>)) {
+    loop {
+        let Some(next) = schedule.after(&clock.now()).next() else {
+            return;
+        };
+        if !clock.sleep_until(next) {
+            return;
+        }
+        job(next);
+    }
+}
+
+/// run `job` on `schedule` in a background thread for as long as the process lives
+pub fn spawn(schedule: Schedule, job: impl FnMut(DateTime<Local>) + Send + 'static) {
+    thread::spawn(move || run(&schedule, &SystemClock, job));
+}
+
+#[test]
+fn weekly_schedule_fires_once_a_week() {
+    use chrono::{Datelike, NaiveDate, TimeZone, Weekday};
+    use std::cell::Cell;
+
+    struct FakeClock {
+        now: Cell<DateTime<Local>>,
+        end: DateTime<Local>,
+    }
+
+    impl Clock for FakeClock {
+        fn now(&self) -> DateTime<Local> {
+            self.now.get()
+        }
+
+        fn sleep_until(&self, time: DateTime<Local>) -> bool {
+            self.now.set(time);
+            time <= self.end
+        }
+    }
+
+    let at = |day| {
+        let date = NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+        Local.from_local_datetime(&date.into()).unwrap()
+    };
+    // Wednesday the 1st to Friday the 31st holds four Mondays
+    let clock = FakeClock {
+        now: Cell::new(at(1)),
+        end: at(31),
+    };
+    let mut fired = Vec::new();
+    run(&parse_schedule("0 9 * * 1").unwrap(), &clock, |time| {
+        fired.push(time)
+    });
+
+    assert_eq!(fired.len(), 4);
+    for time in &fired {
+        assert_eq!(time.weekday(), Weekday::Mon);
+        assert_eq!(time.format("%H:%M").to_string(), "09:00");
+    }
+
+    let weekdays = parse_schedule("30 8 * * 1-5").unwrap();
+    let clock = FakeClock {
+        now: Cell::new(at(6)),
+        end: at(13),
+    };
+    let mut fired = 0;
+    run(&weekdays, &clock, |_| fired += 1);
+    assert_eq!(fired, 5);
+
+    assert!(parse_schedule("0 9 * *").is_err());
+    assert!(parse_schedule("0 9 * * 8").is_err());
+}
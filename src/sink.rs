@@ -0,0 +1,47 @@
+//! Destinations for listed threads, so a command writes to the terminal, a file or several of
+//! them through the same calls.
+
+use crate::EmailThread;
+use anyhow::Result;
+
+/// where listed threads end up, handed over one at a time as they are found
+pub trait ThreadSink {
+    fn write(&mut self, thread: &EmailThread) -> Result<()>;
+
+    /// write out whatever is still buffered and finish the output, once the last thread is
+    /// written
+    fn flush(&mut self) -> Result<()>;
+}
+
+/// threads kept in memory, for callers that want them all at the end
+impl ThreadSink for Vec<EmailThread> {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        self.push(thread.clone());
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: ThreadSink + ?Sized> ThreadSink for &mut S {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        (**self).write(thread)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        (**self).flush()
+    }
+}
+
+/// every thread to each of the sinks in turn, like the terminal and a file
+impl ThreadSink for Vec<Box<dyn ThreadSink + '_>> {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        self.iter_mut().try_for_each(|sink| sink.write(thread))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.iter_mut().try_for_each(|sink| sink.flush())
+    }
+}
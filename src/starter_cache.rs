@@ -0,0 +1,58 @@
+//! Remember which message starts the thread of each message across runs, as that never changes.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// a message-id to starter-id map kept in a file of `id<TAB>starter` lines. entries are only
+/// ever added, each one appended as it is learned, so a crash loses at most the lookup in flight
+#[derive(Debug)]
+pub struct StarterCache {
+    starters: Mutex<HashMap<String, String>>,
+    file: Mutex<File>,
+}
+
+impl StarterCache {
+    /// the cache stored at `path`, created empty when there is no such file yet
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut starters = HashMap::new();
+        if path.exists() {
+            let file = File::open(path)
+                .with_context(|| format!("failed to open the starter cache {}", path.display()))?;
+            for line in BufReader::new(file).lines() {
+                // a line cut short by a crash is simply looked up again
+                if let Some((id, starter)) = line?.split_once('\t') {
+                    starters.insert(id.to_string(), starter.to_string());
+                }
+            }
+        }
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("failed to open the starter cache {}", path.display()))?;
+        Ok(StarterCache {
+            starters: Mutex::new(starters),
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn get(&self, id: &str) -> Option<String> {
+        self.starters.lock().unwrap().get(id).cloned()
+    }
+
+    pub fn insert(&self, id: &str, starter: &str) -> Result<()> {
+        let mut starters = self.starters.lock().unwrap();
+        if starters.get(id).is_some_and(|known| known == starter) {
+            return Ok(());
+        }
+        let mut file = self.file.lock().unwrap();
+        writeln!(file, "{id}\t{starter}")?;
+        file.flush()?;
+        starters.insert(id.to_string(), starter.to_string());
+        Ok(())
+    }
+}
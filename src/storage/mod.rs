@@ -0,0 +1,280 @@
+//! Scraped threads and messages kept in a database, so they can be queried again without going
+//! back to the archive. Rows are upserted as they are scraped, a message scraped again replaces
+//! what was kept of it.
+//!
+//! [`Sqlite`] keeps them in a file. [`Postgres`] keeps them in a server several runs can share,
+//! with the `postgres` feature. [`open`] picks one from a database url. With the `tantivy`
+//! feature, [`Tantivy`] indexes the messages of either for faster searches.
+//!
+//! The tables of each database go through the [`Migration`]s of its backend as the model
+//! evolves. [`open`] applies the ones a database has not been through yet, so a database kept by
+//! an older pgdevhub is upgraded rather than started over.
+
+mod sqlite;
+
+#[cfg(feature = "postgres")]
+mod postgres;
+#[cfg(feature = "tantivy")]
+mod tantivy;
+
+#[cfg(feature = "postgres")]
+pub use self::postgres::Postgres;
+pub use self::sqlite::Sqlite;
+#[cfg(feature = "tantivy")]
+pub use self::tantivy::Tantivy;
+
+use crate::sink::ThreadSink;
+use crate::{
+    content_new_text, content_stats, extract_inline_patches, extract_references, EmailThread,
+    EmailThreadDetail, MailingList, ThreadAttachment,
+};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A database of scraped threads and messages, shared by the threads of a run.
+pub trait Storage: Send + Sync + std::fmt::Debug {
+    /// keep the listing row `thread`. a reply count already kept stays when `thread` has none
+    fn upsert_thread(&self, thread: &EmailThread) -> Result<()>;
+
+    /// keep the message `detail` of `list` with its attachments and the ids of the rest of its
+    /// thread, replacing the ones kept before
+    fn upsert_message(&self, list: MailingList, detail: &EmailThreadDetail) -> Result<()>;
+
+    /// the threads kept of `list` listed between `start` and `end`, both included, oldest first
+    fn threads_between(
+        &self,
+        list: MailingList,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>>;
+
+    /// the message `id` as it was kept, if it was
+    fn message(&self, id: &str) -> Result<Option<EmailThreadDetail>>;
+
+    /// the messages kept of `list`, or of every list, posted between `start` and `end`, both
+    /// included, whose subject, author or content match `query`, best matches first, a match in
+    /// the subject counting more than one elsewhere. `query` is in the full-text syntax of the
+    /// search backend, FTS5, `websearch_to_tsquery` or tantivy's. in all of them, words like
+    /// `io_uring` find the messages that have all of them and quotes make a phrase
+    fn search(
+        &self,
+        query: &str,
+        list: Option<MailingList>,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>>;
+
+    /// the time of the newest thread of `list` the last [`sync`](crate::sync) listed, if any
+    fn cursor(&self, list: MailingList) -> Result<Option<NaiveDateTime>>;
+
+    /// remember `since` as where the next [`sync`](crate::sync) of `list` starts
+    fn set_cursor(&self, list: MailingList, since: NaiveDateTime) -> Result<()>;
+
+    /// how far the [`backfill`](crate::backfill) of `list` from `since` got, everything listed
+    /// before it is kept
+    fn backfilled(&self, list: MailingList, since: NaiveDateTime) -> Result<Option<NaiveDateTime>>;
+
+    /// remember that the [`backfill`](crate::backfill) of `list` from `since` kept everything
+    /// listed before `until`
+    fn set_backfilled(
+        &self,
+        list: MailingList,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> Result<()>;
+
+    /// remember the message `id` as seen, like [`HashSet::insert`](std::collections::HashSet)
+    /// but across runs: whether it was not seen before
+    fn mark_seen(&self, id: &str) -> Result<bool>;
+}
+
+/// A change to the tables of a database, applied once, in the order of the versions. The
+/// versions applied are recorded in the `schema_migrations` table of the database.
+#[derive(Debug)]
+pub struct Migration {
+    pub version: i64,
+    pub description: &'static str,
+    sql: &'static str,
+}
+
+/// The database at `url`, created with its tables when missing and migrated otherwise: a
+/// PostgreSQL server for `postgres://` and `postgresql://` urls, a SQLite file otherwise, its
+/// path optionally after `sqlite:`. `sqlite::memory:` is a SQLite database that lives as long
+/// as the storage.
+pub fn open(url: &str) -> Result<Arc<dyn Storage>> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return Ok(Arc::new(Postgres::connect(url)?));
+        #[cfg(not(feature = "postgres"))]
+        anyhow::bail!("{url}: PostgreSQL storage needs pgdevhub built with the postgres feature");
+    }
+    let path = url.strip_prefix("sqlite:").unwrap_or(url);
+    Ok(match path {
+        ":memory:" => Arc::new(Sqlite::open_in_memory()?),
+        path => Arc::new(Sqlite::open(Path::new(path))?),
+    })
+}
+
+/// the migrations [`open`] would apply to the database at `url`, without applying them. a
+/// database migrated by a newer pgdevhub is an error
+pub fn pending_migrations(url: &str) -> Result<Vec<&'static Migration>> {
+    if url.starts_with("postgres://") || url.starts_with("postgresql://") {
+        #[cfg(feature = "postgres")]
+        return postgres::pending_migrations(url);
+        #[cfg(not(feature = "postgres"))]
+        anyhow::bail!("{url}: PostgreSQL storage needs pgdevhub built with the postgres feature");
+    }
+    let path = url.strip_prefix("sqlite:").unwrap_or(url);
+    sqlite::pending_migrations(Path::new(path))
+}
+
+/// the migrations of `migrations` whose version is not in `applied`, checking that none of
+/// `applied` is newer than the last of them
+fn unapplied<'m>(migrations: &'m [Migration], applied: &[i64]) -> Result<Vec<&'m Migration>> {
+    let known = migrations.last().map_or(0, |migration| migration.version);
+    if let Some(newer) = applied.iter().find(|version| **version > known) {
+        anyhow::bail!(
+            "the database was migrated to version {newer} by a newer pgdevhub, this one knows \
+             versions up to {known}"
+        );
+    }
+    Ok(migrations
+        .iter()
+        .filter(|migration| !applied.contains(&migration.version))
+        .collect())
+}
+
+/// when a row is written, kept with it to tell how fresh it is
+fn scraped_at() -> NaiveDateTime {
+    chrono::Utc::now().naive_utc()
+}
+
+/// The columns kept of a message, whichever database they were read from. What is derived from
+/// the content is not kept but worked out again.
+struct KeptMessage {
+    id: String,
+    subject: String,
+    datetime: NaiveDateTime,
+    author_name: String,
+    author_email: String,
+    content: String,
+    content_missing: bool,
+    /// the headers as JSON
+    headers: String,
+    attachments: Vec<ThreadAttachment>,
+    replies: Vec<String>,
+}
+
+impl KeptMessage {
+    fn into_detail(self) -> Result<EmailThreadDetail> {
+        Ok(EmailThreadDetail {
+            id: self.id,
+            subject: self.subject,
+            datetime: self.datetime,
+            author_name: self.author_name,
+            author_email: self.author_email,
+            stats: content_stats(&self.content),
+            inline_patches: extract_inline_patches(&self.content),
+            references: extract_references(&self.content),
+            content_new_text: content_new_text(&self.content),
+            content: self.content,
+            content_missing: self.content_missing,
+            attachments: self.attachments,
+            replies: self.replies,
+            headers: serde_json::from_str(&self.headers)?,
+        })
+    }
+}
+
+/// listed threads kept in the storage as they are written
+impl ThreadSink for &dyn Storage {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        self.upsert_thread(thread)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Passes on to `sink` only the threads whose message was not seen before in `storage`, by this
+/// run or an earlier one, so overlapping ranges and repeated runs list each thread once.
+pub struct Unseen<'a, S> {
+    storage: &'a dyn Storage,
+    sink: S,
+}
+
+impl<'a, S: ThreadSink> Unseen<'a, S> {
+    pub fn new(storage: &'a dyn Storage, sink: S) -> Self {
+        Unseen { storage, sink }
+    }
+}
+
+impl<S: ThreadSink> ThreadSink for Unseen<'_, S> {
+    fn write(&mut self, thread: &EmailThread) -> Result<()> {
+        if self.storage.mark_seen(&thread.id)? {
+            self.sink.write(thread)?;
+        }
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.sink.flush()
+    }
+}
+
+#[test]
+fn storage_is_picked_by_url() {
+    let storage = open("sqlite::memory:").unwrap();
+    assert!(storage.message("a%40x").unwrap().is_none());
+
+    let dir = std::env::temp_dir().join(format!("pgdevhub-storage-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("kept.db");
+    open(path.to_str().unwrap()).unwrap();
+    assert!(path.exists());
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    #[cfg(not(feature = "postgres"))]
+    assert!(open("postgres://localhost/pgdevhub")
+        .unwrap_err()
+        .to_string()
+        .contains("postgres feature"));
+}
+
+#[test]
+fn threads_seen_by_an_earlier_run_are_skipped() {
+    let storage = open("sqlite::memory:").unwrap();
+    let thread = |id: &str| EmailThread {
+        id: id.to_string(),
+        list: MailingList::Hackers,
+        subject: format!("Topic {id}"),
+        datetime: chrono::NaiveDate::from_ymd_opt(2025, 1, 18)
+            .unwrap()
+            .and_hms_opt(9, 0, 0)
+            .unwrap(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+
+    let mut first_run = Vec::new();
+    let mut sink = Unseen::new(&*storage, &mut first_run);
+    for id in ["a%40x", "b%40x", "a%40x"] {
+        sink.write(&thread(id)).unwrap();
+    }
+    sink.flush().unwrap();
+    let ids = |threads: &[EmailThread]| -> Vec<String> {
+        threads.iter().map(|thread| thread.id.clone()).collect()
+    };
+    assert_eq!(ids(&first_run), ["a%40x", "b%40x"]);
+
+    let mut second_run = Vec::new();
+    let mut sink = Unseen::new(&*storage, &mut second_run);
+    for id in ["b%40x", "c%40x"] {
+        sink.write(&thread(id)).unwrap();
+    }
+    assert_eq!(ids(&second_run), ["c%40x"]);
+}
@@ -0,0 +1,446 @@
+//! [`Storage`] in a PostgreSQL server, for runs on several machines sharing what they scraped.
+
+use super::{scraped_at, unapplied, KeptMessage, Migration, Storage};
+use crate::{EmailThread, EmailThreadDetail, MailingList, ThreadAttachment};
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use r2d2_postgres::postgres::{Client, GenericClient, NoTls};
+use r2d2_postgres::{r2d2, PostgresConnectionManager};
+
+/// connections kept open to the server, enough for the pages fetched at once by a run
+const POOL_SIZE: u32 = 8;
+
+/// the migrations of a PostgreSQL database, the first one creating the tables of the databases
+/// kept before there were migrations when missing
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "threads, messages with their search index, cursors, backfills and seen ids",
+    sql: "
+CREATE TABLE IF NOT EXISTS threads (
+    id TEXT PRIMARY KEY,
+    list TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    author TEXT NOT NULL,
+    datetime TIMESTAMP NOT NULL,
+    reply_count BIGINT,
+    scraped_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS threads_by_datetime ON threads (list, datetime);
+CREATE TABLE IF NOT EXISTS messages (
+    id TEXT PRIMARY KEY,
+    list TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    datetime TIMESTAMP NOT NULL,
+    author_name TEXT NOT NULL,
+    author_email TEXT NOT NULL,
+    content TEXT NOT NULL,
+    content_missing BOOLEAN NOT NULL,
+    headers TEXT NOT NULL,
+    scraped_at TIMESTAMP NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_by_datetime ON messages (list, datetime);
+CREATE INDEX IF NOT EXISTS messages_search ON messages USING GIN (
+    to_tsvector('simple', subject || ' ' || author_name || ' ' || author_email || ' ' || content)
+);
+CREATE TABLE IF NOT EXISTS attachments (
+    message_id TEXT NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    href TEXT NOT NULL,
+    size BIGINT,
+    PRIMARY KEY (message_id, position)
+);
+CREATE TABLE IF NOT EXISTS replies (
+    message_id TEXT NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    reply_id TEXT NOT NULL,
+    PRIMARY KEY (message_id, position)
+);
+CREATE TABLE IF NOT EXISTS cursors (
+    list TEXT PRIMARY KEY,
+    since TIMESTAMP NOT NULL,
+    synced_at TIMESTAMP NOT NULL
+);
+CREATE TABLE IF NOT EXISTS backfills (
+    list TEXT NOT NULL,
+    since TIMESTAMP NOT NULL,
+    until TIMESTAMP NOT NULL,
+    backfilled_at TIMESTAMP NOT NULL,
+    PRIMARY KEY (list, since)
+);
+CREATE TABLE IF NOT EXISTS seen (
+    id TEXT PRIMARY KEY,
+    seen_at TIMESTAMP NOT NULL
+);
+",
+}];
+
+const MIGRATIONS_TABLE: &str = "
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    version BIGINT PRIMARY KEY,
+    description TEXT NOT NULL,
+    applied_at TIMESTAMP NOT NULL
+);
+";
+
+/// the versions applied to the database, none when it has not been migrated
+fn applied_versions(client: &mut impl GenericClient) -> Result<Vec<i64>> {
+    let migrated: bool = client
+        .query_one("SELECT to_regclass('schema_migrations') IS NOT NULL", &[])?
+        .get(0);
+    if !migrated {
+        return Ok(Vec::new());
+    }
+    Ok(client
+        .query(
+            "SELECT version FROM schema_migrations ORDER BY version",
+            &[],
+        )?
+        .iter()
+        .map(|row| row.get(0))
+        .collect())
+}
+
+/// apply the migrations the database has not been through, in one transaction holding the
+/// migrations table, so servers started together do not apply them twice
+fn migrate(client: &mut Client) -> Result<()> {
+    client.batch_execute(MIGRATIONS_TABLE)?;
+    let mut tx = client.transaction()?;
+    tx.batch_execute("LOCK TABLE schema_migrations IN EXCLUSIVE MODE")?;
+    let applied = applied_versions(&mut tx)?;
+    for migration in unapplied(MIGRATIONS, &applied)? {
+        tx.batch_execute(migration.sql)
+            .with_context(|| format!("failed to migrate to version {}", migration.version))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at) VALUES ($1, $2, $3)",
+            &[&migration.version, &migration.description, &scraped_at()],
+        )?;
+        tracing::info!(
+            "migrated the database to version {}: {}",
+            migration.version,
+            migration.description
+        );
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+/// the migrations connecting to the database at `url` would apply
+pub(super) fn pending_migrations(url: &str) -> Result<Vec<&'static Migration>> {
+    blocking(|| {
+        let mut client = Client::connect(url, NoTls)
+            .with_context(|| format!("failed to connect to the database {url}"))?;
+        unapplied(MIGRATIONS, &applied_versions(&mut client)?)
+    })
+}
+
+/// run `f`, which calls the blocking client, on a thread of its own when called on the async
+/// runtime, as the client cannot start its own runtime inside of another
+fn blocking<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    match tokio::runtime::Handle::try_current() {
+        Ok(_) => std::thread::scope(|scope| {
+            scope
+                .spawn(f)
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+        }),
+        Err(_) => f(),
+    }
+}
+
+/// A pool of connections to a PostgreSQL database of scraped threads and messages.
+#[derive(Debug)]
+pub struct Postgres {
+    pool: r2d2::Pool<PostgresConnectionManager<NoTls>>,
+}
+
+impl Postgres {
+    /// the database at `url`, like `postgres://user@host/db`, its tables created when missing
+    /// and migrated otherwise
+    pub fn connect(url: &str) -> Result<Self> {
+        let manager = PostgresConnectionManager::new(
+            url.parse()
+                .with_context(|| format!("bad database url {url}"))?,
+            NoTls,
+        );
+        blocking(|| {
+            let pool = r2d2::Pool::builder()
+                .max_size(POOL_SIZE)
+                .build(manager)
+                .with_context(|| format!("failed to connect to the database {url}"))?;
+            migrate(&mut *pool.get()?)?;
+            Ok(Postgres { pool })
+        })
+    }
+}
+
+impl Storage for Postgres {
+    fn upsert_thread(&self, thread: &EmailThread) -> Result<()> {
+        blocking(|| {
+            self.pool.get()?.execute(
+                "INSERT INTO threads (id, list, subject, author, datetime, reply_count, scraped_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7)
+                 ON CONFLICT (id) DO UPDATE SET
+                     list = excluded.list,
+                     subject = excluded.subject,
+                     author = excluded.author,
+                     datetime = excluded.datetime,
+                     reply_count = COALESCE(excluded.reply_count, threads.reply_count),
+                     scraped_at = excluded.scraped_at",
+                &[
+                    &thread.id,
+                    &thread.list.name(),
+                    &thread.subject,
+                    &thread.author,
+                    &thread.datetime,
+                    &thread.reply_count.map(|count| count as i64),
+                    &scraped_at(),
+                ],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn upsert_message(&self, list: MailingList, detail: &EmailThreadDetail) -> Result<()> {
+        blocking(|| {
+            let mut conn = self.pool.get()?;
+            let mut tx = conn.transaction()?;
+            tx.execute(
+                "INSERT INTO messages (id, list, subject, datetime, author_name, author_email, content,
+                                       content_missing, headers, scraped_at)
+                 VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+                 ON CONFLICT (id) DO UPDATE SET
+                     list = excluded.list,
+                     subject = excluded.subject,
+                     datetime = excluded.datetime,
+                     author_name = excluded.author_name,
+                     author_email = excluded.author_email,
+                     content = excluded.content,
+                     content_missing = excluded.content_missing,
+                     headers = excluded.headers,
+                     scraped_at = excluded.scraped_at",
+                &[
+                    &detail.id,
+                    &list.name(),
+                    &detail.subject,
+                    &detail.datetime,
+                    &detail.author_name,
+                    &detail.author_email,
+                    &detail.content,
+                    &detail.content_missing,
+                    &serde_json::to_string(&detail.headers)?,
+                    &scraped_at(),
+                ],
+            )?;
+            tx.execute(
+                "DELETE FROM attachments WHERE message_id = $1",
+                &[&detail.id],
+            )?;
+            tx.execute("DELETE FROM replies WHERE message_id = $1", &[&detail.id])?;
+            for (position, attachment) in detail.attachments.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO attachments (message_id, position, name, href, size)
+                     VALUES ($1, $2, $3, $4, $5)",
+                    &[
+                        &detail.id,
+                        &(position as i32),
+                        &attachment.name,
+                        &attachment.href,
+                        &attachment.size.map(|size| size as i64),
+                    ],
+                )?;
+            }
+            for (position, reply_id) in detail.replies.iter().enumerate() {
+                tx.execute(
+                    "INSERT INTO replies (message_id, position, reply_id) VALUES ($1, $2, $3)",
+                    &[&detail.id, &(position as i32), reply_id],
+                )?;
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+
+    fn threads_between(
+        &self,
+        list: MailingList,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        blocking(|| {
+            let rows = self.pool.get()?.query(
+                "SELECT id, subject, author, datetime, reply_count FROM threads
+                 WHERE list = $1 AND datetime BETWEEN $2 AND $3
+                 ORDER BY datetime, id",
+                &[&list.name(), &start, &end],
+            )?;
+            Ok(rows
+                .iter()
+                .map(|row| EmailThread {
+                    id: row.get(0),
+                    list,
+                    subject: row.get(1),
+                    author: row.get(2),
+                    datetime: row.get(3),
+                    reply_count: row.get::<_, Option<i64>>(4).map(|count| count as usize),
+                    replies: None,
+                })
+                .collect())
+        })
+    }
+
+    fn message(&self, id: &str) -> Result<Option<EmailThreadDetail>> {
+        blocking(|| {
+            let mut conn = self.pool.get()?;
+            let Some(row) = conn.query_opt(
+                "SELECT subject, datetime, author_name, author_email, content, content_missing,
+                        headers
+                 FROM messages WHERE id = $1",
+                &[&id],
+            )?
+            else {
+                return Ok(None);
+            };
+            let attachments = conn
+                .query(
+                    "SELECT name, href, size FROM attachments WHERE message_id = $1 ORDER BY position",
+                    &[&id],
+                )?
+                .iter()
+                .map(|row| ThreadAttachment {
+                    name: row.get(0),
+                    href: row.get(1),
+                    size: row.get::<_, Option<i64>>(2).map(|size| size as u64),
+                })
+                .collect();
+            let replies = conn
+                .query(
+                    "SELECT reply_id FROM replies WHERE message_id = $1 ORDER BY position",
+                    &[&id],
+                )?
+                .iter()
+                .map(|row| row.get(0))
+                .collect();
+            KeptMessage {
+                id: id.to_string(),
+                subject: row.get(0),
+                datetime: row.get(1),
+                author_name: row.get(2),
+                author_email: row.get(3),
+                content: row.get(4),
+                content_missing: row.get(5),
+                headers: row.get(6),
+                attachments,
+                replies,
+            }
+            .into_detail()
+            .map(Some)
+        })
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        list: Option<MailingList>,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        blocking(|| {
+            let rows = self
+                .pool
+                .get()?
+                .query(
+                    "SELECT id, list, subject, author_name, datetime FROM messages
+                     WHERE to_tsvector('simple', subject || ' ' || author_name || ' ' || author_email
+                                                 || ' ' || content)
+                               @@ websearch_to_tsquery('simple', $1)
+                         AND datetime BETWEEN $2 AND $3
+                         AND ($4::TEXT IS NULL OR list = $4)
+                     ORDER BY ts_rank(setweight(to_tsvector('simple', subject), 'A')
+                                          || to_tsvector('simple', author_name || ' ' || author_email
+                                                                   || ' ' || content),
+                                      websearch_to_tsquery('simple', $1)) DESC,
+                              datetime, id",
+                    &[&query, &start, &end, &list.map(MailingList::name)],
+                )
+                .with_context(|| format!("failed to search for '{query}'"))?;
+            rows.iter()
+                .map(|row| {
+                    Ok(EmailThread {
+                        id: row.get(0),
+                        list: row.get::<_, String>(1).parse()?,
+                        subject: row.get(2),
+                        author: row.get(3),
+                        datetime: row.get(4),
+                        reply_count: None,
+                        replies: None,
+                    })
+                })
+                .collect()
+        })
+    }
+
+    fn cursor(&self, list: MailingList) -> Result<Option<NaiveDateTime>> {
+        blocking(|| {
+            Ok(self
+                .pool
+                .get()?
+                .query_opt("SELECT since FROM cursors WHERE list = $1", &[&list.name()])?
+                .map(|row| row.get(0)))
+        })
+    }
+
+    fn set_cursor(&self, list: MailingList, since: NaiveDateTime) -> Result<()> {
+        blocking(|| {
+            self.pool.get()?.execute(
+                "INSERT INTO cursors (list, since, synced_at) VALUES ($1, $2, $3)
+                 ON CONFLICT (list) DO UPDATE SET
+                     since = excluded.since,
+                     synced_at = excluded.synced_at",
+                &[&list.name(), &since, &scraped_at()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn backfilled(&self, list: MailingList, since: NaiveDateTime) -> Result<Option<NaiveDateTime>> {
+        blocking(|| {
+            Ok(self
+                .pool
+                .get()?
+                .query_opt(
+                    "SELECT until FROM backfills WHERE list = $1 AND since = $2",
+                    &[&list.name(), &since],
+                )?
+                .map(|row| row.get(0)))
+        })
+    }
+
+    fn set_backfilled(
+        &self,
+        list: MailingList,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> Result<()> {
+        blocking(|| {
+            self.pool.get()?.execute(
+                "INSERT INTO backfills (list, since, until, backfilled_at) VALUES ($1, $2, $3, $4)
+                 ON CONFLICT (list, since) DO UPDATE SET
+                     until = excluded.until,
+                     backfilled_at = excluded.backfilled_at",
+                &[&list.name(), &since, &until, &scraped_at()],
+            )?;
+            Ok(())
+        })
+    }
+
+    fn mark_seen(&self, id: &str) -> Result<bool> {
+        blocking(|| {
+            let inserted = self.pool.get()?.execute(
+                "INSERT INTO seen (id, seen_at) VALUES ($1, $2) ON CONFLICT (id) DO NOTHING",
+                &[&id, &scraped_at()],
+            )?;
+            Ok(inserted == 1)
+        })
+    }
+}
@@ -0,0 +1,674 @@
+//! [`Storage`] in a SQLite file, for running on one machine.
+
+use super::{scraped_at, unapplied, KeptMessage, Migration, Storage};
+use crate::{EmailThread, EmailThreadDetail, MailingList, ThreadAttachment};
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// the migrations of a SQLite database, the first one creating the tables of the databases kept
+/// before there were migrations when missing
+const MIGRATIONS: &[Migration] = &[Migration {
+    version: 1,
+    description: "threads, messages with their search index, cursors, backfills and seen ids",
+    sql: "
+CREATE TABLE IF NOT EXISTS threads (
+    id TEXT PRIMARY KEY,
+    list TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    author TEXT NOT NULL,
+    datetime TEXT NOT NULL,
+    reply_count INTEGER,
+    scraped_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS threads_by_datetime ON threads (list, datetime);
+CREATE TABLE IF NOT EXISTS messages (
+    id TEXT PRIMARY KEY,
+    list TEXT NOT NULL,
+    subject TEXT NOT NULL,
+    datetime TEXT NOT NULL,
+    author_name TEXT NOT NULL,
+    author_email TEXT NOT NULL,
+    content TEXT NOT NULL,
+    content_missing INTEGER NOT NULL,
+    headers TEXT NOT NULL,
+    scraped_at TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS messages_by_datetime ON messages (list, datetime);
+CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5 (
+    subject, author_name, author_email, content, content = 'messages'
+);
+CREATE TRIGGER IF NOT EXISTS messages_fts_insert AFTER INSERT ON messages BEGIN
+    INSERT INTO messages_fts (rowid, subject, author_name, author_email, content)
+    VALUES (new.rowid, new.subject, new.author_name, new.author_email, new.content);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_delete AFTER DELETE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, subject, author_name, author_email, content)
+    VALUES ('delete', old.rowid, old.subject, old.author_name, old.author_email, old.content);
+END;
+CREATE TRIGGER IF NOT EXISTS messages_fts_update AFTER UPDATE ON messages BEGIN
+    INSERT INTO messages_fts (messages_fts, rowid, subject, author_name, author_email, content)
+    VALUES ('delete', old.rowid, old.subject, old.author_name, old.author_email, old.content);
+    INSERT INTO messages_fts (rowid, subject, author_name, author_email, content)
+    VALUES (new.rowid, new.subject, new.author_name, new.author_email, new.content);
+END;
+CREATE TABLE IF NOT EXISTS attachments (
+    message_id TEXT NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    href TEXT NOT NULL,
+    size INTEGER,
+    PRIMARY KEY (message_id, position)
+);
+CREATE TABLE IF NOT EXISTS replies (
+    message_id TEXT NOT NULL REFERENCES messages (id) ON DELETE CASCADE,
+    position INTEGER NOT NULL,
+    reply_id TEXT NOT NULL,
+    PRIMARY KEY (message_id, position)
+);
+CREATE TABLE IF NOT EXISTS cursors (
+    list TEXT PRIMARY KEY,
+    since TEXT NOT NULL,
+    synced_at TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS backfills (
+    list TEXT NOT NULL,
+    since TEXT NOT NULL,
+    until TEXT NOT NULL,
+    backfilled_at TEXT NOT NULL,
+    PRIMARY KEY (list, since)
+);
+CREATE TABLE IF NOT EXISTS seen (
+    id TEXT PRIMARY KEY,
+    seen_at TEXT NOT NULL
+);
+",
+}];
+
+/// the versions applied to the database of `conn`, none when it has not been migrated
+fn applied_versions(conn: &Connection) -> Result<Vec<i64>> {
+    let migrated: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM sqlite_master
+                        WHERE type = 'table' AND name = 'schema_migrations')",
+        [],
+        |row| row.get(0),
+    )?;
+    if !migrated {
+        return Ok(Vec::new());
+    }
+    let mut stmt = conn.prepare("SELECT version FROM schema_migrations ORDER BY version")?;
+    let versions = stmt.query_map([], |row| row.get(0))?;
+    Ok(versions.collect::<Result<_, _>>()?)
+}
+
+/// apply the `migrations` the database of `conn` has not been through, each in a transaction
+fn migrate(conn: &mut Connection, migrations: &[Migration]) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+             version INTEGER PRIMARY KEY,
+             description TEXT NOT NULL,
+             applied_at TEXT NOT NULL
+         );",
+    )?;
+    let applied = applied_versions(conn)?;
+    for migration in unapplied(migrations, &applied)? {
+        let tx = conn.transaction()?;
+        tx.execute_batch(migration.sql)
+            .with_context(|| format!("failed to migrate to version {}", migration.version))?;
+        tx.execute(
+            "INSERT INTO schema_migrations (version, description, applied_at) VALUES (?1, ?2, ?3)",
+            params![migration.version, migration.description, scraped_at()],
+        )?;
+        tx.commit()?;
+        tracing::info!(
+            "migrated the database to version {}: {}",
+            migration.version,
+            migration.description
+        );
+    }
+    Ok(())
+}
+
+/// the migrations opening the SQLite file at `path` would apply, all of them when it is missing
+pub(super) fn pending_migrations(path: &Path) -> Result<Vec<&'static Migration>> {
+    if path == Path::new(":memory:") || !path.exists() {
+        return unapplied(MIGRATIONS, &[]);
+    }
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("failed to open the database {}", path.display()))?;
+    unapplied(MIGRATIONS, &applied_versions(&conn)?)
+}
+
+/// A SQLite database of scraped threads and messages, shared by the threads of a run.
+#[derive(Debug)]
+pub struct Sqlite {
+    conn: Mutex<Connection>,
+}
+
+impl Sqlite {
+    /// the database at `path`, created with its tables when missing and migrated otherwise
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = Connection::open(path)
+            .with_context(|| format!("failed to open the database {}", path.display()))?;
+        Sqlite::with_connection(conn)
+    }
+
+    /// a database that lives as long as the store, for tests
+    pub fn open_in_memory() -> Result<Self> {
+        Sqlite::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(mut conn: Connection) -> Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        migrate(&mut conn, MIGRATIONS)?;
+        Ok(Sqlite {
+            conn: Mutex::new(conn),
+        })
+    }
+}
+
+impl Storage for Sqlite {
+    fn upsert_thread(&self, thread: &EmailThread) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO threads (id, list, subject, author, datetime, reply_count, scraped_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+             ON CONFLICT (id) DO UPDATE SET
+                 list = excluded.list,
+                 subject = excluded.subject,
+                 author = excluded.author,
+                 datetime = excluded.datetime,
+                 reply_count = COALESCE(excluded.reply_count, threads.reply_count),
+                 scraped_at = excluded.scraped_at",
+            params![
+                thread.id,
+                thread.list.name(),
+                thread.subject,
+                thread.author,
+                thread.datetime,
+                thread.reply_count.map(|count| count as i64),
+                scraped_at(),
+            ],
+        )?;
+        Ok(())
+    }
+
+    fn upsert_message(&self, list: MailingList, detail: &EmailThreadDetail) -> Result<()> {
+        let mut conn = self.conn.lock().unwrap();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT INTO messages (id, list, subject, datetime, author_name, author_email, content,
+                                   content_missing, headers, scraped_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+             ON CONFLICT (id) DO UPDATE SET
+                 list = excluded.list,
+                 subject = excluded.subject,
+                 datetime = excluded.datetime,
+                 author_name = excluded.author_name,
+                 author_email = excluded.author_email,
+                 content = excluded.content,
+                 content_missing = excluded.content_missing,
+                 headers = excluded.headers,
+                 scraped_at = excluded.scraped_at",
+            params![
+                detail.id,
+                list.name(),
+                detail.subject,
+                detail.datetime,
+                detail.author_name,
+                detail.author_email,
+                detail.content,
+                detail.content_missing,
+                serde_json::to_string(&detail.headers)?,
+                scraped_at(),
+            ],
+        )?;
+        tx.execute(
+            "DELETE FROM attachments WHERE message_id = ?1",
+            [&detail.id],
+        )?;
+        tx.execute("DELETE FROM replies WHERE message_id = ?1", [&detail.id])?;
+        for (position, attachment) in detail.attachments.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO attachments (message_id, position, name, href, size)
+                 VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![
+                    detail.id,
+                    position,
+                    attachment.name,
+                    attachment.href,
+                    attachment.size.map(|size| size as i64),
+                ],
+            )?;
+        }
+        for (position, reply_id) in detail.replies.iter().enumerate() {
+            tx.execute(
+                "INSERT INTO replies (message_id, position, reply_id) VALUES (?1, ?2, ?3)",
+                params![detail.id, position, reply_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn threads_between(
+        &self,
+        list: MailingList,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT id, subject, author, datetime, reply_count FROM threads
+             WHERE list = ?1 AND datetime BETWEEN ?2 AND ?3
+             ORDER BY datetime, id",
+        )?;
+        let threads = statement
+            .query_map(params![list.name(), start, end], |row| {
+                Ok(EmailThread {
+                    id: row.get(0)?,
+                    list,
+                    subject: row.get(1)?,
+                    author: row.get(2)?,
+                    datetime: row.get(3)?,
+                    reply_count: row.get::<_, Option<i64>>(4)?.map(|count| count as usize),
+                    replies: None,
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(threads)
+    }
+
+    fn message(&self, id: &str) -> Result<Option<EmailThreadDetail>> {
+        let conn = self.conn.lock().unwrap();
+        let message = conn
+            .query_row(
+                "SELECT subject, datetime, author_name, author_email, content, content_missing,
+                        headers
+                 FROM messages WHERE id = ?1",
+                [id],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, NaiveDateTime>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, String>(4)?,
+                        row.get::<_, bool>(5)?,
+                        row.get::<_, String>(6)?,
+                    ))
+                },
+            )
+            .optional()?;
+        let Some((subject, datetime, author_name, author_email, content, content_missing, headers)) =
+            message
+        else {
+            return Ok(None);
+        };
+
+        let attachments = conn
+            .prepare(
+                "SELECT name, href, size FROM attachments WHERE message_id = ?1 ORDER BY position",
+            )?
+            .query_map([id], |row| {
+                Ok(ThreadAttachment {
+                    name: row.get(0)?,
+                    href: row.get(1)?,
+                    size: row.get::<_, Option<i64>>(2)?.map(|size| size as u64),
+                })
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        let replies = conn
+            .prepare("SELECT reply_id FROM replies WHERE message_id = ?1 ORDER BY position")?
+            .query_map([id], |row| row.get(0))?
+            .collect::<rusqlite::Result<_>>()?;
+        KeptMessage {
+            id: id.to_string(),
+            subject,
+            datetime,
+            author_name,
+            author_email,
+            content,
+            content_missing,
+            headers,
+            attachments,
+            replies,
+        }
+        .into_detail()
+        .map(Some)
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        list: Option<MailingList>,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        let conn = self.conn.lock().unwrap();
+        let mut statement = conn.prepare(
+            "SELECT messages.id, messages.list, messages.subject, messages.author_name,
+                    messages.datetime
+             FROM messages_fts JOIN messages ON messages.rowid = messages_fts.rowid
+             WHERE messages_fts MATCH ?1 AND messages.datetime BETWEEN ?2 AND ?3
+                 AND (?4 IS NULL OR messages.list = ?4)
+             ORDER BY bm25(messages_fts, 4.0, 1.0, 1.0, 1.0), messages.datetime, messages.id",
+        )?;
+        let rows = statement
+            .query_map(
+                params![query, start, end, list.map(MailingList::name)],
+                |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                        row.get::<_, NaiveDateTime>(4)?,
+                    ))
+                },
+            )?
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .with_context(|| format!("failed to search for '{query}'"))?;
+        rows.into_iter()
+            .map(|(id, list, subject, author, datetime)| {
+                Ok(EmailThread {
+                    id,
+                    list: list.parse()?,
+                    subject,
+                    author,
+                    datetime,
+                    reply_count: None,
+                    replies: None,
+                })
+            })
+            .collect()
+    }
+
+    fn cursor(&self, list: MailingList) -> Result<Option<NaiveDateTime>> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT since FROM cursors WHERE list = ?1",
+                [list.name()],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn set_cursor(&self, list: MailingList, since: NaiveDateTime) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO cursors (list, since, synced_at) VALUES (?1, ?2, ?3)
+             ON CONFLICT (list) DO UPDATE SET
+                 since = excluded.since,
+                 synced_at = excluded.synced_at",
+            params![list.name(), since, scraped_at()],
+        )?;
+        Ok(())
+    }
+
+    fn backfilled(&self, list: MailingList, since: NaiveDateTime) -> Result<Option<NaiveDateTime>> {
+        Ok(self
+            .conn
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT until FROM backfills WHERE list = ?1 AND since = ?2",
+                params![list.name(), since],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    fn set_backfilled(
+        &self,
+        list: MailingList,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> Result<()> {
+        self.conn.lock().unwrap().execute(
+            "INSERT INTO backfills (list, since, until, backfilled_at) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (list, since) DO UPDATE SET
+                 until = excluded.until,
+                 backfilled_at = excluded.backfilled_at",
+            params![list.name(), since, until, scraped_at()],
+        )?;
+        Ok(())
+    }
+
+    fn mark_seen(&self, id: &str) -> Result<bool> {
+        let inserted = self.conn.lock().unwrap().execute(
+            "INSERT INTO seen (id, seen_at) VALUES (?1, ?2) ON CONFLICT (id) DO NOTHING",
+            params![id, scraped_at()],
+        )?;
+        Ok(inserted == 1)
+    }
+}
+
+#[tokio::test]
+async fn scraped_rows_are_upserted_and_read_back() {
+    use crate::mock_server::{MessagePage, MockResponse, MockServer};
+    use crate::sink::ThreadSink;
+    use crate::{get_thread_by_id, Fetcher, MessageId};
+    use chrono::NaiveDate;
+    use std::sync::Arc;
+
+    let store: Arc<dyn Storage> = Arc::new(Sqlite::open_in_memory().unwrap());
+    let day = NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let mut thread = EmailThread {
+        id: "a%40x".to_string(),
+        list: MailingList::Hackers,
+        subject: "Topic a".to_string(),
+        datetime: day.and_hms_opt(9, 0, 0).unwrap(),
+        author: "Alice".to_string(),
+        reply_count: Some(2),
+        replies: None,
+    };
+    store.upsert_thread(&thread).unwrap();
+    thread.subject = "Topic a, v2".to_string();
+    thread.reply_count = None;
+    (&*store).write(&thread).unwrap();
+
+    let kept = store
+        .threads_between(
+            MailingList::Hackers,
+            day.into(),
+            day.and_hms_opt(23, 59, 59).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(kept.len(), 1);
+    assert_eq!(kept[0].subject, "Topic a, v2");
+    assert_eq!(kept[0].reply_count, Some(2));
+    assert!(store
+        .threads_between(
+            MailingList::Bugs,
+            day.into(),
+            day.and_hms_opt(23, 59, 59).unwrap()
+        )
+        .unwrap()
+        .is_empty());
+
+    let server = MockServer::start(|req| {
+        if req.path.starts_with("/message-id/raw/") {
+            return MockResponse::new(200, "text/plain", "Subject: Topic a\r\n\r\nbody\r\n");
+        }
+        let id = req.path.trim_start_matches("/message-id/");
+        let mut page = MessagePage::new(id).thread(&["a%40x", "b%40x"]);
+        page.attachments = vec![(
+            "v1-0001.patch".to_string(),
+            "/message-id/attachment/1/v1-0001.patch".to_string(),
+            "1 KB".to_string(),
+        )];
+        MockResponse::html(page.render())
+    });
+    let fetcher = Fetcher::new(&server.url()).with_store(store.clone());
+    let fetched = get_thread_by_id(&fetcher, &MessageId::parse("a%40x").unwrap())
+        .await
+        .unwrap();
+
+    let kept = store.message("a%40x").unwrap().unwrap();
+    assert_eq!(kept.subject, fetched.subject);
+    assert_eq!(kept.datetime, fetched.datetime);
+    assert_eq!(kept.content, fetched.content);
+    assert_eq!(kept.replies, fetched.replies);
+    let attachment = |attachment: &ThreadAttachment| {
+        (
+            attachment.name.clone(),
+            attachment.href.clone(),
+            attachment.size,
+        )
+    };
+    assert_eq!(
+        kept.attachments.iter().map(attachment).collect::<Vec<_>>(),
+        fetched
+            .attachments
+            .iter()
+            .map(attachment)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(kept.attachments.len(), 1);
+    assert_eq!(kept.headers, fetched.headers);
+    assert!(store.message("b%40x").unwrap().is_none());
+}
+
+#[test]
+fn messages_are_searched_by_their_words() {
+    use chrono::NaiveDate;
+
+    let store = Sqlite::open_in_memory().unwrap();
+    let day = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+    let message = |id: &str, day: NaiveDate, subject: &str, content: &str| {
+        KeptMessage {
+            id: id.to_string(),
+            subject: subject.to_string(),
+            datetime: day.and_hms_opt(9, 0, 0).unwrap(),
+            author_name: "Alice".to_string(),
+            author_email: "alice@example.org".to_string(),
+            content: content.to_string(),
+            content_missing: false,
+            headers: "{}".to_string(),
+            attachments: Vec::new(),
+            replies: Vec::new(),
+        }
+        .into_detail()
+        .unwrap()
+    };
+    let kept = [
+        (
+            MailingList::Hackers,
+            message("a%40x", day(3), "AIO", "uses io_uring for reads"),
+        ),
+        (
+            MailingList::Hackers,
+            message("b%40x", day(5), "Vacuum", "nothing about it"),
+        ),
+        (
+            MailingList::Bugs,
+            message("c%40x", day(7), "io_uring crash", "it crashed"),
+        ),
+    ];
+    for (list, detail) in &kept {
+        store.upsert_message(*list, detail).unwrap();
+    }
+    let search = |query: &str, list, start, end| -> Vec<String> {
+        store
+            .search(query, list, start, end)
+            .unwrap()
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect()
+    };
+    let (start, end) = (day(1).into(), day(31).into());
+
+    assert_eq!(search("io_uring", None, start, end), ["c%40x", "a%40x"]);
+    assert_eq!(
+        search("io_uring", Some(MailingList::Bugs), start, end),
+        ["c%40x"]
+    );
+    assert_eq!(search("io_uring", None, start, day(4).into()), ["a%40x"]);
+    assert_eq!(search("alice", None, start, end).len(), 3);
+
+    // a message scraped again is indexed by what it says now
+    let detail = message("a%40x", day(3), "AIO", "uses plain reads");
+    store.upsert_message(MailingList::Hackers, &detail).unwrap();
+    assert_eq!(search("io_uring", None, start, end), ["c%40x"]);
+    assert_eq!(search("plain", None, start, end), ["a%40x"]);
+}
+
+#[test]
+fn databases_are_migrated_once_and_keep_their_rows() {
+    let dir = std::env::temp_dir().join(format!("pgdevhub-migrations-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("kept.db");
+    let versions = |migrations: Vec<&Migration>| -> Vec<i64> {
+        migrations
+            .iter()
+            .map(|migration| migration.version)
+            .collect()
+    };
+    assert_eq!(versions(pending_migrations(&path).unwrap()), [1]);
+    assert!(!path.exists());
+
+    // a database kept before there were migrations
+    let conn = Connection::open(&path).unwrap();
+    conn.execute_batch(
+        "CREATE TABLE threads (
+             id TEXT PRIMARY KEY, list TEXT NOT NULL, subject TEXT NOT NULL,
+             author TEXT NOT NULL, datetime TEXT NOT NULL, reply_count INTEGER,
+             scraped_at TEXT NOT NULL
+         );
+         INSERT INTO threads VALUES ('a%40x', 'pgsql-hackers', 'Topic a', 'Alice',
+                                     '2025-01-18 09:00:00', 2, '2025-01-18 10:00:00');",
+    )
+    .unwrap();
+    drop(conn);
+    assert_eq!(versions(pending_migrations(&path).unwrap()), [1]);
+    let store = Sqlite::open(&path).unwrap();
+    assert!(pending_migrations(&path).unwrap().is_empty());
+    let day = chrono::NaiveDate::from_ymd_opt(2025, 1, 18).unwrap();
+    let kept = store
+        .threads_between(
+            MailingList::Hackers,
+            day.into(),
+            day.and_hms_opt(23, 0, 0).unwrap(),
+        )
+        .unwrap();
+    assert_eq!(kept[0].reply_count, Some(2));
+    drop(store);
+
+    // a later version adds to the tables
+    let later = [
+        Migration {
+            version: 1,
+            description: MIGRATIONS[0].description,
+            sql: MIGRATIONS[0].sql,
+        },
+        Migration {
+            version: 2,
+            description: "thread tags",
+            sql: "ALTER TABLE threads ADD COLUMN tags TEXT;",
+        },
+    ];
+    let mut conn = Connection::open(&path).unwrap();
+    assert_eq!(
+        versions(unapplied(&later, &applied_versions(&conn).unwrap()).unwrap()),
+        [2]
+    );
+    migrate(&mut conn, &later).unwrap();
+    migrate(&mut conn, &later).unwrap();
+    assert_eq!(applied_versions(&conn).unwrap(), [1, 2]);
+    let tags: Option<String> = conn
+        .query_row("SELECT tags FROM threads WHERE id = 'a%40x'", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(tags, None);
+    drop(conn);
+
+    // which this version does not know of
+    let err = Sqlite::open(&path).unwrap_err();
+    assert!(err.to_string().contains("newer pgdevhub"), "{err}");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
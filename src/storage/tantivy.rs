@@ -0,0 +1,310 @@
+//! [`Storage::search`] through a tantivy index kept next to another storage, for databases of
+//! hundreds of thousands of messages.
+
+use super::Storage;
+use crate::{EmailThread, EmailThreadDetail, MailingList};
+use anyhow::{Context, Result};
+use chrono::NaiveDateTime;
+use std::ops::Bound;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tantivy::collector::{Count, TopDocs};
+use tantivy::directory::MmapDirectory;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, RangeQuery, TermQuery};
+use tantivy::schema::{
+    Field, IndexRecordOption, Schema, Value, FAST, INDEXED, STORED, STRING, TEXT,
+};
+use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy, TantivyDocument, Term};
+
+/// memory the writer may fill before it writes a segment
+const WRITER_HEAP: usize = 50_000_000;
+
+/// how much more a word in the subject counts than one in the content or the author
+const SUBJECT_BOOST: f32 = 4.0;
+
+struct Fields {
+    id: Field,
+    list: Field,
+    subject: Field,
+    author: Field,
+    author_name: Field,
+    content: Field,
+    datetime: Field,
+}
+
+/// Another storage with its messages also indexed by tantivy, which answers
+/// [`search`](Storage::search). Everything else goes to the storage wrapped.
+pub struct Tantivy {
+    storage: Arc<dyn Storage>,
+    index: Index,
+    fields: Fields,
+    writer: Mutex<IndexWriter>,
+    reader: IndexReader,
+}
+
+impl std::fmt::Debug for Tantivy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Tantivy")
+            .field("storage", &self.storage)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Tantivy {
+    /// `storage` with the index in the directory `dir`, created when missing. only messages
+    /// kept from now on are indexed
+    pub fn open(dir: &Path, storage: Arc<dyn Storage>) -> Result<Self> {
+        let mut schema = Schema::builder();
+        let fields = Fields {
+            id: schema.add_text_field("id", STRING | STORED),
+            list: schema.add_text_field("list", STRING | STORED),
+            subject: schema.add_text_field("subject", TEXT | STORED),
+            author: schema.add_text_field("author", TEXT),
+            author_name: schema.add_text_field("author_name", STORED),
+            content: schema.add_text_field("content", TEXT),
+            datetime: schema.add_date_field("datetime", INDEXED | STORED | FAST),
+        };
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("failed to create the search index {}", dir.display()))?;
+        let index = Index::open_or_create(MmapDirectory::open(dir)?, schema.build())
+            .with_context(|| format!("failed to open the search index {}", dir.display()))?;
+        let writer = index.writer(WRITER_HEAP)?;
+        let reader = index
+            .reader_builder()
+            .reload_policy(ReloadPolicy::Manual)
+            .try_into()?;
+        Ok(Tantivy {
+            storage,
+            index,
+            fields,
+            writer: Mutex::new(writer),
+            reader,
+        })
+    }
+
+    fn index_message(&self, list: MailingList, detail: &EmailThreadDetail) -> Result<()> {
+        let fields = &self.fields;
+        let mut writer = self.writer.lock().unwrap();
+        writer.delete_term(Term::from_field_text(fields.id, &detail.id));
+        writer.add_document(doc!(
+            fields.id => detail.id.as_str(),
+            fields.list => list.name(),
+            fields.subject => detail.subject.as_str(),
+            fields.author => format!("{} {}", detail.author_name, detail.author_email),
+            fields.author_name => detail.author_name.as_str(),
+            fields.content => detail.content.as_str(),
+            fields.datetime => date(detail.datetime),
+        ))?;
+        // messages are kept one page fetch at a time, committing each keeps the index in step
+        // with the storage if the run stops
+        writer.commit()?;
+        Ok(())
+    }
+
+    fn thread(&self, document: &TantivyDocument) -> Result<EmailThread> {
+        let fields = &self.fields;
+        let text = |field| {
+            document
+                .get_first(field)
+                .and_then(|value| value.as_str())
+                .unwrap_or_default()
+                .to_string()
+        };
+        let datetime = document
+            .get_first(fields.datetime)
+            .and_then(|value| value.as_datetime())
+            .context("an indexed message has no datetime")?;
+        Ok(EmailThread {
+            id: text(fields.id),
+            list: text(fields.list).parse()?,
+            subject: text(fields.subject),
+            author: text(fields.author_name),
+            datetime: chrono::DateTime::from_timestamp(datetime.into_timestamp_secs(), 0)
+                .context("an indexed message has a datetime out of range")?
+                .naive_utc(),
+            reply_count: None,
+            replies: None,
+        })
+    }
+}
+
+fn date(datetime: NaiveDateTime) -> tantivy::DateTime {
+    tantivy::DateTime::from_timestamp_secs(datetime.and_utc().timestamp())
+}
+
+impl Storage for Tantivy {
+    fn upsert_thread(&self, thread: &EmailThread) -> Result<()> {
+        self.storage.upsert_thread(thread)
+    }
+
+    fn upsert_message(&self, list: MailingList, detail: &EmailThreadDetail) -> Result<()> {
+        self.storage.upsert_message(list, detail)?;
+        self.index_message(list, detail)
+            .with_context(|| format!("failed to index {}", detail.id))
+    }
+
+    fn threads_between(
+        &self,
+        list: MailingList,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        self.storage.threads_between(list, start, end)
+    }
+
+    fn message(&self, id: &str) -> Result<Option<EmailThreadDetail>> {
+        self.storage.message(id)
+    }
+
+    fn search(
+        &self,
+        query: &str,
+        list: Option<MailingList>,
+        start: NaiveDateTime,
+        end: NaiveDateTime,
+    ) -> Result<Vec<EmailThread>> {
+        let fields = &self.fields;
+        let mut parser = QueryParser::for_index(
+            &self.index,
+            vec![fields.subject, fields.author, fields.content],
+        );
+        parser.set_conjunction_by_default();
+        parser.set_field_boost(fields.subject, SUBJECT_BOOST);
+        let words = parser
+            .parse_query(query)
+            .with_context(|| format!("bad search query '{query}'"))?;
+        let mut clauses: Vec<(Occur, Box<dyn Query>)> = vec![
+            (Occur::Must, words),
+            (
+                Occur::Must,
+                Box::new(RangeQuery::new_date_bounds(
+                    "datetime".to_string(),
+                    Bound::Included(date(start)),
+                    Bound::Included(date(end)),
+                )),
+            ),
+        ];
+        if let Some(list) = list {
+            clauses.push((
+                Occur::Must,
+                Box::new(TermQuery::new(
+                    Term::from_field_text(fields.list, list.name()),
+                    IndexRecordOption::Basic,
+                )),
+            ));
+        }
+        let query = BooleanQuery::new(clauses);
+
+        self.reader.reload()?;
+        let searcher = self.reader.searcher();
+        let count = searcher.search(&query, &Count)?;
+        searcher
+            .search(&query, &TopDocs::with_limit(count.max(1)))?
+            .into_iter()
+            .map(|(_, address)| self.thread(&searcher.doc(address)?))
+            .collect()
+    }
+
+    fn cursor(&self, list: MailingList) -> Result<Option<NaiveDateTime>> {
+        self.storage.cursor(list)
+    }
+
+    fn set_cursor(&self, list: MailingList, since: NaiveDateTime) -> Result<()> {
+        self.storage.set_cursor(list, since)
+    }
+
+    fn backfilled(&self, list: MailingList, since: NaiveDateTime) -> Result<Option<NaiveDateTime>> {
+        self.storage.backfilled(list, since)
+    }
+
+    fn set_backfilled(
+        &self,
+        list: MailingList,
+        since: NaiveDateTime,
+        until: NaiveDateTime,
+    ) -> Result<()> {
+        self.storage.set_backfilled(list, since, until)
+    }
+
+    fn mark_seen(&self, id: &str) -> Result<bool> {
+        self.storage.mark_seen(id)
+    }
+}
+
+#[test]
+fn subject_matches_rank_first() {
+    use super::{KeptMessage, Sqlite};
+    use chrono::NaiveDate;
+
+    let dir = std::env::temp_dir().join(format!("pgdevhub-tantivy-{}", std::process::id()));
+    let store = Tantivy::open(&dir, Arc::new(Sqlite::open_in_memory().unwrap())).unwrap();
+    let day = |day| NaiveDate::from_ymd_opt(2025, 1, day).unwrap();
+    let message = |id: &str, day: NaiveDate, subject: &str, content: &str| {
+        KeptMessage {
+            id: id.to_string(),
+            subject: subject.to_string(),
+            datetime: day.and_hms_opt(9, 0, 0).unwrap(),
+            author_name: "Alice Doe".to_string(),
+            author_email: "alice@example.org".to_string(),
+            content: content.to_string(),
+            content_missing: false,
+            headers: "{}".to_string(),
+            attachments: Vec::new(),
+            replies: Vec::new(),
+        }
+        .into_detail()
+        .unwrap()
+    };
+    let kept = [
+        (
+            MailingList::Hackers,
+            message("a%40x", day(3), "AIO", "uses io_uring for reads"),
+        ),
+        (
+            MailingList::Hackers,
+            message("b%40x", day(5), "Vacuum", "async reads later"),
+        ),
+        (
+            MailingList::Bugs,
+            message("c%40x", day(7), "io_uring crash", "it crashed"),
+        ),
+    ];
+    for (list, detail) in &kept {
+        store.upsert_message(*list, detail).unwrap();
+    }
+    let search = |query: &str, list, start, end| -> Vec<String> {
+        store
+            .search(query, list, start, end)
+            .unwrap()
+            .into_iter()
+            .map(|thread| thread.id)
+            .collect()
+    };
+    let (start, end) = (day(1).into(), day(31).into());
+
+    assert_eq!(search("io_uring", None, start, end), ["c%40x", "a%40x"]);
+    assert_eq!(
+        search("io_uring", Some(MailingList::Hackers), start, end),
+        ["a%40x"]
+    );
+    assert_eq!(search("io_uring", None, start, day(4).into()), ["a%40x"]);
+    assert_eq!(
+        search("\"io_uring for reads\"", None, start, end),
+        ["a%40x"]
+    );
+    assert!(search("\"reads for io_uring\"", None, start, end).is_empty());
+
+    let found = store.search("crash", None, start, end).unwrap();
+    assert_eq!(found[0].author, "Alice Doe");
+    assert_eq!(found[0].list, MailingList::Bugs);
+    assert_eq!(found[0].datetime, day(7).and_hms_opt(9, 0, 0).unwrap());
+    // kept in the storage wrapped too
+    assert!(store.message("c%40x").unwrap().is_some());
+
+    // a message kept again replaces what was indexed of it
+    let detail = message("a%40x", day(3), "AIO", "uses plain reads");
+    store.upsert_message(MailingList::Hackers, &detail).unwrap();
+    assert_eq!(search("io_uring", None, start, end), ["c%40x"]);
+    drop(store);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
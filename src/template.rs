@@ -0,0 +1,126 @@
+//! User-supplied output templates for the CLI, like `{datetime}\t{author}\t{subject}`.
+
+use crate::EmailThread;
+use anyhow::{bail, Result};
+
+const FIELDS: [&str; 5] = ["subject", "author", "datetime", "url", "id"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Literal(String),
+    Field(&'static str),
+}
+
+/// a parsed output template. `{name}` is replaced by one of the thread fields in [`FIELDS`],
+/// `{{` and `}}` stand for literal braces, and `\n` and `\t` for a newline and a tab.
+#[derive(Debug, Clone)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+impl std::str::FromStr for Template {
+    type Err = anyhow::Error;
+
+    fn from_str(template: &str) -> Result<Self> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let Some((name, rest)) = chars.as_str().split_once('}') else {
+                        bail!("unclosed '{{' in template '{template}'");
+                    };
+                    let Some(field) = FIELDS.iter().find(|field| **field == name) else {
+                        bail!(
+                            "unknown placeholder {{{name}}} in template '{template}', \
+                             expected one of {{{}}}",
+                            FIELDS.join("}, {")
+                        );
+                    };
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(Part::Field(field));
+                    chars = rest.chars();
+                }
+                '}' => bail!("unmatched '}}' in template '{template}', write '}}}}' for a brace"),
+                '\\' if chars.as_str().starts_with(['n', 't']) => {
+                    literal.push(if chars.next() == Some('n') {
+                        '\n'
+                    } else {
+                        '\t'
+                    });
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+        Ok(Template { parts })
+    }
+}
+
+impl Template {
+    /// `thread` as this template says, `url` being where it can be read
+    pub fn render(&self, thread: &EmailThread, url: &str) -> String {
+        let mut rendered = String::new();
+        for part in &self.parts {
+            match part {
+                Part::Literal(literal) => rendered.push_str(literal),
+                Part::Field("subject") => rendered.push_str(&thread.subject),
+                Part::Field("author") => rendered.push_str(&thread.author),
+                Part::Field("datetime") => {
+                    rendered.push_str(&thread.datetime.format("%Y-%m-%d %H:%M:%S").to_string())
+                }
+                Part::Field("url") => rendered.push_str(url),
+                Part::Field("id") => rendered.push_str(&thread.id),
+                Part::Field(field) => unreachable!("placeholder {{{field}}} was validated"),
+            }
+        }
+        rendered
+    }
+}
+
+#[test]
+fn render_custom_template() {
+    use crate::MailingList;
+    use chrono::NaiveDate;
+
+    let thread = EmailThread {
+        id: "abc@example.org".to_string(),
+        list: MailingList::default(),
+        subject: "Add a GUC".to_string(),
+        datetime: NaiveDate::from_ymd_opt(2025, 1, 18)
+            .unwrap()
+            .and_hms_opt(9, 30, 0)
+            .unwrap(),
+        author: "Alice".to_string(),
+        reply_count: None,
+        replies: None,
+    };
+    let url = "https://www.postgresql.org/message-id/abc@example.org";
+
+    let template: Template = "{datetime}\\t{author}\\t{subject} <{url}> {{{id}}}"
+        .parse()
+        .unwrap();
+    assert_eq!(
+        template.render(&thread, url),
+        "2025-01-18 09:30:00\tAlice\tAdd a GUC \
+         <https://www.postgresql.org/message-id/abc@example.org> {abc@example.org}"
+    );
+
+    let err = "{subject} by {sender}".parse::<Template>().unwrap_err();
+    assert!(err.to_string().contains("unknown placeholder {sender}"));
+    assert!("{subject".parse::<Template>().is_err());
+    assert!("subject}".parse::<Template>().is_err());
+}